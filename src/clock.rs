@@ -0,0 +1,225 @@
+use specs::prelude::*;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::locale::tr_args;
+use super::widgets::{blend, Color};
+
+/// Tick length of every speed level relative to normal speed, slowest first.
+pub const SPEED_LEVELS: [f32; 5] = [2.0, 1.0, 0.5, 0.25, 0.1];
+const DEFAULT_SPEED: usize = 1;
+
+/// One tick is one in-game minute, and a new game starts in the morning.
+pub const MINUTES_PER_DAY: u64 = 24 * 60;
+const START_MINUTE: u64 = 8 * 60;
+
+/// Minutes after midnight when the sky starts to lighten, the sun is fully up, it starts to set and it is gone.
+const DAWN: u64 = 5 * 60;
+const SUNRISE: u64 = 7 * 60;
+const SUNSET: u64 = 19 * 60;
+const DUSK: u64 = 21 * 60;
+
+const NIGHT_LIGHT: Color = (70, 80, 130);
+const TWILIGHT_LIGHT: Color = (230, 150, 120);
+const DAY_LIGHT: Color = (255, 255, 255);
+
+pub struct GameSpeed {
+    pub level: usize,
+    pub paused: bool,
+    /// Frames per tick at normal speed.
+    pub tick_size: u32,
+    frame: u32
+}
+
+impl GameSpeed {
+    pub fn new(tick_size: u32) -> GameSpeed {
+        GameSpeed { level: DEFAULT_SPEED, paused: false, tick_size, frame: 0 }
+    }
+
+    pub fn frames_per_tick(&self) -> u32 {
+        ((self.tick_size as f32 * SPEED_LEVELS[self.level]).round() as u32).max(1)
+    }
+
+    pub fn faster(&mut self) {
+        if self.level + 1 < SPEED_LEVELS.len() {
+            self.level += 1;
+        }
+    }
+
+    pub fn slower(&mut self) {
+        if self.level > 0 {
+            self.level -= 1;
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Advances one rendered frame and tells whether a simulation tick is due.
+    pub fn advance_frame(&mut self) -> bool {
+        if self.paused {
+            return false;
+        }
+
+        self.frame += 1;
+        if self.frame >= self.frames_per_tick() {
+            self.frame = 0;
+            return true;
+        }
+        false
+    }
+
+    /// How far up the speed levels the game runs, for the speed gauge.
+    pub fn level_fraction(&self) -> f32 {
+        (self.level + 1) as f32 / SPEED_LEVELS.len() as f32
+    }
+}
+
+#[derive(Default)]
+pub struct GameClock {
+    pub tick: u64
+}
+
+impl GameClock {
+    fn minutes(&self) -> u64 {
+        START_MINUTE + self.tick
+    }
+
+    pub fn day(&self) -> u64 {
+        self.minutes() / MINUTES_PER_DAY + 1
+    }
+
+    pub fn hour(&self) -> u64 {
+        self.minutes() % MINUTES_PER_DAY / 60
+    }
+
+    pub fn minute(&self) -> u64 {
+        self.minutes() % 60
+    }
+
+    /// How far the sun is up, from 0 at night to 1 by day.
+    pub fn daylight(&self) -> f32 {
+        let minute = self.minutes() % MINUTES_PER_DAY;
+        if !(DAWN..DUSK).contains(&minute) {
+            0.
+        } else if minute < SUNRISE {
+            (minute - DAWN) as f32 / (SUNRISE - DAWN) as f32
+        } else if minute < SUNSET {
+            1.
+        } else {
+            (DUSK - minute) as f32 / (DUSK - SUNSET) as f32
+        }
+    }
+
+    /// Light falling on the whole map, blue at night and red at dawn and dusk.
+    pub fn ambient_light(&self) -> Color {
+        let daylight = self.daylight();
+        if daylight < 0.5 {
+            blend(NIGHT_LIGHT, TWILIGHT_LIGHT, daylight * 2.)
+        } else {
+            blend(TWILIGHT_LIGHT, DAY_LIGHT, daylight * 2. - 1.)
+        }
+    }
+
+    pub fn get_description(&self) -> String {
+        tr_args("clock.time", &[("day", self.day().to_string()),
+                                ("hour", format!("{:02}", self.hour())),
+                                ("minute", format!("{:02}", self.minute()))])
+    }
+}
+
+pub struct ClockSystem {}
+
+impl<'a> System<'a> for ClockSystem {
+    type SystemData = Write<'a, GameClock>;
+
+    fn run(&mut self, mut clock: Self::SystemData) {
+        clock.tick += 1;
+    }
+}
+
+/// Wall clock time in UTC as `YYYYMMDD-HHMMSS`, for file names.
+pub fn file_timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let (days, rest) = (secs / 86400, secs % 86400);
+
+    // Days since 1970 to a civil date, counting in 400 year eras that start on March 1st
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z % 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day, rest / 3600, rest / 60 % 60, rest % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn file_timestamps_are_utc_dates() {
+        assert_eq!(file_timestamp(UNIX_EPOCH), "19700101-000000");
+        assert_eq!(file_timestamp(UNIX_EPOCH + Duration::from_secs(951782400)), "20000229-000000");
+        assert_eq!(file_timestamp(UNIX_EPOCH + Duration::from_secs(1700000000)), "20231114-221320");
+    }
+
+    #[test]
+    fn speed_levels_are_clamped() {
+        let mut speed = GameSpeed::new(10);
+        assert_eq!(speed.frames_per_tick(), 10);
+        speed.slower();
+        speed.slower();
+        assert_eq!(speed.level, 0);
+        assert_eq!(speed.frames_per_tick(), 20);
+        for _ in 0..SPEED_LEVELS.len() + 1 {
+            speed.faster();
+        }
+        assert_eq!(speed.level, SPEED_LEVELS.len() - 1);
+        assert_eq!(speed.frames_per_tick(), 1);
+        assert_eq!(speed.level_fraction(), 1.);
+    }
+
+    #[test]
+    fn frames_add_up_to_ticks() {
+        let mut speed = GameSpeed::new(3);
+        let ticks: Vec<bool> = (0..6).map(|_| speed.advance_frame()).collect();
+        assert_eq!(ticks, vec![false, false, true, false, false, true]);
+
+        // Pausing keeps the frames counted so far
+        speed.advance_frame();
+        speed.toggle_pause();
+        assert!(!speed.advance_frame());
+        assert!(!speed.advance_frame());
+        speed.toggle_pause();
+        assert!(!speed.advance_frame());
+        assert!(speed.advance_frame());
+    }
+
+    #[test]
+    fn clock_starts_in_the_morning() {
+        crate::locale::set_language("en").unwrap();
+        assert_eq!(GameClock::default().get_description(), "Day 1 08:00");
+        assert_eq!(GameClock { tick: 65 }.get_description(), "Day 1 09:05");
+        assert_eq!(GameClock { tick: MINUTES_PER_DAY - START_MINUTE - 1 }.get_description(), "Day 1 23:59");
+        assert_eq!(GameClock { tick: MINUTES_PER_DAY - START_MINUTE }.get_description(), "Day 2 00:00");
+    }
+
+    #[test]
+    fn the_sun_rises_and_sets() {
+        let at = |hour: u64, minute: u64| GameClock { tick: MINUTES_PER_DAY - START_MINUTE + hour * 60 + minute };
+        assert_eq!(at(0, 0).daylight(), 0.);
+        assert_eq!(at(6, 0).daylight(), 0.5);
+        assert_eq!(at(12, 0).daylight(), 1.);
+        assert_eq!(at(20, 30).daylight(), 0.25);
+        assert_eq!(at(23, 0).daylight(), 0.);
+        assert_eq!(at(12, 0).ambient_light(), DAY_LIGHT);
+        assert_eq!(at(6, 0).ambient_light(), TWILIGHT_LIGHT);
+        assert_eq!(at(0, 0).ambient_light(), NIGHT_LIGHT);
+    }
+}
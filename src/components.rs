@@ -1,63 +1,383 @@
 use specs::prelude::*;
-use specs::Component;
+use specs::{Component, ConvertSaveload};
+use specs::saveload::{Marker, ConvertSaveload};
+use std::convert::Infallible as NoError;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
 
-#[derive(Component, Clone, Copy)]
+use super::locale::{tr, tr_args};
+use super::names::NameStyle;
+
+/// What an entity is drawn over or under on the same tile. Later layers are drawn on top.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum RenderLayer { Decal, Building, Item, Corpse, #[default] Unit, Effect, Marker }
+
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct Renderable {
     pub glyph: u32,
-    pub color: (u8, u8, u8)
+    pub color: (u8, u8, u8),
+    /// Fills the tile behind the glyph. Tiles without one show what is below.
+    #[serde(default)]
+    pub background: Option<(u8, u8, u8)>,
+    #[serde(default)]
+    pub layer: RenderLayer,
+    /// Glyphs shown in turn instead of `glyph` while on the map.
+    #[serde(default)]
+    pub frames: Vec<u32>
 }
 
-#[derive(Component)]
+impl Renderable {
+    pub fn new(glyph: u32, color: (u8, u8, u8), layer: RenderLayer) -> Renderable {
+        Renderable { glyph, color, background: None, layer, frames: vec![] }
+    }
+
+    /// Glyph shown at a step of the map animation.
+    pub fn glyph_at(&self, step: u64) -> u32 {
+        if self.frames.is_empty() {
+            self.glyph
+        } else {
+            self.frames[(step % self.frames.len() as u64) as usize]
+        }
+    }
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Position {
     pub x: u32,
     pub y: u32
 }
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Name {
     pub name: String
 }
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Physical {
     pub weight: i32,
     pub size: i32
 }
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct BlocksTile {}
 
-pub enum MaterialType { Logs }
+#[derive(Component)]
+pub struct WantsToMove {
+    pub x: u32,
+    pub y: u32
+}
 
+/// A spell to cast at a tile on the next tick.
 #[derive(Component)]
+pub struct WantsToCast {
+    pub spell: String,
+    pub x: u32,
+    pub y: u32
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum MaterialType { Logs }
+
+impl MaterialType {
+    pub fn get_text(&self) -> String {
+        match self {
+            MaterialType::Logs => tr("material.logs")
+        }
+    }
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Material {
     pub material_type: MaterialType
 }
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Choppable {
     pub chops_into: MaterialType
 }
 
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Building {}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum FactionType { Undead, Villagers, Nature }
+
+impl FactionType {
+    pub fn get_text(&self) -> String {
+        match self {
+            FactionType::Undead => tr("faction.undead"),
+            FactionType::Villagers => tr("faction.villagers"),
+            FactionType::Nature => tr("faction.nature")
+        }
+    }
+
+    /// Color of the faction's dots on the minimap.
+    pub fn get_color(&self) -> (u8, u8, u8) {
+        match self {
+            FactionType::Undead => (170, 60, 220),
+            FactionType::Villagers => (220, 170, 60),
+            FactionType::Nature => (0, 130, 0)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Profession { Farmer, Woodcutter, Priest }
+
+impl Profession {
+    pub fn all() -> [Profession; 3] {
+        [Profession::Farmer, Profession::Woodcutter, Profession::Priest]
+    }
+
+    pub fn get_text(&self) -> String {
+        match self {
+            Profession::Farmer => tr("profession.farmer"),
+            Profession::Woodcutter => tr("profession.woodcutter"),
+            Profession::Priest => tr("profession.priest")
+        }
+    }
+}
+
+/// A living villager. The given name is kept apart from the shown one, so it survives being raised.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Villager {
+    pub given_name: String,
+    pub profession: Profession
+}
+
+impl Villager {
+    /// "Gregor the Farmer"
+    pub fn title(&self) -> String {
+        tr_args("name.villager", &[("name", self.given_name.clone()), ("profession", self.profession.get_text())])
+    }
+
+    /// Name of the undead raised from this villager: "Gregor's Husk"
+    pub fn raised_name(&self) -> String {
+        tr_args("name.raised", &[("name", self.given_name.clone())])
+    }
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Faction {
+    pub faction: FactionType
+}
+
+/// Materials carried by an entity, with amounts.
+#[derive(Component, Serialize, Deserialize, Clone, Default)]
+pub struct Inventory {
+    pub items: Vec<(MaterialType, u32)>
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum StatusEffect { Decaying, Empowered, Slowed, Burning, Feared }
+
+impl StatusEffect {
+    pub fn get_text(&self) -> String {
+        match self {
+            StatusEffect::Decaying => tr("effect.decaying"),
+            StatusEffect::Empowered => tr("effect.empowered"),
+            StatusEffect::Slowed => tr("effect.slowed"),
+            StatusEffect::Burning => tr("effect.burning"),
+            StatusEffect::Feared => tr("effect.feared")
+        }
+    }
+}
+
+/// Active effects with the ticks they have left.
+#[derive(Component, Serialize, Deserialize, Clone, Default)]
+pub struct StatusEffects {
+    pub effects: Vec<(StatusEffect, u32)>
+}
+
+impl StatusEffects {
+    pub fn has(&self, effect: StatusEffect) -> bool {
+        self.effects.iter().any(|(e, _)| *e == effect)
+    }
+
+    /// Gives the effect for at least `ticks` more ticks.
+    pub fn add(&mut self, effect: StatusEffect, ticks: u32) {
+        match self.effects.iter_mut().find(|(e, _)| *e == effect) {
+            Some(entry) => entry.1 = entry.1.max(ticks),
+            None => self.effects.push((effect, ticks))
+        }
+    }
+}
+
+/// The player's avatar, and the caster of spells. The game is lost with it.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Necromancer {
+    pub mana: i32,
+    pub max_mana: i32,
+    /// Ids of the spells it knows.
+    pub spellbook: Vec<String>,
+    /// Tick from which each spell cast before can be cast again.
+    #[serde(default)]
+    pub cooldowns: BTreeMap<String, u64>
+}
+
+impl Necromancer {
+    pub fn new(max_mana: i32, spellbook: Vec<String>) -> Necromancer {
+        Necromancer { mana: max_mana, max_mana, spellbook, cooldowns: BTreeMap::new() }
+    }
+
+    /// Undead it can hold together at once, growing with its power.
+    pub fn upkeep_cap(&self) -> usize {
+        (self.max_mana / 5).max(0) as usize
+    }
+}
+
+/// Remains of a slain villager, for raising or feeding on.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Corpse {
+    pub villager: Option<Villager>
+}
+
+/// Lights the tiles around it. Holy light hurts the undead like sunlight does.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct LightSource {
+    pub radius: u32,
+    pub color: (u8, u8, u8),
+    pub holy: bool
+}
+
+#[derive(Component, Serialize, Deserialize, Clone, Default)]
+pub struct Kills {
+    pub count: u32
+}
+
+#[derive(ConvertSaveload, Clone)]
 pub enum Mission { Stay, GoTo(u32, u32), Chop(Entity) }
 
 impl Mission {
     pub fn get_description(&self) -> String {
         match self {
-            Mission::Stay => "Stay".to_string(),
-            Mission::GoTo(x, y) => format!("GoTo {}:{}", x, y).to_string(),
-            Mission::Chop(entity) => "Chop".to_string()
+            Mission::Stay => tr("mission.stay"),
+            Mission::GoTo(x, y) => tr_args("mission.goto", &[("x", x.to_string()), ("y", y.to_string())]),
+            Mission::Chop(_) => tr("mission.chop")
         }
     }
 }
 
-#[derive(Component)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum UndeadType { Skeleton, Zombie, Wraith }
+
+impl UndeadType {
+    pub fn all() -> [UndeadType; 3] {
+        [UndeadType::Skeleton, UndeadType::Zombie, UndeadType::Wraith]
+    }
+
+    pub fn get_text(&self) -> String {
+        match self {
+            UndeadType::Skeleton => tr("undead.skeleton"),
+            UndeadType::Zombie => tr("undead.zombie"),
+            UndeadType::Wraith => tr("undead.wraith")
+        }
+    }
+
+    pub fn get_glyph(&self) -> u32 {
+        match self {
+            UndeadType::Skeleton => 139,
+            UndeadType::Zombie => 140,
+            UndeadType::Wraith => 141
+        }
+    }
+
+    /// What sunlight does to this kind of undead, if anything.
+    pub fn sunlight_weakness(&self) -> Option<StatusEffect> {
+        match self {
+            UndeadType::Skeleton => None,
+            UndeadType::Zombie => Some(StatusEffect::Slowed),
+            UndeadType::Wraith => Some(StatusEffect::Burning)
+        }
+    }
+
+    /// Integrity a freshly raised undead of this kind has.
+    pub fn max_integrity(&self) -> i32 {
+        match self {
+            UndeadType::Skeleton => 300,
+            UndeadType::Zombie => 200,
+            UndeadType::Wraith => 250
+        }
+    }
+
+    /// Integrity lost every decay interval, before anything speeds it up.
+    pub fn decay_rate(&self) -> i32 {
+        match self {
+            UndeadType::Skeleton => 1,
+            UndeadType::Zombie => 2,
+            UndeadType::Wraith => 1
+        }
+    }
+
+    pub fn name_style(&self) -> NameStyle {
+        match self {
+            UndeadType::Skeleton => NameStyle::Skeleton,
+            UndeadType::Zombie => NameStyle::Zombie,
+            UndeadType::Wraith => NameStyle::Wraith
+        }
+    }
+}
+
+#[derive(Component, ConvertSaveload, Clone)]
 pub struct Unit {
-    pub mission: Mission
+    pub mission: Mission,
+    pub kind: UndeadType
 }
 
-#[derive(Component)]
+/// Missions a unit takes up, in order, once the current one is done.
+#[derive(Component, Clone, Default)]
+pub struct MissionQueue {
+    pub missions: Vec<Mission>
+}
+
+impl<M: Marker + Serialize> ConvertSaveload<M> for MissionQueue where for<'de> M: Deserialize<'de> {
+    type Data = Vec<<Mission as ConvertSaveload<M>>::Data>;
+    type Error = NoError;
+
+    fn convert_into<F>(&self, mut ids: F) -> Result<Self::Data, Self::Error> where F: FnMut(Entity) -> Option<M> {
+        self.missions.iter().map(|mission| mission.convert_into(&mut ids)).collect()
+    }
+
+    fn convert_from<F>(data: Self::Data, mut ids: F) -> Result<Self, Self::Error> where F: FnMut(M) -> Option<Entity> {
+        let missions = data.into_iter().map(|mission| Mission::convert_from(mission, &mut ids)).collect::<Result<_, _>>()?;
+        Ok(MissionQueue { missions })
+    }
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Living {
-    max_health: i32,
-    health: i32
+    pub max_health: i32,
+    pub health: i32
+}
+
+impl Living {
+    pub fn new(max_health: i32) -> Living {
+        Living { max_health, health: max_health }
+    }
+}
+
+/// How well an undead holds together. It rots away over time and falls apart at zero.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Integrity {
+    pub max_integrity: i32,
+    pub integrity: i32
+}
+
+impl Integrity {
+    pub fn new(max_integrity: i32) -> Integrity {
+        Integrity { max_integrity, integrity: max_integrity }
+    }
+}
+
+/// Marks entities that are written to save files.
+pub struct SerializeMe;
+
+/// Carries world resources through a save file. Exists only while saving or loading.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct SerializationHelper {
+    pub version: u32,
+    pub map: super::map::Map,
+    pub tick: u64,
+    #[serde(default)]
+    pub stats: super::rules::GameStats
 }
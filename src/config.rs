@@ -0,0 +1,203 @@
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use super::{TICK_SIZE, tileset::Tileset, input::{Action, InputContext, KeyBinding}, locale::{self, tr, tr_args, Catalog}};
+
+pub const DEFAULT_TILESET: &str = "./resources/16x16-RogueYun-AgmEdit.toml";
+
+pub const TILE_SIZES: [u32; 5] = [16, 24, 32, 48, 64];
+pub const MIN_TICK_SIZE: u32 = 1;
+pub const MAX_TICK_SIZE: u32 = 60;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowMode { FullscreenDesktop, Fullscreen, Windowed }
+
+impl WindowMode {
+    pub fn next(&self) -> WindowMode {
+        match self {
+            WindowMode::FullscreenDesktop => WindowMode::Fullscreen,
+            WindowMode::Fullscreen => WindowMode::Windowed,
+            WindowMode::Windowed => WindowMode::FullscreenDesktop
+        }
+    }
+
+    pub fn prev(&self) -> WindowMode {
+        self.next().next()
+    }
+
+    pub fn get_text(&self) -> String {
+        match self {
+            WindowMode::FullscreenDesktop => tr("window.borderless"),
+            WindowMode::Fullscreen => tr("window.fullscreen"),
+            WindowMode::Windowed => tr("window.windowed")
+        }
+    }
+}
+
+/// User settings, stored as TOML in the user's config directory.
+/// Missing keys fall back to their defaults.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub window_mode: WindowMode,
+    /// Path to the tileset manifest.
+    pub tileset: String,
+    /// Height of a map cell in pixels. Width follows the tileset's glyph proportions.
+    pub tile_size: u32,
+    /// Frames per simulation tick at normal speed.
+    pub tick_size: u32,
+    /// Name of the string catalog in `resources/lang`.
+    pub language: String,
+    /// Key binding overrides: context name to action name to key names, e.g. `[keys.game] order = ["G"]`.
+    pub keys: BTreeMap<String, BTreeMap<String, Vec<String>>>
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            window_mode: WindowMode::FullscreenDesktop,
+            tileset: DEFAULT_TILESET.to_string(),
+            tile_size: 32,
+            tick_size: TICK_SIZE,
+            language: locale::DEFAULT_LANGUAGE.to_string(),
+            keys: BTreeMap::new()
+        }
+    }
+}
+
+/// Why the config file cannot be used, as a catalog id with its arguments.
+type Problem = (&'static str, Vec<(&'static str, String)>);
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("necronix").join("config.toml"))
+    }
+
+    /// Loads the config file, falling back to defaults for anything missing or invalid.
+    /// Returns the config along with warnings about what had to be fixed.
+    pub fn load() -> (Config, Vec<String>) {
+        let (mut config, problem) = match Config::read() {
+            Ok(config) => (config.unwrap_or_default(), None),
+            Err(problem) => (Config::default(), Some(problem))
+        };
+
+        // The warnings are in the configured language, so it is switched to before any of them is written
+        if locale::set_language(&config.language).is_err() {
+            let _ = locale::set_language(locale::DEFAULT_LANGUAGE);
+        }
+        let mut warnings: Vec<String> = problem.map(|(id, args)| tr_args(id, &args)).into_iter().collect();
+        warnings.extend(config.validate());
+        (config, warnings)
+    }
+
+    /// The config file as it is, None when there is none yet.
+    fn read() -> Result<Option<Config>, Problem> {
+        let path = Config::path().ok_or(("warning.no_config_dir", vec![]))?;
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(("warning.config_unreadable", vec![("path", path.display().to_string()), ("error", e.to_string())]))
+        };
+        toml::from_str(&text).map(Some).map_err(|e| ("warning.bad_config", vec![("path", path.display().to_string()), ("error", e.to_string())]))
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Config::path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string_pretty(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+
+    /// Binds an action to a single key, or goes back to its default keys when given none.
+    pub fn rebind(&mut self, context: InputContext, action: Action, binding: Option<KeyBinding>) {
+        match binding {
+            Some(binding) => {
+                self.keys.entry(context.name().to_string()).or_default().insert(action.name().to_string(), vec![binding.get_text()]);
+            },
+            None => {
+                if let Some(actions) = self.keys.get_mut(context.name()) {
+                    actions.remove(action.name());
+                    if actions.is_empty() {
+                        self.keys.remove(context.name());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replaces out of range values with the closest valid ones.
+    pub fn validate(&mut self) -> Vec<String> {
+        let mut warnings = vec![];
+        let defaults = Config::default();
+
+        if !TILE_SIZES.contains(&self.tile_size) {
+            let closest = *TILE_SIZES.iter().min_by_key(|size| (**size as i64 - self.tile_size as i64).abs()).unwrap();
+            warnings.push(tr_args("warning.tile_size", &[("size", self.tile_size.to_string()), ("closest", closest.to_string())]));
+            self.tile_size = closest;
+        }
+
+        if self.tick_size < MIN_TICK_SIZE || self.tick_size > MAX_TICK_SIZE {
+            let clamped = self.tick_size.clamp(MIN_TICK_SIZE, MAX_TICK_SIZE);
+            warnings.push(tr_args("warning.tick_size", &[("size", self.tick_size.to_string()), ("clamped", clamped.to_string())]));
+            self.tick_size = clamped;
+        }
+
+        if let Err(e) = Tileset::load(&self.tileset) {
+            warnings.push(tr_args("warning.tileset", &[("tileset", self.tileset.clone()), ("error", e.to_string())]));
+            self.tileset = defaults.tileset;
+        }
+
+        if let Err(e) = Catalog::load(&self.language) {
+            warnings.push(tr_args("warning.language", &[("language", self.language.clone()), ("error", e.to_string())]));
+            self.language = defaults.language;
+        }
+
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_values_are_replaced() {
+        locale::set_language("en").unwrap();
+        let mut config: Config = toml::from_str("tile_size = 30\ntick_size = 0\ntileset = \"missing.toml\"\nlanguage = \"xx\"").unwrap();
+        let warnings = config.validate();
+
+        assert_eq!(warnings.len(), 4);
+        assert_eq!(warnings[0], "Tile size 30 is not supported, using 32");
+        assert_eq!(config.language, locale::DEFAULT_LANGUAGE);
+        assert_eq!(config.tile_size, 32);
+        assert_eq!(config.tick_size, MIN_TICK_SIZE);
+        assert_eq!(config.tileset, DEFAULT_TILESET);
+        assert_eq!(config.window_mode, WindowMode::FullscreenDesktop);
+    }
+
+    #[test]
+    fn rebound_keys_replace_the_defaults() {
+        use crate::input::KeyBindings;
+        use sdl2::keyboard::{Keycode, Mod};
+
+        let mut config = Config::default();
+        config.rebind(InputContext::Game, Action::Order, Some(KeyBinding::pressed(Keycode::G, Mod::LCTRLMOD)));
+        assert_eq!(config.keys["game"]["order"], vec!["Ctrl+G".to_string()]);
+
+        let (bindings, warnings) = KeyBindings::with_overrides(&config.keys);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(bindings.action(InputContext::Game, Keycode::G, Mod::LCTRLMOD), Some(Action::Order));
+        assert_eq!(bindings.action(InputContext::Game, Keycode::M, Mod::NOMOD), None);
+        let rows = |bindings: &KeyBindings| bindings.describe_all().iter().map(|(context, action, _)| (*context, *action)).collect::<Vec<_>>();
+        assert_eq!(rows(&bindings), rows(&KeyBindings::defaults()));
+
+        config.rebind(InputContext::Game, Action::Order, None);
+        assert!(config.keys.is_empty());
+    }
+}
@@ -0,0 +1,162 @@
+use specs::prelude::*;
+
+use super::{Building, Faction, FactionType, LightSource, Position, StatusEffect, StatusEffects, Unit};
+use super::clock::GameClock;
+use super::map::{Map, MAX_CORRUPTION};
+
+/// Ticks between corruption spreading or receding.
+const SPREAD_INTERVAL: u64 = 10;
+/// Ticks the ground keeps affecting whoever steps off it.
+const GROUND_EFFECT_TICKS: u32 = 2;
+/// Corruption that a fully corrupted tile passes to each neighbour.
+const SEEP: i32 = 2;
+
+/// Pushes corruption up or down around an entity every spread.
+struct Influence {
+    x: u32,
+    y: u32,
+    radius: u32,
+    strength: i32
+}
+
+/// Spreads corruption from necromantic buildings and the undead, pushes it back around holy sites and villagers,
+/// and lets the ground strengthen the undead and weaken the living standing on it.
+pub struct CorruptionSystem {}
+
+impl<'a> System<'a> for CorruptionSystem {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        Read<'a, GameClock>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, Building>,
+        ReadStorage<'a, Unit>,
+        ReadStorage<'a, LightSource>,
+        WriteStorage<'a, StatusEffects>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut map, clock, positions, factions, buildings, units, lights, mut statuses) = data;
+
+        let size = (map.width * map.height) as usize;
+        if map.corruption.len() != size {
+            map.corruption = vec![0; size];
+        }
+
+        for (pos, faction, effects) in (&positions, &factions, &mut statuses).join() {
+            if pos.x >= map.width || pos.y >= map.height || !map.is_corrupted(pos.x, pos.y) {
+                continue;
+            }
+            match faction.faction {
+                FactionType::Undead => effects.add(StatusEffect::Empowered, GROUND_EFFECT_TICKS),
+                FactionType::Villagers => effects.add(StatusEffect::Slowed, GROUND_EFFECT_TICKS),
+                FactionType::Nature => {}
+            }
+        }
+
+        if clock.tick % SPREAD_INTERVAL != 0 {
+            return;
+        }
+
+        let mut influences = vec![];
+        for (pos, faction, building, unit, effects) in (&positions, &factions, buildings.maybe(), units.maybe(), statuses.maybe()).join() {
+            let (x, y) = (pos.x, pos.y);
+            // Frightened villagers stop standing up to the corruption
+            if effects.is_some_and(|effects| effects.has(StatusEffect::Feared)) {
+                continue;
+            }
+            match (faction.faction, building.is_some(), unit.is_some()) {
+                (FactionType::Undead, true, _) => influences.push(Influence { x, y, radius: 3, strength: 5 }),
+                (FactionType::Undead, false, true) => influences.push(Influence { x, y, radius: 1, strength: 2 }),
+                (FactionType::Villagers, false, _) => influences.push(Influence { x, y, radius: 1, strength: -1 }),
+                _ => {}
+            }
+        }
+        for (pos, light) in (&positions, &lights).join() {
+            if light.holy {
+                influences.push(Influence { x: pos.x, y: pos.y, radius: light.radius, strength: -5 });
+            }
+        }
+
+        let mut pressure = vec![0; size];
+        for influence in influences.iter() {
+            let radius = influence.radius;
+            for y in influence.y.saturating_sub(radius)..(influence.y + radius + 1).min(map.height) {
+                for x in influence.x.saturating_sub(radius)..(influence.x + radius + 1).min(map.width) {
+                    let (dx, dy) = (x.abs_diff(influence.x), y.abs_diff(influence.y));
+                    if dx * dx + dy * dy <= radius * radius {
+                        pressure[map.xy_idx(x, y) as usize] += influence.strength;
+                    }
+                }
+            }
+        }
+
+        // Fully corrupted ground seeps into its neighbours, unless something holds it back there
+        for y in 0..map.height {
+            for x in 0..map.width {
+                if map.corruption_at(x, y) < MAX_CORRUPTION {
+                    continue;
+                }
+                let neighbours = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)];
+                for (nx, ny) in neighbours {
+                    if nx >= map.width || ny >= map.height {
+                        continue;
+                    }
+                    let idx = map.xy_idx(nx, ny) as usize;
+                    if pressure[idx] >= 0 {
+                        pressure[idx] += SEEP;
+                    }
+                }
+            }
+        }
+
+        let mut changed = false;
+        for (corruption, pressure) in map.corruption.iter_mut().zip(pressure) {
+            let next = (*corruption as i32 + pressure).clamp(0, MAX_CORRUPTION as i32) as u8;
+            changed |= next != *corruption;
+            *corruption = next;
+        }
+        if changed {
+            map.revision += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatcher;
+
+    #[test]
+    fn corruption_spreads_from_the_crypt_and_recedes_from_shrines() {
+        let mut world = World::new();
+        dispatcher::register_components(&mut world);
+        world.insert(Map::new(12, 5));
+        world.insert(GameClock::default());
+        world.create_entity().with(Position{ x: 2, y: 2 }).with(Building{}).with(Faction{ faction: FactionType::Undead }).build();
+        world.create_entity().with(Position{ x: 10, y: 2 }).with(LightSource{ radius: 2, color: (255, 255, 200), holy: true }).build();
+        {
+            let mut map = world.write_resource::<Map>();
+            let idx = map.xy_idx(10, 2) as usize;
+            map.corruption[idx] = 30;
+        }
+        let zombie = world.create_entity().with(Position{ x: 2, y: 2 }).with(Faction{ faction: FactionType::Undead })
+                          .with(StatusEffects::default()).build();
+        let villager = world.create_entity().with(Position{ x: 3, y: 2 }).with(Faction{ faction: FactionType::Villagers })
+                            .with(StatusEffects::default()).build();
+
+        for _ in 0..30 {
+            CorruptionSystem {}.run_now(&world);
+            world.write_resource::<GameClock>().tick += SPREAD_INTERVAL;
+        }
+
+        let map = world.fetch::<Map>();
+        assert_eq!(map.corruption_at(2, 2), MAX_CORRUPTION);
+        assert!(map.corruption_at(6, 2) > 0);
+        assert_eq!(map.corruption_at(10, 2), 0);
+        assert!(map.corrupted_share() > 0.1);
+        let statuses = world.read_storage::<StatusEffects>();
+        assert!(statuses.get(zombie).unwrap().has(StatusEffect::Empowered));
+        assert!(statuses.get(villager).unwrap().has(StatusEffect::Slowed));
+    }
+}
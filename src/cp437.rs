@@ -0,0 +1,56 @@
+//! Maps Unicode characters to glyph indices of the IBM code page 437 that tilesets are laid out in.
+
+/// Glyph drawn for characters that have no CP437 equivalent (`¿`).
+pub const FALLBACK_GLYPH: u32 = 168;
+
+/// Unicode character at each CP437 position.
+const CP437: [char; 256] = [
+    '\u{0}', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼',
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+    ' ', '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?',
+    '@', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O',
+    'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '[', '\\', ']', '^', '_',
+    '`', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o',
+    'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', '⌂',
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', ' ',
+];
+
+pub fn to_glyph(c: char) -> Option<u32> {
+    if (' '..='~').contains(&c) {
+        return Some(c as u32);
+    }
+    CP437.iter().position(|x| *x == c).map(|idx| idx as u32)
+}
+
+/// Character shown by a glyph, for text frontends. Glyph 0 and glyphs past the code page are blanks.
+pub fn to_char(glyph: u32) -> char {
+    match CP437.get(glyph as usize) {
+        Some('\u{0}') | None => ' ',
+        Some(c) => *c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_beyond_ascii() {
+        assert_eq!(to_glyph('A'), Some(65));
+        assert_eq!(to_glyph('☺'), Some(1));
+        assert_eq!(to_glyph('│'), Some(179));
+        assert_eq!(to_glyph('é'), Some(130));
+        assert_eq!(to_glyph('█'), Some(219));
+        assert_eq!(to_glyph('€'), None);
+        assert_eq!(to_char(130), 'é');
+        assert_eq!(to_char(0), ' ');
+    }
+}
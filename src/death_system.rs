@@ -0,0 +1,58 @@
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+
+use super::{Corpse, Faction, FactionType, Living, Name, Position, RenderLayer, Renderable, SerializeMe, Villager};
+use super::clock::GameClock;
+use super::gamelog::{Gamelog, LogCategory, LogEntry};
+use super::locale::tr_args;
+use super::rules::GameStats;
+
+/// Removes whatever has run out of health. Slain villagers leave their corpse behind.
+pub struct DeathSystem {}
+
+impl<'a> System<'a> for DeathSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Living>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, Villager>,
+        Read<'a, GameClock>,
+        Read<'a, LazyUpdate>,
+        Write<'a, Gamelog>,
+        Write<'a, GameStats>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, livings, names, positions, factions, villagers, clock, lazy, mut log, mut stats) = data;
+
+        for (entity, living, name, pos, faction) in (&entities, &livings, names.maybe(), positions.maybe(), factions.maybe()).join() {
+            if living.health > 0 {
+                continue;
+            }
+            match faction.map(|faction| faction.faction) {
+                Some(FactionType::Undead) => stats.undead_lost += 1,
+                Some(FactionType::Villagers) => stats.villagers_slain += 1,
+                _ => {}
+            }
+            if let Some(name) = name {
+                let mut entry = LogEntry::new(clock.tick, LogCategory::Combat, tr_args("log.destroyed", &[("name", name.name.clone())]));
+                if let Some(pos) = pos {
+                    entry = entry.at(pos.x, pos.y);
+                }
+                log.add(entry);
+            }
+            if let (Some(villager), Some(pos)) = (villagers.get(entity), pos) {
+                lazy.create_entity(&entities)
+                    .with(Position{ x: pos.x, y: pos.y })
+                    .with(Renderable::new('%' as u32, (140, 40, 40), RenderLayer::Corpse))
+                    .with(Name{ name: tr_args("name.corpse", &[("name", villager.given_name.clone())]) })
+                    .with(Corpse{ villager: Some(villager.clone()) })
+                    .marked::<SimpleMarker<SerializeMe>>()
+                    .build();
+            }
+            entities.delete(entity).expect("Unable to delete destroyed entity");
+        }
+    }
+}
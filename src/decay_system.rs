@@ -0,0 +1,204 @@
+use specs::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use super::{Building, Corpse, Faction, FactionType, Integrity, Living, Name, Necromancer, Position, StatusEffect, StatusEffects, Unit};
+use super::clock::GameClock;
+use super::gamelog::{Gamelog, LogCategory, LogEntry, Severity};
+use super::locale::{tr, tr_args};
+
+/// Ticks between the undead rotting a little more.
+const DECAY_INTERVAL: u64 = 10;
+/// Extra integrity lost in the sun, while hurt, and while the necromancer has more undead than it can hold together.
+const SUNLIGHT_DECAY: i32 = 2;
+const COMBAT_DECAY: i32 = 2;
+const UPKEEP_DECAY: i32 = 2;
+/// Integrity mended every interval spent on the crypt.
+const CRYPT_REPAIR: i32 = 6;
+
+/// Rots the undead away, faster in the sun, when hurt or when there are more of them than the necromancer can hold together.
+/// They mend at the crypt, and feed on corpses they stand on. Undead whose integrity runs out fall apart.
+#[derive(Default)]
+pub struct DecaySystem {
+    /// Health of each undead at the last interval, to tell which ones have been hurt since.
+    health: HashMap<Entity, i32>,
+    /// Whether the army was over the upkeep cap at the last interval, so the warning is only logged once.
+    over_upkeep: bool,
+    /// Tick of the last interval. Anything else than the next interval means a new or loaded game,
+    /// which the health and upkeep above say nothing about.
+    tick: Option<u64>
+}
+
+impl<'a> System<'a> for DecaySystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Unit>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, Integrity>,
+        WriteStorage<'a, Living>,
+        ReadStorage<'a, StatusEffects>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Corpse>,
+        ReadStorage<'a, Building>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, Necromancer>,
+        Read<'a, GameClock>,
+        Write<'a, Gamelog>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, units, positions, mut integrities, mut livings, statuses, names, corpses, buildings, factions, necromancers, clock, mut log) = data;
+        if clock.tick % DECAY_INTERVAL != 0 {
+            return;
+        }
+        let continued = self.tick.is_some_and(|tick| tick + DECAY_INTERVAL == clock.tick);
+        self.tick = Some(clock.tick);
+        if !continued {
+            self.health.clear();
+            self.over_upkeep = false;
+        }
+
+        let cap: usize = necromancers.join().map(|necromancer| necromancer.upkeep_cap()).sum();
+        let count = units.join().count();
+        let over_upkeep = count > cap;
+        if over_upkeep && !self.over_upkeep {
+            let text = tr_args("log.over_upkeep", &[("count", count.to_string()), ("cap", cap.to_string())]);
+            log.add(LogEntry::new(clock.tick, LogCategory::System, text).severity(Severity::Warning));
+        }
+        self.over_upkeep = over_upkeep;
+
+        let crypts: HashSet<(u32, u32)> = (&buildings, &factions, &positions).join()
+            .filter(|(_, faction, _)| faction.faction == FactionType::Undead)
+            .map(|(_, _, pos)| (pos.x, pos.y))
+            .collect();
+        let mut eaten = HashSet::new();
+
+        let mut health = HashMap::new();
+        for (entity, unit, pos, integrity, living) in (&entities, &units, &positions, &mut integrities, (&mut livings).maybe()).join() {
+            let name = names.get(entity).map_or_else(|| tr("unit.unnamed"), |name| name.name.clone());
+
+            let mut change = -unit.kind.decay_rate();
+            if statuses.get(entity).is_some_and(|effects| effects.has(StatusEffect::Decaying)) {
+                change -= SUNLIGHT_DECAY;
+            }
+            if let (Some(living), Some(before)) = (living.as_ref(), self.health.get(&entity)) {
+                if living.health < *before {
+                    change -= COMBAT_DECAY;
+                }
+            }
+            if over_upkeep {
+                change -= UPKEEP_DECAY;
+            }
+            if crypts.contains(&(pos.x, pos.y)) {
+                change += CRYPT_REPAIR;
+            }
+
+            if integrity.integrity < integrity.max_integrity {
+                let corpse = (&entities, &corpses, &positions).join()
+                    .find(|(corpse, _, corpse_pos)| (corpse_pos.x, corpse_pos.y) == (pos.x, pos.y) && !eaten.contains(corpse));
+                if let Some((corpse, _, _)) = corpse {
+                    eaten.insert(corpse);
+                    entities.delete(corpse).expect("Unable to delete eaten corpse");
+                    change += integrity.max_integrity / 2;
+                    let corpse_name = names.get(corpse).map_or_else(|| tr("kind.object"), |name| name.name.clone());
+                    log.add(LogEntry::new(clock.tick, LogCategory::Work, tr_args("log.fed", &[("name", name.clone()), ("corpse", corpse_name)]))
+                        .about(entity).at(pos.x, pos.y));
+                }
+            }
+
+            let before = integrity.integrity;
+            integrity.integrity = (before + change).clamp(0, integrity.max_integrity);
+            if before * 4 >= integrity.max_integrity && integrity.integrity * 4 < integrity.max_integrity {
+                log.add(LogEntry::new(clock.tick, LogCategory::Combat, tr_args("log.rotting", &[("name", name)]))
+                    .severity(Severity::Warning).about(entity).at(pos.x, pos.y));
+            }
+
+            if let Some(living) = living {
+                if integrity.integrity == 0 {
+                    living.health = 0;
+                }
+                health.insert(entity, living.health);
+            }
+        }
+        self.health = health;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dispatcher, Mission, UndeadType};
+
+    fn spawn(world: &mut World, kind: UndeadType, x: u32, y: u32, integrity: i32) -> Entity {
+        world.create_entity()
+             .with(Position{ x, y })
+             .with(Unit{ mission: Mission::Stay, kind })
+             .with(Living::new(10))
+             .with(Integrity{ integrity, ..Integrity::new(kind.max_integrity()) })
+             .build()
+    }
+
+    #[test]
+    fn undead_rot_unless_fed_or_kept_at_the_crypt() {
+        crate::locale::set_language("en").unwrap();
+        let mut world = World::new();
+        dispatcher::register_components(&mut world);
+        world.insert(GameClock::default());
+        world.insert(Gamelog::default());
+        world.create_entity().with(Necromancer::new(25, vec![])).build();
+        world.create_entity().with(Position{ x: 5, y: 5 }).with(Building{}).with(Faction{ faction: FactionType::Undead }).build();
+        world.create_entity().with(Position{ x: 2, y: 2 }).with(Corpse{ villager: None }).build();
+
+        let zombie = spawn(&mut world, UndeadType::Zombie, 0, 0, 100);
+        let kept = spawn(&mut world, UndeadType::Zombie, 5, 5, 100);
+        let fed = spawn(&mut world, UndeadType::Skeleton, 2, 2, 100);
+        let crumbling = spawn(&mut world, UndeadType::Zombie, 0, 1, 2);
+        let rotting = spawn(&mut world, UndeadType::Zombie, 0, 2, 51);
+
+        let mut system = DecaySystem::default();
+        system.run_now(&world);
+        world.maintain();
+        {
+            let integrities = world.read_storage::<Integrity>();
+            assert_eq!(integrities.get(zombie).unwrap().integrity, 98);
+            assert_eq!(integrities.get(kept).unwrap().integrity, 104);
+            assert_eq!(integrities.get(fed).unwrap().integrity, 249);
+            assert_eq!(world.read_storage::<Living>().get(crumbling).unwrap().health, 0);
+            assert_eq!(integrities.get(rotting).unwrap().integrity, 49);
+            assert_eq!(world.read_storage::<Corpse>().count(), 0);
+        }
+
+        // A sixth undead is more than a necromancer of this power can hold together
+        spawn(&mut world, UndeadType::Wraith, 9, 9, 250);
+        world.write_storage::<Living>().get_mut(zombie).unwrap().health = 5;
+        world.write_resource::<GameClock>().tick += DECAY_INTERVAL;
+        system.run_now(&world);
+        assert_eq!(world.read_storage::<Integrity>().get(zombie).unwrap().integrity, 98 - 2 - COMBAT_DECAY - UPKEEP_DECAY);
+        let warnings = world.fetch::<Gamelog>().visible().filter(|entry| entry.severity == Severity::Warning).count();
+        assert_eq!(warnings, 2);
+    }
+
+    #[test]
+    fn a_new_game_starts_with_a_clean_slate() {
+        crate::locale::set_language("en").unwrap();
+        let mut world = World::new();
+        dispatcher::register_components(&mut world);
+        world.insert(GameClock{ tick: 500 });
+        world.insert(Gamelog::default());
+        let zombie = spawn(&mut world, UndeadType::Zombie, 0, 0, 100);
+        spawn(&mut world, UndeadType::Zombie, 0, 1, 100);
+
+        // No necromancer to hold them together, so they are over the upkeep cap
+        let mut system = DecaySystem::default();
+        system.run_now(&world);
+
+        // The game starts over: the zombie is the same entity with less health, which is not a hit
+        world.write_storage::<Living>().get_mut(zombie).unwrap().health = 5;
+        world.write_storage::<Integrity>().get_mut(zombie).unwrap().integrity = 100;
+        world.insert(Gamelog::default());
+        world.write_resource::<GameClock>().tick = 0;
+        system.run_now(&world);
+        assert_eq!(world.read_storage::<Integrity>().get(zombie).unwrap().integrity, 100 - 2 - UPKEEP_DECAY);
+        let warnings = world.fetch::<Gamelog>().visible().filter(|entry| entry.severity == Severity::Warning).count();
+        assert_eq!(warnings, 1);
+    }
+}
@@ -0,0 +1,164 @@
+use specs::prelude::*;
+use specs::saveload::{SimpleMarker, SimpleMarkerAllocator};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::components::*;
+use super::clock::ClockSystem;
+use super::mission_system::MissionSystem;
+use super::movement_system::MovementSystem;
+use super::visibility_system::VisibilitySystem;
+use super::effect_system::EffectSystem;
+use super::lighting_system::LightingSystem;
+use super::sunlight_system::SunlightSystem;
+use super::status_system::StatusSystem;
+use super::death_system::DeathSystem;
+use super::corruption_system::CorruptionSystem;
+use super::rules_system::RulesSystem;
+use super::mana_system::ManaSystem;
+use super::spell_system::SpellSystem;
+use super::decay_system::DecaySystem;
+
+/// Wall-clock time spent in each system during the last dispatch.
+/// Shared between the timed systems and the world, so reading it does not
+/// add a data dependency to the dispatcher.
+#[derive(Clone, Default)]
+pub struct SystemTimings {
+    entries: Arc<Mutex<Vec<(&'static str, Duration)>>>
+}
+
+impl SystemTimings {
+    fn record(&self, name: &'static str, time: Duration) {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.iter_mut().find(|(n, _)| *n == name) {
+            Some(entry) => entry.1 = time,
+            None => entries.push((name, time))
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<(&'static str, Duration)> {
+        self.entries.lock().unwrap().clone()
+    }
+}
+
+/// Runs the wrapped system and records how long it took.
+pub struct Timed<S> {
+    name: &'static str,
+    system: S,
+    timings: SystemTimings
+}
+
+impl<S> Timed<S> {
+    pub fn new(name: &'static str, system: S, timings: &SystemTimings) -> Timed<S> {
+        Timed { name, system, timings: timings.clone() }
+    }
+}
+
+impl<'a, S: System<'a>> System<'a> for Timed<S> {
+    type SystemData = S::SystemData;
+
+    fn run(&mut self, data: Self::SystemData) {
+        let start = Instant::now();
+        self.system.run(data);
+        self.timings.record(self.name, start.elapsed());
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        self.system.setup(world);
+    }
+}
+
+pub fn register_components(world: &mut World) {
+    world.register::<Renderable>();
+    world.register::<Position>();
+    world.register::<Unit>();
+    world.register::<Name>();
+    world.register::<BlocksTile>();
+    world.register::<WantsToMove>();
+    world.register::<MissionQueue>();
+    world.register::<Physical>();
+    world.register::<Living>();
+    world.register::<Faction>();
+    world.register::<Material>();
+    world.register::<Choppable>();
+    world.register::<Building>();
+    world.register::<Inventory>();
+    world.register::<StatusEffects>();
+    world.register::<Kills>();
+    world.register::<Villager>();
+    world.register::<LightSource>();
+    world.register::<Necromancer>();
+    world.register::<Corpse>();
+    world.register::<WantsToCast>();
+    world.register::<Integrity>();
+    world.register::<SimpleMarker<SerializeMe>>();
+    world.register::<SerializationHelper>();
+    world.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+}
+
+/// Builds the simulation dispatcher. Systems run in parallel when the
+/// `parallel` feature is enabled, in declaration order otherwise.
+pub fn build_dispatcher<'a, 'b>(world: &mut World) -> Dispatcher<'a, 'b> {
+    let timings = SystemTimings::default();
+    world.insert(timings.clone());
+
+    let mut dispatcher = DispatcherBuilder::new()
+        .with(Timed::new("clock", ClockSystem {}, &timings), "clock", &[])
+        .with(Timed::new("missions", MissionSystem {}, &timings), "missions", &[])
+        .with(Timed::new("movement", MovementSystem {}, &timings), "movement", &["clock", "missions"])
+        .with(Timed::new("visibility", VisibilitySystem {}, &timings), "visibility", &["movement"])
+        .with(Timed::new("lighting", LightingSystem {}, &timings), "lighting", &["clock", "movement"])
+        .with(Timed::new("sunlight", SunlightSystem {}, &timings), "sunlight", &["lighting"])
+        .with(Timed::new("corruption", CorruptionSystem {}, &timings), "corruption", &["clock", "movement"])
+        .with(Timed::new("spells", SpellSystem {}, &timings), "spells", &["clock", "movement"])
+        .with(Timed::new("status", StatusSystem {}, &timings), "status", &["sunlight", "corruption", "spells"])
+        .with(Timed::new("decay", DecaySystem::default(), &timings), "decay", &["status"])
+        .with(Timed::new("death", DeathSystem {}, &timings), "death", &["status", "decay"])
+        .with(Timed::new("effects", EffectSystem::default(), &timings), "effects", &["clock", "movement", "death"])
+        .with(Timed::new("mana", ManaSystem {}, &timings), "mana", &["corruption", "spells"])
+        .with(Timed::new("rules", RulesSystem {}, &timings), "rules", &["death", "corruption"])
+        .build();
+
+    dispatcher.setup(world);
+    dispatcher
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Map;
+
+    #[test]
+    fn units_walk_to_their_goal() {
+        let mut world = World::new();
+        world.insert(Map::new(15, 15));
+        register_components(&mut world);
+        let mut dispatcher = build_dispatcher(&mut world);
+
+        let unit = world.create_entity()
+                        .with(Position{ x: 0, y: 0 })
+                        .with(Unit{ mission: Mission::GoTo(5, 3), kind: UndeadType::Skeleton })
+                        .with(MissionQueue{ missions: vec![Mission::GoTo(1, 1)] })
+                        .with(Faction{ faction: FactionType::Undead })
+                        .build();
+
+        for _ in 0..20 {
+            dispatcher.dispatch(&world);
+            world.maintain();
+        }
+
+        let positions = world.read_storage::<Position>();
+        let pos = positions.get(unit).unwrap();
+        assert_eq!((pos.x, pos.y), (1, 1));
+        assert!(matches!(world.read_storage::<Unit>().get(unit).unwrap().mission, Mission::Stay));
+        assert!(world.read_storage::<MissionQueue>().get(unit).unwrap().missions.is_empty());
+
+        let map = world.fetch::<Map>();
+        assert!(map.is_revealed(8, 3));
+        assert!(!map.is_revealed(14, 14));
+
+        let timings = world.fetch::<SystemTimings>().snapshot();
+        assert!(timings.iter().any(|(name, _)| *name == "missions"));
+        assert!(timings.iter().any(|(name, _)| *name == "movement"));
+    }
+}
@@ -0,0 +1,104 @@
+use specs::prelude::*;
+use std::collections::HashMap;
+
+use super::{Choppable, Faction, FactionType, Living, Position};
+use super::clock::GameClock;
+use super::effects::{EffectRequest, EffectRequests};
+
+/// What was last seen of an entity: its health, where it was and the effect it leaves when destroyed.
+struct Seen {
+    health: Option<i32>,
+    x: u32,
+    y: u32,
+    remains: Option<&'static str>
+}
+
+/// Asks for effects when things are hurt, healed or destroyed. It only watches, so whatever
+/// does the hurting does not need to know about effects.
+#[derive(Default)]
+pub struct EffectSystem {
+    seen: HashMap<Entity, Seen>,
+    /// Tick of the last run. Anything else than the next tick means a new or loaded game,
+    /// where differences are not something that happened.
+    tick: Option<u64>
+}
+
+impl<'a> System<'a> for EffectSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Living>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, Choppable>,
+        Read<'a, GameClock>,
+        Write<'a, EffectRequests>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, positions, livings, factions, choppables, clock, mut requests) = data;
+        let watching = self.tick.is_some_and(|tick| tick + 1 == clock.tick);
+        self.tick = Some(clock.tick);
+
+        let mut seen = HashMap::new();
+        for (entity, pos, living) in (&entities, &positions, livings.maybe()).join() {
+            let remains = if choppables.contains(entity) {
+                Some("dust")
+            } else {
+                match factions.get(entity).map(|faction| faction.faction) {
+                    Some(FactionType::Undead) => Some("bones"),
+                    Some(FactionType::Villagers) => Some("blood"),
+                    _ => None
+                }
+            };
+            let now = Seen { health: living.map(|living| living.health), x: pos.x, y: pos.y, remains };
+
+            if let (true, Some(before), Some(health)) = (watching, self.seen.get(&entity).and_then(|seen| seen.health), now.health) {
+                if health < before {
+                    requests.queue.push(EffectRequest::new("damage", pos.x, pos.y).text(format!("-{}", before - health)));
+                } else if health > before {
+                    requests.queue.push(EffectRequest::new("heal", pos.x, pos.y).text(format!("+{}", health - before)));
+                }
+            }
+            seen.insert(entity, now);
+        }
+
+        if watching {
+            for (entity, gone) in self.seen.iter() {
+                if let (false, Some(remains)) = (seen.contains_key(entity), gone.remains) {
+                    requests.queue.push(EffectRequest::new(remains, gone.x, gone.y));
+                }
+            }
+        }
+        self.seen = seen;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hurt_and_destroyed_entities_ask_for_effects() {
+        let mut world = World::new();
+        world.register::<Position>();
+        world.register::<Living>();
+        world.register::<Faction>();
+        world.register::<Choppable>();
+        world.insert(GameClock::default());
+        world.insert(EffectRequests::default());
+        let skeleton = world.create_entity().with(Position{ x: 2, y: 3 }).with(Living::new(10)).with(Faction{ faction: FactionType::Undead }).build();
+
+        let mut system = EffectSystem::default();
+        system.run_now(&world);
+        world.write_storage::<Living>().get_mut(skeleton).unwrap().health = 7;
+        world.write_resource::<GameClock>().tick += 1;
+        system.run_now(&world);
+        world.delete_entity(skeleton).unwrap();
+        world.write_resource::<GameClock>().tick += 1;
+        system.run_now(&world);
+
+        let requests = world.fetch::<EffectRequests>();
+        let shown: Vec<(&str, u32, u32, Option<&str>)> = requests.queue.iter().map(|r| (r.name, r.x, r.y, r.text.as_deref())).collect();
+        assert_eq!(shown, vec![("damage", 2, 3, Some("-3")), ("bones", 2, 3, None)]);
+    }
+}
@@ -0,0 +1,244 @@
+//! Short-lived particles drawn over the map: damage numbers, splatter, spell bursts and dust.
+//! Systems ask for them through `EffectRequests` and never hear back, so effects cannot change
+//! how the game plays. Particles live for rendered frames, not simulation ticks.
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::f32::consts::TAU;
+use std::fs;
+
+use super::locale::tr_args;
+use super::widgets::{blend, Color, Symbol};
+
+const EFFECTS_PATH: &str = "./resources/effects.toml";
+/// Frames drawn per second, which effect timings are given in.
+pub const FRAME_RATE: f32 = 60.;
+/// Frames between trail particles appearing.
+const TRAIL_DELAY: i32 = 2;
+/// Velocity kept from one frame to the next by scattered particles.
+const SCATTER_DRAG: f32 = 0.9;
+
+/// How the particles of an effect move.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Pattern {
+    /// One particle per character of the text, floating up.
+    Rise,
+    /// Particles flying out evenly in every direction.
+    Burst,
+    /// Particles thrown around at random, slowing down.
+    Scatter,
+    /// Particles lighting up one after another on the way from the source to the target.
+    Trail
+}
+
+/// How an effect looks. Loaded from `resources/effects.toml`, one table per effect.
+#[derive(Debug, Deserialize, Clone)]
+pub struct EffectStyle {
+    pub pattern: Pattern,
+    /// Glyphs picked at random for each particle, unless the effect is given text.
+    #[serde(default)]
+    pub glyphs: String,
+    pub color: Color,
+    /// Color particles have faded to by the end of their life.
+    pub fade: Color,
+    /// Life of a particle, in seconds.
+    pub duration: f32,
+    #[serde(default = "default_count")]
+    pub count: u32,
+    /// Tiles per second.
+    #[serde(default)]
+    pub speed: f32
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+/// An effect to show at a map tile.
+pub struct EffectRequest {
+    pub name: &'static str,
+    pub x: u32,
+    pub y: u32,
+    pub text: Option<String>,
+    /// Where trails start. They start at the target otherwise.
+    pub from: Option<(u32, u32)>
+}
+
+impl EffectRequest {
+    pub fn new(name: &'static str, x: u32, y: u32) -> EffectRequest {
+        EffectRequest { name, x, y, text: None, from: None }
+    }
+
+    pub fn text(mut self, text: String) -> EffectRequest {
+        self.text = Some(text);
+        self
+    }
+
+    pub fn from(mut self, x: u32, y: u32) -> EffectRequest {
+        self.from = Some((x, y));
+        self
+    }
+}
+
+/// Effects asked for since the GUI last looked.
+#[derive(Default)]
+pub struct EffectRequests {
+    pub queue: Vec<EffectRequest>
+}
+
+struct Particle {
+    x: f32,
+    y: f32,
+    /// Tiles per frame.
+    velocity: (f32, f32),
+    drag: f32,
+    symbol: Symbol,
+    color: Color,
+    fade: Color,
+    /// Frames since the particle appeared. Negative while it waits to appear.
+    age: i32,
+    lifetime: i32
+}
+
+/// Effect styles and the particles currently showing.
+#[derive(Default)]
+pub struct Effects {
+    styles: BTreeMap<String, EffectStyle>,
+    particles: Vec<Particle>
+}
+
+impl Effects {
+    /// Loads the effect styles. Returns a warning when they could not be read, and no effects are shown then.
+    pub fn load() -> (Effects, Vec<String>) {
+        let styles = fs::read_to_string(EFFECTS_PATH).map_err(|e| e.to_string()).and_then(|text| Effects::parse(&text));
+        match styles {
+            Ok(effects) => (effects, vec![]),
+            Err(e) => (Effects::default(), vec![tr_args("log.file_failed", &[("path", EFFECTS_PATH.to_string()), ("error", e.to_string())])])
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Effects, String> {
+        let styles = toml::from_str(text).map_err(|e| e.to_string())?;
+        Ok(Effects { styles, particles: vec![] })
+    }
+
+    /// Sends off the particles of an effect. Effects without a style are not shown.
+    pub fn spawn<R: Rng>(&mut self, request: &EffectRequest, rng: &mut R) {
+        let style = match self.styles.get(request.name) {
+            Some(style) => style,
+            None => return
+        };
+        let glyphs: Vec<char> = style.glyphs.chars().collect();
+        let glyph = |rng: &mut R| Symbol::Char(glyphs.choose(rng).copied().unwrap_or('*'));
+        let lifetime = (style.duration * FRAME_RATE).round().max(1.) as i32;
+        let speed = style.speed / FRAME_RATE;
+        let (x, y) = (request.x as f32, request.y as f32);
+        let particle = |x: f32, y: f32, velocity: (f32, f32), symbol: Symbol| Particle {
+            x, y, velocity, drag: 1., symbol, color: style.color, fade: style.fade, age: 0, lifetime
+        };
+
+        match style.pattern {
+            Pattern::Rise => {
+                let symbols: Vec<Symbol> = match &request.text {
+                    Some(text) => text.chars().map(Symbol::Char).collect(),
+                    None => vec![glyph(rng)]
+                };
+                let left = x - (symbols.len() as f32 - 1.) / 2.;
+                for (i, symbol) in symbols.into_iter().enumerate() {
+                    self.particles.push(particle(left + i as f32, y, (0., -speed), symbol));
+                }
+            },
+            Pattern::Burst => {
+                for i in 0..style.count {
+                    let angle = TAU * i as f32 / style.count as f32;
+                    self.particles.push(particle(x, y, (angle.cos() * speed, angle.sin() * speed), glyph(rng)));
+                }
+            },
+            Pattern::Scatter => {
+                for _ in 0..style.count {
+                    let angle = rng.gen_range(0.0..TAU);
+                    let speed = speed * rng.gen_range(0.3..1.0);
+                    let symbol = glyph(rng);
+                    self.particles.push(Particle { drag: SCATTER_DRAG, ..particle(x, y, (angle.cos() * speed, angle.sin() * speed), symbol) });
+                }
+            },
+            Pattern::Trail => {
+                let (from_x, from_y) = request.from.map_or((x, y), |(fx, fy)| (fx as f32, fy as f32));
+                let steps = (x - from_x).abs().max((y - from_y).abs()).round().max(1.) as i32;
+                for i in 0..=steps {
+                    let t = i as f32 / steps as f32;
+                    let symbol = glyph(rng);
+                    self.particles.push(Particle { age: -i * TRAIL_DELAY, ..particle(from_x + (x - from_x) * t, from_y + (y - from_y) * t, (0., 0.), symbol) });
+                }
+            }
+        }
+    }
+
+    /// Moves every particle one rendered frame on and drops the ones that are done.
+    pub fn advance(&mut self) {
+        for particle in self.particles.iter_mut() {
+            particle.age += 1;
+            if particle.age > 0 {
+                particle.x += particle.velocity.0;
+                particle.y += particle.velocity.1;
+                particle.velocity = (particle.velocity.0 * particle.drag, particle.velocity.1 * particle.drag);
+            }
+        }
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    /// Particles showing now: the map tile each is over, what it looks like and its faded color.
+    pub fn sprites(&self) -> Vec<((i32, i32), Symbol, Color)> {
+        self.particles.iter()
+            .filter(|particle| particle.age >= 0)
+            .map(|particle| {
+                let color = blend(particle.color, particle.fade, particle.age as f32 / particle.lifetime as f32);
+                ((particle.x.round() as i32, particle.y.round() as i32), particle.symbol, color)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cp437;
+
+    #[test]
+    fn shipped_effects_use_cp437() {
+        let (effects, warnings) = Effects::load();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        for name in ["damage", "heal", "blood", "bones", "dust", "spell_burst", "trail"] {
+            let style = effects.styles.get(name).unwrap_or_else(|| panic!("{} is missing", name));
+            assert!(style.glyphs.chars().all(|c| cp437::to_glyph(c).is_some()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn damage_numbers_rise_and_fade() {
+        let mut effects = Effects::parse("[damage]\npattern = \"rise\"\ncolor = [200, 0, 0]\nfade = [0, 0, 0]\nduration = 0.5\nspeed = 60").unwrap();
+        effects.spawn(&EffectRequest::new("damage", 5, 5).text("-12".to_string()), &mut rand::thread_rng());
+        effects.spawn(&EffectRequest::new("unknown", 5, 5), &mut rand::thread_rng());
+
+        let sprites = effects.sprites();
+        assert_eq!(sprites.iter().map(|(tile, symbol, _)| (*tile, *symbol)).collect::<Vec<_>>(),
+                   vec![((4, 5), Symbol::Char('-')), ((5, 5), Symbol::Char('1')), ((6, 5), Symbol::Char('2'))]);
+        assert_eq!(sprites[0].2, (200, 0, 0));
+
+        effects.advance();
+        assert_eq!(effects.sprites()[0].0, (4, 4));
+        assert!(effects.sprites()[0].2.0 < 200);
+
+        for _ in 0..30 {
+            effects.advance();
+        }
+        assert!(effects.sprites().is_empty());
+    }
+}
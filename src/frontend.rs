@@ -0,0 +1,67 @@
+//! Where input comes from and frames go. The game loop only talks to a `Frontend`.
+
+use sdl2::event::Event;
+
+use super::config::Config;
+use super::renderer::{MemoryRenderer, Renderer};
+
+pub trait Frontend {
+    type Renderer: Renderer;
+
+    /// Input since the last call. Every frontend speaks in SDL events, so the game loop handles one kind.
+    fn poll_events(&mut self, renderer: &mut Self::Renderer) -> Vec<Event>;
+
+    /// Sends typed characters as `TextInput` events, which is only wanted while typing a name.
+    fn set_text_input(&mut self, enabled: bool);
+
+    fn show_cursor(&mut self, shown: bool);
+
+    /// Applies the display settings of the config. Returns errors to log.
+    fn configure(&mut self, config: &Config, renderer: &mut Self::Renderer) -> Vec<String>;
+
+    /// Name of the tileset glyphs are drawn with, for frontends that use one.
+    fn tileset_name(&self, renderer: &Self::Renderer) -> Option<String>;
+
+    /// Checked before every frame. Some when the game has to end: Ok when the frontend is done, an error
+    /// when it cannot go on.
+    fn finished(&mut self, renderer: &mut Self::Renderer) -> Option<Result<(), String>>;
+}
+
+/// Runs a number of frames with no input and no screen, to check that the game starts without a window or terminal.
+pub struct HeadlessFrontend {
+    frames: u32
+}
+
+impl HeadlessFrontend {
+    pub fn new(frames: u32) -> HeadlessFrontend {
+        HeadlessFrontend { frames }
+    }
+}
+
+impl Frontend for HeadlessFrontend {
+    type Renderer = MemoryRenderer;
+
+    fn poll_events(&mut self, _renderer: &mut MemoryRenderer) -> Vec<Event> {
+        vec![]
+    }
+
+    fn set_text_input(&mut self, _enabled: bool) {}
+
+    fn show_cursor(&mut self, _shown: bool) {}
+
+    fn configure(&mut self, _config: &Config, _renderer: &mut MemoryRenderer) -> Vec<String> {
+        vec![]
+    }
+
+    fn tileset_name(&self, _renderer: &MemoryRenderer) -> Option<String> {
+        None
+    }
+
+    fn finished(&mut self, _renderer: &mut MemoryRenderer) -> Option<Result<(), String>> {
+        if self.frames == 0 {
+            return Some(Ok(()));
+        }
+        self.frames -= 1;
+        None
+    }
+}
@@ -1,3 +1,165 @@
+use specs::prelude::*;
+use std::collections::VecDeque;
+
+use super::clock::GameClock;
+use super::locale::tr;
+
+/// Oldest entries are dropped once the log holds this many.
+pub const LOG_CAPACITY: usize = 200;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LogCategory { Combat, Work, System }
+
+impl LogCategory {
+    pub fn all() -> [LogCategory; 3] {
+        [LogCategory::Combat, LogCategory::Work, LogCategory::System]
+    }
+
+    /// Id of the filter button in the Log tab.
+    pub fn filter_id(&self) -> &'static str {
+        match self {
+            LogCategory::Combat => "filter_combat",
+            LogCategory::Work => "filter_work",
+            LogCategory::System => "filter_system"
+        }
+    }
+
+    pub fn from_filter_id(id: &str) -> Option<LogCategory> {
+        LogCategory::all().iter().copied().find(|category| category.filter_id() == id)
+    }
+
+    pub fn get_text(&self) -> String {
+        match self {
+            LogCategory::Combat => tr("log_category.combat"),
+            LogCategory::Work => tr("log_category.work"),
+            LogCategory::System => tr("log_category.system")
+        }
+    }
+
+    pub fn get_color(&self) -> (u8, u8, u8) {
+        match self {
+            LogCategory::Combat => (200, 100, 100),
+            LogCategory::Work => (150, 200, 100),
+            LogCategory::System => (100, 200, 200)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity { Info, Warning, Error }
+
+pub struct LogEntry {
+    pub tick: u64,
+    pub category: LogCategory,
+    pub severity: Severity,
+    pub text: String,
+    pub entity: Option<Entity>,
+    pub location: Option<(u32, u32)>
+}
+
+impl LogEntry {
+    pub fn new(tick: u64, category: LogCategory, text: String) -> LogEntry {
+        LogEntry { tick, category, severity: Severity::Info, text, entity: None, location: None }
+    }
+
+    pub fn severity(mut self, severity: Severity) -> LogEntry {
+        self.severity = severity;
+        self
+    }
+
+    pub fn about(mut self, entity: Entity) -> LogEntry {
+        self.entity = Some(entity);
+        self
+    }
+
+    pub fn at(mut self, x: u32, y: u32) -> LogEntry {
+        self.location = Some((x, y));
+        self
+    }
+
+    pub fn get_color(&self) -> (u8, u8, u8) {
+        match self.severity {
+            Severity::Info => self.category.get_color(),
+            Severity::Warning => (200, 150, 0),
+            Severity::Error => (200, 50, 50)
+        }
+    }
+
+    pub fn get_text(&self) -> String {
+        let clock = GameClock { tick: self.tick };
+        format!("{:02}:{:02} {}", clock.hour(), clock.minute(), self.text)
+    }
+}
+
+/// Messages shown in the Log tab. Kept as a resource so systems can report what happens.
 pub struct Gamelog {
-    pub entries: Vec<String>
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+    hidden: Vec<LogCategory>
+}
+
+impl Default for Gamelog {
+    fn default() -> Gamelog {
+        Gamelog::new(LOG_CAPACITY)
+    }
+}
+
+impl Gamelog {
+    pub fn new(capacity: usize) -> Gamelog {
+        Gamelog { entries: VecDeque::with_capacity(capacity), capacity, hidden: vec![] }
+    }
+
+    pub fn add(&mut self, entry: LogEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn shows(&self, category: LogCategory) -> bool {
+        !self.hidden.contains(&category)
+    }
+
+    pub fn toggle(&mut self, category: LogCategory) {
+        if self.shows(category) {
+            self.hidden.push(category);
+        } else {
+            self.hidden.retain(|c| *c != category);
+        }
+    }
+
+    /// Entries of the shown categories, newest first.
+    pub fn visible(&self) -> impl Iterator<Item = &LogEntry> {
+        self.entries.iter().rev().filter(move |entry| self.shows(entry.category))
+    }
+}
+
+/// Adds a system message, stamped with the current game time.
+pub fn log_system(ecs: &World, severity: Severity, text: String) {
+    let tick = ecs.fetch::<GameClock>().tick;
+    ecs.write_resource::<Gamelog>().add(LogEntry::new(tick, LogCategory::System, text).severity(severity));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_entries_are_dropped_and_filters_apply() {
+        let mut log = Gamelog::new(3);
+        for i in 0..5 {
+            let category = if i % 2 == 0 { LogCategory::Work } else { LogCategory::Combat };
+            log.add(LogEntry::new(i, category, i.to_string()));
+        }
+
+        let texts: Vec<&str> = log.visible().map(|entry| entry.text.as_str()).collect();
+        assert_eq!(texts, ["4", "3", "2"]);
+
+        log.toggle(LogCategory::Combat);
+        let texts: Vec<&str> = log.visible().map(|entry| entry.text.as_str()).collect();
+        assert_eq!(texts, ["4", "2"]);
+
+        log.toggle(LogCategory::Combat);
+        assert!(log.shows(LogCategory::Combat));
+    }
 }
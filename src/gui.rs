@@ -1,62 +1,97 @@
-use sdl2::render::{WindowCanvas, Texture};
 use sdl2::rect::Rect;
-use sdl2::pixels::Color;
 
 use specs::prelude::*;
 
-use super::{State, map::Map, Position, Renderable, Unit, Name};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::locale::{tr, tr_args, tr_count};
+use super::effects::{EffectRequests, Effects};
+use super::lighting_system::{lit, Lighting};
+use super::rules::{GameResult, GameStats};
+use super::spells::{Spell, Spells};
+use super::renderer::Renderer;
+use super::widgets::{blend, Align, Area, Batch, Choice, Hit, Symbol, Ui, Widget};
+use super::gamelog::{log_system, Gamelog, LogCategory, Severity};
+use super::names::MAX_LENGTH;
+use super::components::{RenderLayer, Building, Choppable, Faction, FactionType, Integrity, Inventory, Kills, Living, Material, MissionQueue, Necromancer, Physical, StatusEffects, Villager};
+use super::{State, config::Config, input::InputContext, map::{Map, MAX_CORRUPTION}, Position, Renderable, Unit, Name, dispatcher::SystemTimings, clock::{GameClock, SPEED_LEVELS}};
 
 
-const TILEMAP_TILE: u32 = 16;
-const TILE_SIZE: u32 = 32;
 const MAP_SIZE: u32 = 15;
-
-const BG_COLOR: Color = Color::RGB(11, 32, 39);
-const DARK_BG_COLOR: Color = Color::RGB(1, 22, 29);
-const LIGHT_BG_COLOR: Color = Color::RGB(64, 121, 140);
+/// Rows of the side panel taken by the minimap, border included.
+const MINIMAP_SIZE: u32 = 12;
+const NECROMANCER_PANEL_SIZE: u32 = 5;
+const MANA_COLOR: (u8, u8, u8) = (60, 90, 220);
+/// Ground under a spell being aimed, and under one that cannot be cast there.
+const AIM_COLOR: (u8, u8, u8) = (80, 30, 110);
+const AIM_BLOCKED_COLOR: (u8, u8, u8) = (110, 25, 25);
+/// How long each frame of an animated glyph is shown.
+const ANIMATION_STEP_MS: u128 = 250;
+/// Part of the light of light sources that shows on the ground, as a divisor.
+const GLOW_SHARE: u8 = 5;
+
+const BG_COLOR: (u8, u8, u8) = (11, 32, 39);
+const DARK_BG_COLOR: (u8, u8, u8) = (1, 22, 29);
+const LIGHT_BG_COLOR: (u8, u8, u8) = (64, 121, 140);
+/// Ground of fully corrupted tiles.
+const CORRUPTION_COLOR: (u8, u8, u8) = (55, 10, 65);
 
 #[derive(PartialEq)]
-pub enum GuiMenu { MainMenu(MainMenuButton), HelpMenu, GameMenu(GameMenuTab), CreditsMenu }
-
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub enum MainMenuButton { Start, Help, Credits }
+pub enum GuiMenu {
+    MainMenu(MainMenuButton),
+    HelpMenu(HelpPage),
+    GameMenu(GameMenuTab),
+    CreditsMenu,
+    PauseMenu(PauseMenuButton, GameMenuTab),
+    ConfirmMenu(PauseMenuButton, GameMenuTab),
+    /// Opened from the pause menu when a tab to return to is given, from the main menu otherwise.
+    SettingsMenu(SettingsField, Option<GameMenuTab>),
+    /// Key bindings opened from the settings menu: the selected row, and whether a key press is awaited for it.
+    KeysMenu(usize, bool, Option<GameMenuTab>),
+    /// Typing a new name for the entity, kept in `GUI::text_input`.
+    RenameMenu(Entity, GameMenuTab),
+    /// Shown over the game once it is won or lost.
+    GameOverMenu(GameOverButton, GameMenuTab)
+}
 
-impl MainMenuButton {
-    pub fn variants_count() -> u32 { 3 }
-    pub fn from_u8(id: u8) -> MainMenuButton {
-        match id {
-            0 => MainMenuButton::Start,
-            1 => MainMenuButton::Help,
-            2 => MainMenuButton::Credits,
-            _ => MainMenuButton::Start
-        }
+impl GuiMenu {
+    pub fn shows_cursor(&self) -> bool {
+        !matches!(self, GuiMenu::MainMenu(..) | GuiMenu::HelpMenu(..) | GuiMenu::CreditsMenu)
     }
+}
 
-    pub fn to_u8(&self) -> u8 {
-        match self {
-            MainMenuButton::Start => 0,
-            MainMenuButton::Help => 1,
-            MainMenuButton::Credits => 2
-        }
-    }
+pub enum CarouselHit { Prev, Next, Select }
 
-    pub fn next(&self) -> MainMenuButton {
-        MainMenuButton::from_u8((self.to_u8() + 1) % MainMenuButton::variants_count() as u8)
-    }
+/// A spell from the necromancer's spellbook being aimed at a map tile.
+#[derive(Clone, Copy)]
+pub struct Aim {
+    pub slot: usize,
+    pub x: u32,
+    pub y: u32
+}
 
-    pub fn prev(&self) -> MainMenuButton {
-        let i = self.to_u8();
-        if i == 0 {
-            return MainMenuButton::from_u8(MainMenuButton::variants_count() as u8 - 1);
-        }
-        MainMenuButton::from_u8((self.to_u8() - 1) % MainMenuButton::variants_count() as u8)
+pub enum ConfirmHit { Yes, No }
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MainMenuButton { Start, Help, Settings, Credits }
+
+impl Choice for MainMenuButton {
+    fn all() -> &'static [MainMenuButton] {
+        &[MainMenuButton::Start, MainMenuButton::Help, MainMenuButton::Settings, MainMenuButton::Credits]
     }
+}
 
+impl MainMenuButton {
     pub fn get_text(&self) -> String {
         match self {
-            MainMenuButton::Start => "Start".to_string(),
-            MainMenuButton::Help => "Help".to_string(),
-            MainMenuButton::Credits => "Credits".to_string()
+            MainMenuButton::Start => tr("menu.start"),
+            MainMenuButton::Help => tr("menu.help"),
+            MainMenuButton::Settings => tr("menu.settings"),
+            MainMenuButton::Credits => tr("menu.credits")
         }
     }
 
@@ -64,6 +99,7 @@ impl MainMenuButton {
         match self {
             MainMenuButton::Start => 140,
             MainMenuButton::Help => '?' as u32,
+            MainMenuButton::Settings => 15,
             MainMenuButton::Credits => '@' as u32
         }
     }
@@ -72,6 +108,7 @@ impl MainMenuButton {
         match self {
             MainMenuButton::Start => (100, 0, 200),
             MainMenuButton::Help => (200, 100, 0),
+            MainMenuButton::Settings => (0, 150, 100),
             MainMenuButton::Credits => (200, 0, 100)
         }
     }
@@ -79,56 +116,349 @@ impl MainMenuButton {
     pub fn get_menu(&self) -> GuiMenu {
         match self {
             MainMenuButton::Start => GuiMenu::GameMenu(GameMenuTab::Unit),
-            MainMenuButton::Help => GuiMenu::HelpMenu,
+            MainMenuButton::Help => GuiMenu::HelpMenu(HelpPage::About),
+            MainMenuButton::Settings => GuiMenu::SettingsMenu(SettingsField::WindowMode, None),
             MainMenuButton::Credits => GuiMenu::CreditsMenu
         }
     }
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GameOverButton { Restart, Load, MainMenu }
+
+impl Choice for GameOverButton {
+    fn all() -> &'static [GameOverButton] {
+        &[GameOverButton::Restart, GameOverButton::Load, GameOverButton::MainMenu]
+    }
+}
+
+impl GameOverButton {
+    pub fn get_text(&self) -> String {
+        match self {
+            GameOverButton::Restart => tr("game_over.restart"),
+            GameOverButton::Load => tr("game_over.load"),
+            GameOverButton::MainMenu => tr("game_over.main_menu")
+        }
+    }
+
+    pub fn get_icon(&self) -> u32 {
+        match self {
+            GameOverButton::Restart => 140,
+            GameOverButton::Load => 24,
+            GameOverButton::MainMenu => 17
+        }
+    }
+
+    pub fn get_color(&self) -> (u8, u8, u8) {
+        match self {
+            GameOverButton::Restart => (100, 0, 200),
+            GameOverButton::Load => (0, 100, 200),
+            GameOverButton::MainMenu => (200, 0, 100)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum HelpPage { About, Controls }
+
+impl Choice for HelpPage {
+    fn all() -> &'static [HelpPage] {
+        &[HelpPage::About, HelpPage::Controls]
+    }
+}
+
+impl HelpPage {
+    pub fn get_text(&self) -> String {
+        match self {
+            HelpPage::About => tr("help.about"),
+            HelpPage::Controls => tr("help.controls")
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SettingsField { WindowMode, Tileset, TileSize, TickSize, Language, Keys }
+
+impl Choice for SettingsField {
+    fn all() -> &'static [SettingsField] {
+        &[SettingsField::WindowMode, SettingsField::Tileset, SettingsField::TileSize, SettingsField::TickSize, SettingsField::Language, SettingsField::Keys]
+    }
+}
+
+impl SettingsField {
+    pub fn get_text(&self) -> String {
+        match self {
+            SettingsField::WindowMode => tr("settings.window"),
+            SettingsField::Tileset => tr("settings.tileset"),
+            SettingsField::TileSize => tr("settings.tile_size"),
+            SettingsField::TickSize => tr("settings.tick_size"),
+            SettingsField::Language => tr("settings.language"),
+            SettingsField::Keys => tr("settings.keys")
+        }
+    }
+
+    pub fn get_value(&self, config: &Config) -> String {
+        match self {
+            SettingsField::WindowMode => config.window_mode.get_text(),
+            SettingsField::Tileset => Path::new(&config.tileset).file_stem().map_or(config.tileset.clone(), |stem| stem.to_string_lossy().to_string()),
+            SettingsField::TileSize => tr_count("settings.pixels", config.tile_size as u64, &[]),
+            SettingsField::TickSize => tr_count("settings.frames", config.tick_size as u64, &[]),
+            // Language changes are applied right away, so the current catalog names it
+            SettingsField::Language => tr("language"),
+            SettingsField::Keys => tr("settings.edit")
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PauseMenuButton { Resume, Save, Load, Settings, QuitToMenu, QuitGame }
+
+impl Choice for PauseMenuButton {
+    fn all() -> &'static [PauseMenuButton] {
+        &[PauseMenuButton::Resume, PauseMenuButton::Save, PauseMenuButton::Load,
+          PauseMenuButton::Settings, PauseMenuButton::QuitToMenu, PauseMenuButton::QuitGame]
+    }
+}
+
+impl PauseMenuButton {
+    pub fn get_text(&self) -> String {
+        match self {
+            PauseMenuButton::Resume => tr("pause.resume"),
+            PauseMenuButton::Save => tr("pause.save"),
+            PauseMenuButton::Load => tr("pause.load"),
+            PauseMenuButton::Settings => tr("pause.settings"),
+            PauseMenuButton::QuitToMenu => tr("pause.quit_to_menu"),
+            PauseMenuButton::QuitGame => tr("pause.quit_game")
+        }
+    }
+
+    pub fn get_icon(&self) -> u32 {
+        match self {
+            PauseMenuButton::Resume => 16,
+            PauseMenuButton::Save => 25,
+            PauseMenuButton::Load => 24,
+            PauseMenuButton::Settings => 15,
+            PauseMenuButton::QuitToMenu => 17,
+            PauseMenuButton::QuitGame => 'X' as u32
+        }
+    }
+
+    pub fn get_color(&self) -> (u8, u8, u8) {
+        match self {
+            PauseMenuButton::Resume => (100, 0, 200),
+            PauseMenuButton::Save => (0, 150, 100),
+            PauseMenuButton::Load => (0, 100, 200),
+            PauseMenuButton::Settings => (200, 100, 0),
+            PauseMenuButton::QuitToMenu => (200, 0, 100),
+            PauseMenuButton::QuitGame => (200, 0, 0)
+        }
+    }
+
+    /// Whether choosing this button throws away the running game.
+    pub fn discards_progress(&self) -> bool {
+        matches!(self, PauseMenuButton::Load | PauseMenuButton::QuitToMenu | PauseMenuButton::QuitGame)
+    }
+}
+
 #[derive(PartialEq, Clone, Copy)]
 pub enum GameMenuTab { Unit, Log }
 
-impl GameMenuTab {
-    pub fn variants_count() -> u32 { 2 }
-    pub fn from_u8(id: u8) -> GameMenuTab {
-        match id {
-            0 => GameMenuTab::Unit,
-            1 => GameMenuTab::Log,
-            _ => GameMenuTab::Unit
+impl Choice for GameMenuTab {
+    fn all() -> &'static [GameMenuTab] {
+        &[GameMenuTab::Unit, GameMenuTab::Log]
+    }
+}
+
+pub struct GUI<R: Renderer> {
+    pub renderer: R,
+    pub menu: GuiMenu,
+    pub show_timings: bool,
+    pub ui: Ui,
+    /// Map tile shown in the top left corner of the map view.
+    pub camera: (u32, u32),
+    pub text_input: String,
+    /// Where the minimap was drawn last frame, in pixels, and how many pixels a tile took.
+    minimap_rect: Option<(Rect, u32)>,
+    /// Changed for every new or loaded game, so cached map layers are not reused across games.
+    map_generation: u64,
+    /// Time taken and draw calls issued by the last frame, shown with the system timings.
+    last_frame: (Duration, u32),
+    /// Where to save the next frame.
+    screenshot: Option<PathBuf>,
+    /// Animated glyphs change with the time since this.
+    started: Instant,
+    pub effects: Effects,
+    /// Spell being aimed, while choosing where to cast it.
+    pub aim: Option<Aim>
+}
+
+fn cache_key<T: Hash>(value: T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The part of the map being drawn, in tiles.
+#[derive(Clone, Copy, Hash)]
+struct MapView {
+    camera: (u32, u32),
+    size: (u32, u32),
+    /// Hides what the undead have not seen.
+    fog: bool,
+    /// Shows entities under the light of their tile.
+    lit: bool
+}
+
+impl MapView {
+    /// Where a map tile is drawn, if it is in view.
+    fn cell(&self, x: u32, y: u32) -> Option<(i32, i32)> {
+        if x < self.camera.0 || y < self.camera.1 || x - self.camera.0 >= self.size.0 || y - self.camera.1 >= self.size.1 {
+            return None;
         }
+        Some(((x - self.camera.0) as i32, (y - self.camera.1) as i32))
     }
+}
 
-    pub fn to_u8(&self) -> u8 {
-        match self {
-            GameMenuTab::Unit => 0,
-            GameMenuTab::Log => 1
+/// Draws the map tiles in view under their light. Light sources and corruption tint the ground.
+fn draw_terrain(renderer: &mut dyn Renderer, map: &Map, lighting: &Lighting, view: MapView) {
+    let (width, height) = renderer.cell_size();
+    let (camera_x, camera_y) = view.camera;
+    let ground = lit(DARK_BG_COLOR, lighting.ambient);
+    renderer.fill_rects(&[Rect::new(0, 0, width * view.size.0, height * view.size.1)], ground);
+    let corrupted = lit(CORRUPTION_COLOR, lighting.ambient);
+    for y in 0..view.size.1.min(map.height.saturating_sub(camera_y)) {
+        for x in 0..view.size.0.min(map.width.saturating_sub(camera_x)) {
+            let (map_x, map_y) = (camera_x + x, camera_y + y);
+            let rect = Rect::new((x * width) as i32, (y * height) as i32, width, height);
+            if view.fog && !map.is_revealed(map_x, map_y) {
+                renderer.fill_rects(&[rect], (0, 0, 0));
+                continue;
+            }
+            let corruption = map.corruption_at(map_x, map_y) as f32 / MAX_CORRUPTION as f32;
+            let glow = lighting.glow_at(map_x, map_y);
+            let tile = blend(ground, corrupted, corruption);
+            let tile = (tile.0.saturating_add(glow.0 / GLOW_SHARE), tile.1.saturating_add(glow.1 / GLOW_SHARE), tile.2.saturating_add(glow.2 / GLOW_SHARE));
+            if tile != ground {
+                renderer.fill_rects(&[rect], tile);
+            }
+            if map.tiles[map.xy_idx(map_x, map_y) as usize] == 1 {
+                renderer.draw_glyph(x as i32, y as i32, Symbol::Glyph(0xdb), lit((100, 100, 100), lighting.light_at(map_x, map_y)), None);
+            }
         }
     }
+}
 
-    pub fn next(&self) -> GameMenuTab {
-        GameMenuTab::from_u8((self.to_u8() + 1) % GameMenuTab::variants_count() as u8)
+/// Draws everything on the map in view in the given layers, layer by layer, the selected entity brighter.
+/// `step` picks the frame of animated glyphs.
+fn draw_entities(renderer: &mut dyn Renderer, ecs: &World, map: &Map, view: MapView, selected: Option<Entity>, step: u64, layers: RangeInclusive<RenderLayer>) {
+    let entities = ecs.entities();
+    let renderables = ecs.read_storage::<Renderable>();
+    let positions = ecs.read_storage::<Position>();
+    let lighting = ecs.try_fetch::<Lighting>().filter(|_| view.lit);
+
+    let mut shown: Vec<(Entity, &Renderable, &Position, (i32, i32))> = (&entities, &renderables, &positions).join()
+        .filter(|(_, render, pos)| layers.contains(&render.layer) && (!view.fog || map.is_revealed(pos.x, pos.y)))
+        .filter_map(|(entity, render, pos)| view.cell(pos.x, pos.y).map(|cell| (entity, render, pos, cell)))
+        .collect();
+    shown.sort_by_key(|(entity, render, _, _)| (render.layer, entity.id()));
+
+    for (entity, render, pos, (x, y)) in shown {
+        let light = lighting.as_ref().map_or((255, 255, 255), |lighting| lighting.light_at(pos.x, pos.y));
+        let color = lit(render.color, light);
+        let color = if Some(entity) == selected { (color.0 / 2 * 3, color.1 / 2 * 3, color.2 / 2 * 3) } else { color };
+        renderer.draw_glyph(x, y, Symbol::Glyph(render.glyph_at(step)), color, render.background.map(|background| lit(background, light)));
     }
 }
 
-pub struct GUI<'a> {
-    pub canvas: WindowCanvas,
-    pub tileset: Texture<'a>,
-    pub menu: GuiMenu
+/// The spell being aimed, the tiles it would reach and whether it can be cast there.
+struct AimPreview {
+    id: String,
+    spell: Spell,
+    tiles: Vec<(u32, u32)>,
+    check: Result<(), String>
 }
 
-fn tile_rect(idx: u32) -> Rect {
-    let x = idx % 16;
-    let y = idx / 16;
-    Rect::new((TILEMAP_TILE * x) as i32, (TILEMAP_TILE * y) as i32, TILEMAP_TILE, TILEMAP_TILE)
+fn aim_preview(ecs: &World, aim: Aim) -> Option<AimPreview> {
+    let spells = ecs.try_fetch::<Spells>()?;
+    let necromancers = ecs.read_storage::<Necromancer>();
+    let positions = ecs.read_storage::<Position>();
+    let (necromancer, pos) = (&necromancers, &positions).join().next()?;
+    let id = necromancer.spellbook.get(aim.slot)?;
+    let spell = spells.get(id)?;
+
+    let map = ecs.fetch::<Map>();
+    let check = spell.check(id, necromancer, (pos.x, pos.y), (aim.x, aim.y), ecs.fetch::<GameClock>().tick);
+    Some(AimPreview { id: id.clone(), spell: spell.clone(), tiles: spell.area(aim.x, aim.y, map.width, map.height), check })
 }
 
 fn tab_name(tab: &GameMenuTab) -> String {
     match *tab {
-        GameMenuTab::Unit => "Unit".to_string(),
-        GameMenuTab::Log => "Log".to_string()
+        GameMenuTab::Unit => tr("tab.unit"),
+        GameMenuTab::Log => tr("tab.log")
     }
 }
 
+fn bright(color: (u8, u8, u8)) -> (u8, u8, u8) {
+    (color.0.saturating_mul(2), color.1.saturating_mul(2), color.2.saturating_mul(2))
+}
+
+/// What an entity is, judging by its components.
+fn entity_kind(ecs: &World, entity: Entity) -> String {
+    if let Some(unit) = ecs.read_storage::<Unit>().get(entity) {
+        unit.kind.get_text()
+    } else if let Some(villager) = ecs.read_storage::<Villager>().get(entity) {
+        villager.profession.get_text()
+    } else if ecs.read_storage::<Necromancer>().contains(entity) {
+        tr("kind.necromancer")
+    } else if ecs.read_storage::<Building>().contains(entity) {
+        tr("kind.building")
+    } else if ecs.read_storage::<Choppable>().contains(entity) {
+        tr("kind.tree")
+    } else if let Some(material) = ecs.read_storage::<Material>().get(entity) {
+        material.material_type.get_text()
+    } else {
+        tr("kind.object")
+    }
+}
+
+/// Colors of the minimap pixels: terrain, the fog of war and a dot for each faction member.
+fn minimap_pixels(ecs: &World) -> Vec<(u8, u8, u8)> {
+    let map = ecs.fetch::<Map>();
+    let mut pixels: Vec<(u8, u8, u8)> = map.tiles.iter().enumerate().map(|(idx, tile)| {
+        if *tile == 1 {
+            (120, 120, 120)
+        } else {
+            let corruption = map.corruption.get(idx).copied().unwrap_or(0);
+            blend((40, 60, 45), (100, 30, 110), corruption as f32 / MAX_CORRUPTION as f32)
+        }
+    }).collect();
+
+    let factions = ecs.read_storage::<Faction>();
+    let positions = ecs.read_storage::<Position>();
+    for (faction, pos) in (&factions, &positions).join() {
+        if pos.x < map.width && pos.y < map.height {
+            pixels[map.xy_idx(pos.x, pos.y) as usize] = faction.faction.get_color();
+        }
+    }
+
+    for y in 0..map.height {
+        for x in 0..map.width {
+            if !map.is_revealed(x, y) {
+                pixels[map.xy_idx(x, y) as usize] = (0, 0, 0);
+            }
+        }
+    }
+    pixels
+}
+
+/// Changes when the minimap would look different: when tiles change or faction dots move.
+fn minimap_key(ecs: &World, generation: u64) -> u64 {
+    let dots: Vec<(u32, u32, (u8, u8, u8))> = (&ecs.read_storage::<Faction>(), &ecs.read_storage::<Position>()).join()
+        .map(|(faction, pos)| (pos.x, pos.y, faction.faction.get_color()))
+        .collect();
+    cache_key((ecs.fetch::<Map>().revision, generation, dots))
+}
+
 fn tab_default_icon(tab: &GameMenuTab) -> u32 {
     match *tab {
         GameMenuTab::Unit => 140,
@@ -137,149 +467,472 @@ fn tab_default_icon(tab: &GameMenuTab) -> u32 {
 }
 
 
-impl GUI<'_> {
+impl<R: Renderer> GUI<R> {
+    pub fn new(renderer: R) -> GUI<R> {
+        GUI {
+            renderer,
+            menu: GuiMenu::MainMenu(MainMenuButton::Start),
+            show_timings: false,
+            ui: Ui::default(),
+            camera: (0, 0),
+            text_input: String::new(),
+            minimap_rect: None,
+            map_generation: 0,
+            last_frame: (Duration::ZERO, 0),
+            screenshot: None,
+            started: Instant::now(),
+            effects: Effects::default(),
+            aim: None
+        }
+    }
+
     pub fn render(&mut self, state: &mut State) {
-        self.canvas.set_draw_color(BG_COLOR.clone());
-        self.canvas.clear();
+        let start = Instant::now();
+        self.renderer.clear(BG_COLOR);
+        self.ui.begin_frame();
 
         match self.menu {
             GuiMenu::GameMenu(tab) => {
-                self.draw_map(state);
-                self.draw_unit_list(state);
-                self.draw_menu(state, tab);
-                self.draw_statusline(state, tab);
+                self.draw_game(state, tab);
+            },
+            GuiMenu::PauseMenu(button, tab) => {
+                self.draw_game(state, tab);
+                self.draw_pause_menu(button);
+            },
+            GuiMenu::ConfirmMenu(button, tab) => {
+                self.draw_game(state, tab);
+                self.draw_confirm_menu(button);
+            },
+            GuiMenu::RenameMenu(_, tab) => {
+                self.draw_game(state, tab);
+                self.draw_rename_menu();
+            },
+            GuiMenu::GameOverMenu(button, tab) => {
+                self.draw_game(state, tab);
+                self.draw_game_over_menu(state, button);
             },
             GuiMenu::MainMenu(_) => {
                 self.draw_main_menu(state);
             },
-            GuiMenu::HelpMenu => {
-                self.draw_help_menu();
+            GuiMenu::HelpMenu(page) => {
+                self.draw_help_menu(state, page);
             },
             GuiMenu::CreditsMenu => {
                 self.draw_credits_menu();
             },
-            _ => {}
+            GuiMenu::SettingsMenu(field, _) => {
+                self.draw_settings_menu(state, field);
+            },
+            GuiMenu::KeysMenu(selected, capturing, _) => {
+                self.draw_keys_menu(state, selected, capturing);
+            },
         }
 
-        self.canvas.present();
+        if let Some(path) = self.screenshot.take() {
+            match self.renderer.save_screen(&path) {
+                Ok(()) => log_system(&state.ecs, Severity::Info, tr_args("log.screenshot_saved", &[("path", path.display().to_string())])),
+                Err(e) => log_system(&state.ecs, Severity::Error, tr_args("log.screenshot_failed", &[("error", e)]))
+            }
+        }
+
+        self.renderer.present();
+        self.last_frame = (start.elapsed(), self.renderer.take_draw_calls());
     }
 
+    /// Screen size in cells.
+    fn screen(&self) -> Area {
+        let (width, height) = self.renderer.size();
+        Area::new(0, 0, width, height)
+    }
 
-    fn draw_tile(&mut self, x: u32, y: u32, tile_idx: u32) {
-        self.canvas.copy(&self.tileset, tile_rect(tile_idx),
-                    Rect::new((x * TILE_SIZE) as i32, (y * TILE_SIZE) as i32, TILE_SIZE, TILE_SIZE)).unwrap();
+    fn draw_widget(&mut self, widget: &Widget, area: Area) {
+        self.ui.layout(widget, area);
+        self.paint_cells();
     }
 
-    fn draw_tile_real_xy(&mut self, x: u32, y: u32, tile_idx: u32) {
-        self.canvas.copy(&self.tileset, tile_rect(tile_idx),
-                    Rect::new(x as i32, y as i32, TILE_SIZE, TILE_SIZE)).unwrap();
+    fn draw_widget_centered(&mut self, widget: &Widget) {
+        let screen = self.screen();
+        self.ui.layout_centered(widget, screen);
+        self.paint_cells();
     }
 
-    fn draw_text<T: ToString>(&mut self, x: u32, y: u32, text: T) {
-        let string: String = text.to_string();
-        for (i, char) in string.chars().enumerate() {
-            self.draw_tile(x + i as u32, y, char as u32);
+    fn paint_cells(&mut self) {
+        let batch = Batch::new(&self.ui.take_cells());
+        let (width, height) = self.renderer.cell_size();
+
+        for (background, runs) in batch.backgrounds.iter() {
+            let rects: Vec<Rect> = runs.iter().map(|run| Rect::new(run.x * width as i32, run.y * height as i32, run.width * width, run.height * height)).collect();
+            self.renderer.fill_rects(&rects, *background);
         }
+
+        for (color, symbols) in batch.symbols.iter() {
+            for (x, y, symbol) in symbols.iter() {
+                self.renderer.draw_glyph(*x, *y, *symbol, *color, None);
+            }
+        }
+    }
+
+    /// Finds the widget under the mouse in the last rendered frame.
+    pub fn hit(&self, x: i32, y: i32) -> Option<Hit> {
+        let (width, height) = self.renderer.cell_size();
+        self.ui.hit(x / width as i32, y / height as i32)
+    }
+
+
+    fn draw_help_menu(&mut self, state: &mut State, page: HelpPage) {
+        let title = format!("< {} >", tr_args("help.title", &[("page", page.get_text())]));
+
+        let content = match page {
+            HelpPage::About => Widget::Column((0..20).map(|_| Widget::label(tr("help.readme"), (200, 200, 200))).collect(), Align::Center),
+            HelpPage::Controls => self.controls(state)
+        };
+
+        let screen = self.screen();
+        self.draw_widget(&Widget::Column(vec![
+            Widget::label(title, (200, 100, 0)),
+            Widget::Spacer(1),
+            content
+        ], Align::Center), screen.inset(1));
     }
 
-    fn draw_text_real_xy<T: ToString>(&mut self, x: u32, y: u32, text: T) {
-        let string: String = text.to_string();
-        for (i, char) in string.chars().enumerate() {
-            self.draw_tile_real_xy(x + TILE_SIZE * i as u32, y, char as u32);
+    /// Lists current key bindings, in-game ones in the left column and the rest in the right one.
+    fn controls(&self, state: &State) -> Widget {
+        let mut columns = [vec![], vec![]];
+
+        for context in InputContext::all().iter() {
+            let column = &mut columns[if *context == InputContext::Game { 0 } else { 1 }];
+
+            column.push(Widget::label(context.get_text(), (200, 100, 0)));
+            for (action, keys) in state.bindings.describe(*context) {
+                column.push(Widget::Row(vec![
+                    Widget::label(format!(" {:<15}", action.get_text()), (150, 150, 150)),
+                    Widget::label(keys, (200, 200, 200))
+                ]));
+            }
+            column.push(Widget::Spacer(1));
         }
+
+        let [game, other] = columns;
+        Widget::Row(vec![
+            Widget::Spacer(1),
+            Widget::Column(game, Align::Left),
+            Widget::Fill,
+            Widget::Column(other, Align::Left),
+            Widget::Fill
+        ])
     }
 
 
-    fn draw_help_menu(&mut self) {
-        let (width, height) = self.canvas.output_size().unwrap();
+    fn draw_settings_menu(&mut self, state: &mut State, current_field: SettingsField) {
+        let items = SettingsField::all().iter()
+                                        .map(|field| (format!("{:<12}< {} >", field.get_text(), field.get_value(&state.config)), (125, 125, 125)))
+                                        .collect::<Vec<_>>();
+        self.draw_widget_centered(&Widget::panel(Some(tr("menu.settings")), DARK_BG_COLOR, Widget::Column(vec![
+            Widget::Spacer(1),
+            Widget::Row(vec![
+                Widget::Spacer(1),
+                Widget::List { id: "settings", items, selected: current_field.index(), color: (0, 150, 100) },
+                Widget::Spacer(1)
+            ]),
+            Widget::Spacer(1),
+            Widget::label(tr(if current_field == SettingsField::Keys { "settings.keys_hint" } else { "settings.hint" }), (100, 100, 100))
+        ], Align::Center)));
+    }
+
+    /// Finds which settings field drawn by `draw_settings_menu` is under the mouse.
+    pub fn settings_hit(&self, x: i32, y: i32) -> Option<SettingsField> {
+        match self.hit(x, y) {
+            Some(Hit { id: "settings", index }) => SettingsField::all().get(index).copied(),
+            _ => None
+        }
+    }
 
-        let text = "Help";
-        self.tileset.set_color_mod(200, 100, 0);
-        self.draw_text_real_xy(width / 2 - text.len() as u32 * TILE_SIZE / 2, TILE_SIZE / 2, text);
+    /// Every action with its keys, the ones changed from the defaults highlighted.
+    fn draw_keys_menu(&mut self, state: &mut State, selected: usize, capturing: bool) {
+        let rows = state.bindings.describe_all();
+        let context_width = rows.iter().map(|(context, _, _)| context.get_text().chars().count()).max().unwrap_or(0) + 1;
+        let action_width = rows.iter().map(|(_, action, _)| action.get_text().chars().count()).max().unwrap_or(0) + 1;
+        let items = rows.iter().map(|(context, action, keys)| {
+            let changed = state.config.keys.get(context.name()).is_some_and(|actions| actions.contains_key(action.name()));
+            let color = if changed { (200, 200, 200) } else { (125, 125, 125) };
+            (format!("{:<cw$}{:<aw$}{}", context.get_text(), action.get_text(), keys, cw = context_width, aw = action_width), color)
+        }).collect::<Vec<_>>();
+        let hint = match rows.get(selected) {
+            Some((_, action, _)) if capturing => Widget::label(tr_args("keys.press", &[("action", action.get_text())]), (200, 100, 0)),
+            _ => Widget::label(tr("keys.hint"), (100, 100, 100))
+        };
+
+        self.draw_widget_centered(&Widget::panel(Some(tr("settings.keys")), DARK_BG_COLOR, Widget::Column(vec![
+            hint,
+            Widget::Spacer(1),
+            Widget::Row(vec![
+                Widget::Spacer(1),
+                Widget::List { id: "keys", items, selected, color: (0, 150, 100) },
+                Widget::Spacer(1)
+            ])
+        ], Align::Center)));
+    }
 
-        self.tileset.set_color_mod(200, 200, 200);
-        for i in 0..20 {
-            let text = "You should take a look into README.md";
-            self.draw_text_real_xy(width / 2 - text.len() as u32 * TILE_SIZE / 2, TILE_SIZE / 2 + TILE_SIZE * (i + 2), text);
+    /// Finds which row of the key bindings menu is under the mouse.
+    pub fn keys_hit(&self, x: i32, y: i32) -> Option<usize> {
+        match self.hit(x, y) {
+            Some(Hit { id: "keys", index }) => Some(index),
+            _ => None
         }
     }
 
 
     fn draw_credits_menu(&mut self) {
-        let (width, height) = self.canvas.output_size().unwrap();
-        let text = "Author: ArturLukianov";
-        self.tileset.set_color_mod(200, 200, 200);
-        self.draw_text_real_xy(width / 2 - text.len() as u32 * TILE_SIZE / 2, height / 2 - TILE_SIZE / 2, "Author:");
-        self.tileset.set_color_mod(200, 0, 100);
-        self.draw_text_real_xy(width / 2 - text.len() as u32 * TILE_SIZE / 2 + TILE_SIZE * 8, height / 2 - TILE_SIZE / 2, "ArturLukianov");
+        self.draw_widget_centered(&Widget::Row(vec![
+            Widget::label(tr("credits.author"), (200, 200, 200)),
+            Widget::Spacer(1),
+            Widget::label("ArturLukianov", (200, 0, 100))
+        ]));
     }
 
 
     fn draw_main_menu(&mut self, state: &mut State) {
-        let (width, height) = self.canvas.output_size().unwrap();
-
-        let title = "Necronix";
-
         match self.menu {
             GuiMenu::MainMenu(button) => {
-                let button_text = button.get_text();
-                let button_color = button.get_color();
-
-                self.tileset.set_color_mod(255, 255, 255);
-                self.draw_text_real_xy(width / 2 - title.len() as u32 * TILE_SIZE / 2, height / 2 - TILE_SIZE / 2 - TILE_SIZE * 2, title);
-                self.tileset.set_color_mod(100, 100, 100);
-                self.draw_tile_real_xy(width / 2 - TILE_SIZE / 2 - TILE_SIZE * 2, height / 2 + TILE_SIZE / 2 - TILE_SIZE, '<' as u32);
-                self.draw_tile_real_xy(width / 2 - TILE_SIZE / 2 + TILE_SIZE * 2, height / 2 + TILE_SIZE / 2 - TILE_SIZE, '>' as u32);
-                self.tileset.set_color_mod(button_color.0, button_color.1, button_color.2);
-                self.draw_tile_real_xy(width / 2 - TILE_SIZE / 2, height / 2 + TILE_SIZE / 2 - TILE_SIZE, button.get_icon());
-                self.draw_text_real_xy(width / 2 - button_text.len() as u32 * TILE_SIZE / 2, height / 2 - TILE_SIZE / 2 + TILE_SIZE * 2, button_text);
+                let carousel = self.carousel(Some("Necronix".to_string()), button.get_icon(), button.get_color(), button.get_text());
+                self.draw_widget_centered(&carousel);
             },
             _ => {return;}
         }
 
     }
 
-    fn draw_map(&mut self, state: &mut State) {
-        let map = state.ecs.fetch::<Map>();
+    /// A `< icon >` selector with the button caption below.
+    fn carousel(&self, title: Option<String>, icon: u32, color: (u8, u8, u8), text: String) -> Widget {
+        let mut rows = vec![];
+        if let Some(title) = title {
+            rows.push(Widget::label(title, (255, 255, 255)));
+            rows.push(Widget::Spacer(1));
+        }
+        rows.push(Widget::Row(vec![
+            Widget::Button { id: "prev", text: "<".to_string(), color: (100, 100, 100) },
+            Widget::Spacer(1),
+            Widget::Icon { id: Some("select"), glyph: icon, color },
+            Widget::Spacer(1),
+            Widget::Button { id: "next", text: ">".to_string(), color: (100, 100, 100) }
+        ]));
+        rows.push(Widget::Spacer(1));
+        rows.push(Widget::Button { id: "select", text, color });
+        Widget::Column(rows, Align::Center)
+    }
 
-        self.canvas.set_draw_color(DARK_BG_COLOR.clone());
-        self.canvas.fill_rect(Rect::new(0, 0, TILE_SIZE * MAP_SIZE, TILE_SIZE * MAP_SIZE)).unwrap();
-        self.tileset.set_color_mod(100, 100, 100);
-        let mut y = 0;
-        let mut x = 0;
-        for tile in map.tiles.iter() {
-            if *tile == 1 {
-                self.draw_tile(x, y, 0xdb);
-            }
+    /// Finds which part of the carousel is under the mouse.
+    pub fn carousel_hit(&self, x: i32, y: i32) -> Option<CarouselHit> {
+        match self.hit(x, y)?.id {
+            "prev" => Some(CarouselHit::Prev),
+            "next" => Some(CarouselHit::Next),
+            "select" => Some(CarouselHit::Select),
+            _ => None
+        }
+    }
 
-            x += 1;
-            if x == map.width || x == MAP_SIZE {
-                x = 0;
-                y += 1;
-                if y == MAP_SIZE {
-                    break
+    /// Darkens the game behind a dialog.
+    fn draw_overlay(&mut self) {
+        self.renderer.shade(160);
+    }
+
+    fn draw_pause_menu(&mut self, button: PauseMenuButton) {
+        self.draw_overlay();
+        let carousel = self.carousel(None, button.get_icon(), button.get_color(), button.get_text());
+        self.draw_widget_centered(&Widget::panel(Some(tr("pause.title")), DARK_BG_COLOR, Widget::Column(vec![
+            Widget::Row(vec![Widget::Spacer(18)]),
+            carousel,
+            Widget::Spacer(1)
+        ], Align::Center)));
+    }
+
+    fn draw_game_over_menu(&mut self, state: &State, button: GameOverButton) {
+        let rule = match &state.ecs.fetch::<GameResult>().ended {
+            Some(rule) => rule.clone(),
+            None => return
+        };
+        self.draw_overlay();
+
+        let ecs = &state.ecs;
+        let stats = (*ecs.fetch::<GameStats>()).clone();
+        let units = ecs.read_storage::<Unit>().join().count();
+        let corruption = (ecs.fetch::<Map>().corrupted_share() * 100.).round() as u32;
+        let field = |label: String, value: String| Widget::Row(vec![
+            Widget::label(format!("{:<18}", label), (150, 150, 150)),
+            Widget::label(value, (200, 200, 200))
+        ]);
+        let carousel = self.carousel(None, button.get_icon(), button.get_color(), button.get_text());
+        self.draw_widget_centered(&Widget::panel(Some(rule.outcome.get_text()), DARK_BG_COLOR, Widget::Column(vec![
+            Widget::Row(vec![Widget::Spacer(28)]),
+            Widget::label(rule.condition.get_description(), rule.outcome.get_color()),
+            Widget::Spacer(1),
+            Widget::Column(vec![
+                field(tr("game_over.day"), ecs.fetch::<GameClock>().day().to_string()),
+                field(tr("game_over.corruption"), format!("{}%", corruption)),
+                field(tr("game_over.undead"), units.to_string()),
+                field(tr("game_over.undead_lost"), stats.undead_lost.to_string()),
+                field(tr("game_over.villagers_slain"), stats.villagers_slain.to_string())
+            ], Align::Left),
+            Widget::Spacer(1),
+            carousel,
+            Widget::Spacer(1)
+        ], Align::Center)));
+    }
+
+    fn draw_confirm_menu(&mut self, button: PauseMenuButton) {
+        self.draw_overlay();
+
+        self.draw_widget_centered(&Widget::panel(None, DARK_BG_COLOR, Widget::Column(vec![
+            Widget::Spacer(1),
+            Widget::label(tr_args("confirm.question", &[("action", button.get_text())]), (255, 255, 255)),
+            Widget::Spacer(1),
+            Widget::Row(vec![Widget::Spacer(1), Widget::label(tr("confirm.warning"), (200, 100, 0)), Widget::Spacer(1)]),
+            Widget::Spacer(1),
+            Widget::Row(vec![
+                Widget::Button { id: "yes", text: tr("confirm.yes"), color: button.get_color() },
+                Widget::Spacer(6),
+                Widget::Button { id: "no", text: tr("confirm.no"), color: (200, 200, 200) }
+            ]),
+            Widget::Spacer(1)
+        ], Align::Center)));
+    }
+
+    /// Finds which answer drawn by `draw_confirm_menu` is under the mouse.
+    pub fn confirm_hit(&self, x: i32, y: i32) -> Option<ConfirmHit> {
+        match self.hit(x, y)?.id {
+            "yes" => Some(ConfirmHit::Yes),
+            "no" => Some(ConfirmHit::No),
+            _ => None
+        }
+    }
+
+    fn draw_rename_menu(&mut self) {
+        self.draw_overlay();
+
+        let text = format!("{:<width$}", format!("{}_", self.text_input), width = MAX_LENGTH + 1);
+        self.draw_widget_centered(&Widget::panel(Some(tr("rename.title")), DARK_BG_COLOR, Widget::Column(vec![
+            Widget::Spacer(1),
+            Widget::Row(vec![Widget::Spacer(1), Widget::label(text, (255, 255, 255)), Widget::Spacer(1)]),
+            Widget::Spacer(1),
+            Widget::Row(vec![Widget::Spacer(1), Widget::label(tr("rename.hint"), (100, 100, 100)), Widget::Spacer(1)])
+        ], Align::Center)));
+    }
+
+    fn draw_game(&mut self, state: &mut State, tab: GameMenuTab) {
+        self.draw_map(state);
+        self.draw_unit_list(state);
+        self.draw_menu(state, tab);
+        self.draw_statusline(state, tab);
+        if self.show_timings {
+            self.draw_timings(state);
+        }
+    }
+
+    fn draw_map(&mut self, state: &mut State) {
+        let map = state.ecs.fetch::<Map>();
+        let view = MapView { camera: self.camera, size: (MAP_SIZE, MAP_SIZE), fog: true, lit: true };
+
+        let lighting = state.ecs.fetch::<Lighting>();
+        let key = cache_key((view, map.revision, lighting.revision, self.map_generation));
+        self.renderer.layer("terrain", key, Area::new(0, 0, MAP_SIZE, MAP_SIZE), &mut |renderer| draw_terrain(renderer, &map, &lighting, view));
+        if let Some(preview) = self.aim.and_then(|aim| aim_preview(&state.ecs, aim)) {
+            let (width, height) = self.renderer.cell_size();
+            let color = if preview.check.is_ok() { AIM_COLOR } else { AIM_BLOCKED_COLOR };
+            let aim = self.aim.unwrap();
+            for (x, y) in preview.tiles {
+                if let Some((cell_x, cell_y)) = view.cell(x, y) {
+                    let color = if (x, y) == (aim.x, aim.y) { bright(color) } else { color };
+                    self.renderer.fill_rects(&[Rect::new(cell_x * width as i32, cell_y * height as i32, width, height)], color);
                 }
             }
         }
+        let step = (self.started.elapsed().as_millis() / ANIMATION_STEP_MS) as u64;
+        draw_entities(&mut self.renderer, &state.ecs, &map, view, state.selected, step, RenderLayer::Decal..=RenderLayer::Effect);
+        self.draw_effects(state, &map, view);
+        draw_entities(&mut self.renderer, &state.ecs, &map, MapView { lit: false, ..view }, state.selected, step, RenderLayer::Marker..=RenderLayer::Marker);
 
-        let renderables = state.ecs.read_storage::<Renderable>();
-        let positions = state.ecs.read_storage::<Position>();
+        self.ui.add_hit(Area::new(0, 0, MAP_SIZE, MAP_SIZE), Hit { id: "map", index: 0 });
+    }
 
-        for (i, (render, pos)) in (&renderables, &positions).join().enumerate() {
-            if pos.x >= MAP_SIZE && pos.y >= MAP_SIZE {
+    /// Draws the whole map with everything on it, without the fog of war but in the current light, and saves it as a PNG image.
+    pub fn export_map(&mut self, state: &State, path: &Path) -> Result<(), String> {
+        let map = state.ecs.fetch::<Map>();
+        let lighting = state.ecs.fetch::<Lighting>();
+        let view = MapView { camera: (0, 0), size: (map.width, map.height), fog: false, lit: true };
+        self.renderer.export(Area::new(0, 0, map.width, map.height), path, &mut |renderer| {
+            draw_terrain(renderer, &map, &lighting, view);
+            draw_entities(renderer, &state.ecs, &map, view, None, 0, RenderLayer::Decal..=RenderLayer::Marker);
+        })
+    }
+
+    /// Starts the effects systems asked for and moves the running ones a frame on, whether the game is paused or not.
+    fn draw_effects(&mut self, state: &State, map: &Map, view: MapView) {
+        let mut rng = rand::thread_rng();
+        for request in state.ecs.write_resource::<EffectRequests>().queue.drain(..) {
+            self.effects.spawn(&request, &mut rng);
+        }
+        self.effects.advance();
+
+        for ((x, y), symbol, color) in self.effects.sprites() {
+            if x < 0 || y < 0 || (view.fog && !map.is_revealed(x as u32, y as u32)) {
                 continue;
             }
-
-            if i == state.selected_unit_index {
-                self.tileset.set_color_mod(render.color.0 / 2 * 3, render.color.1 / 2 * 3, render.color.2 / 2 * 3);
-            } else {
-                self.tileset.set_color_mod(render.color.0, render.color.1, render.color.2);
+            if let Some((x, y)) = view.cell(x as u32, y as u32) {
+                self.renderer.draw_glyph(x, y, symbol, color, None);
             }
-            self.draw_tile(pos.x, pos.y, render.glyph);
+        }
+    }
+
+    /// Saves the next frame as a PNG image once it is drawn.
+    pub fn request_screenshot(&mut self, path: PathBuf) {
+        self.screenshot = Some(path);
+    }
+
+    /// Map cell under the mouse, taking the camera into account.
+    pub fn map_cell(&self, x: i32, y: i32) -> Option<(u32, u32)> {
+        let (width, height) = self.renderer.cell_size();
+        let (cell_x, cell_y) = (x / width as i32, y / height as i32);
+        if cell_x < 0 || cell_y < 0 || cell_x >= MAP_SIZE as i32 || cell_y >= MAP_SIZE as i32 {
+            return None;
+        }
+        Some((self.camera.0 + cell_x as u32, self.camera.1 + cell_y as u32))
+    }
+
+    /// Moves the map view, keeping it inside the map.
+    pub fn pan_camera(&mut self, state: &State, dx: i32, dy: i32) {
+        let map = state.ecs.fetch::<Map>();
+        let max_x = map.width.saturating_sub(MAP_SIZE) as i32;
+        let max_y = map.height.saturating_sub(MAP_SIZE) as i32;
+
+        self.camera.0 = (self.camera.0 as i32 + dx).clamp(0, max_x) as u32;
+        self.camera.1 = (self.camera.1 as i32 + dy).clamp(0, max_y) as u32;
+    }
+
+    pub fn center_camera(&mut self, state: &State, x: u32, y: u32) {
+        self.camera = (0, 0);
+        self.pan_camera(state, x as i32 - MAP_SIZE as i32 / 2, y as i32 - MAP_SIZE as i32 / 2);
+    }
+
+    /// Moves the spell cursor, keeping it on the map and in view.
+    pub fn move_aim(&mut self, state: &State, dx: i32, dy: i32) {
+        let (width, height) = {
+            let map = state.ecs.fetch::<Map>();
+            (map.width, map.height)
+        };
+        let aim = match self.aim.as_mut() {
+            Some(aim) => aim,
+            None => return
+        };
+        aim.x = (aim.x as i32 + dx).clamp(0, width.saturating_sub(1) as i32) as u32;
+        aim.y = (aim.y as i32 + dy).clamp(0, height.saturating_sub(1) as i32) as u32;
+        let (x, y) = (aim.x, aim.y);
+        if (MapView { camera: self.camera, size: (MAP_SIZE, MAP_SIZE), fog: false, lit: false }).cell(x, y).is_none() {
+            self.center_camera(state, x, y);
         }
     }
 
     fn draw_unit_list(&mut self, state: &mut State) {
+        let entities = state.ecs.entities();
         let units = state.ecs.read_storage::<Unit>();
         let renderables = state.ecs.read_storage::<Renderable>();
         let map = state.ecs.fetch::<Map>();
@@ -287,13 +940,9 @@ impl GUI<'_> {
         let mut x = 0;
         let mut y = 0;
 
-        for (i, (_unit, render)) in (&units, &renderables).join().enumerate() {
-            if i == state.selected_unit_index {
-                self.tileset.set_color_mod(render.color.0 / 2 * 3, render.color.1 / 2 * 3, render.color.2 / 2 * 3);
-            } else {
-                self.tileset.set_color_mod(render.color.0, render.color.1, render.color.2);
-            }
-            self.draw_tile(x, MAP_SIZE + y, render.glyph);
+        for (entity, _unit, render) in (&entities, &units, &renderables).join() {
+            let color = if Some(entity) == state.selected { (render.color.0 / 2 * 3, render.color.1 / 2 * 3, render.color.2 / 2 * 3) } else { render.color };
+            self.renderer.draw_glyph(x as i32, (MAP_SIZE + y) as i32, Symbol::Glyph(render.glyph), color, None);
 
             x += 1;
             if x == map.width {
@@ -304,102 +953,527 @@ impl GUI<'_> {
     }
 
     fn draw_menu(&mut self, state: &mut State, current_tab: GameMenuTab) {
-        let (width, height) = self.canvas.output_size().unwrap();
-
-        let mut renderable: Option<Renderable> = None;
-        {
-            let units = state.ecs.read_storage::<Unit>();
-            let renderables = state.ecs.read_storage::<Renderable>();
+        let renderable = state.selected.and_then(|entity| state.ecs.read_storage::<Renderable>().get(entity).cloned());
+
+        let tabs = GameMenuTab::all().iter().map(|tab| {
+            let selected = *tab == current_tab;
+            match (tab, renderable.as_ref()) {
+                (GameMenuTab::Unit, Some(renderable)) if selected => (renderable.glyph, bright(renderable.color)),
+                (GameMenuTab::Unit, Some(renderable)) => (renderable.glyph, renderable.color),
+                _ => (tab_default_icon(tab), if selected { (200, 200, 200) } else { (125, 125, 125) })
+            }
+        }).collect();
+
+        let content = match current_tab {
+            GameMenuTab::Log => self.log_view(state),
+            GameMenuTab::Unit => self.inspector(state)
+        };
+
+        let screen = self.screen();
+        let side = Area::new(MAP_SIZE as i32, 0, screen.width.saturating_sub(MAP_SIZE), screen.height.saturating_sub(1));
+        let minimap_height = MINIMAP_SIZE.min(side.height / 2);
+        let necromancer = self.necromancer_panel(state);
+        let panel_height = if necromancer.is_some() { NECROMANCER_PANEL_SIZE.min(side.height - minimap_height) } else { 0 };
+        let menu = Area::new(side.x, side.y, side.width, side.height - minimap_height - panel_height);
+        self.draw_widget(&Widget::Column(vec![
+            Widget::TabBar { id: "tabs", tabs, selected: current_tab.index(), background: DARK_BG_COLOR, selected_background: BG_COLOR },
+            content
+        ], Align::Left), menu);
+        if let Some(panel) = necromancer {
+            self.draw_widget(&panel, Area::new(side.x, side.y + menu.height as i32, side.width, panel_height));
+        }
+        self.draw_minimap(state, Area::new(side.x, side.y + (menu.height + panel_height) as i32, side.width, minimap_height));
+    }
 
-            for (i, (_, render)) in (&units, &renderables).join().enumerate() {
-                if i == state.selected_unit_index {
-                    renderable = Some(*render);
-                    break;
-                }
+    /// The necromancer's health, mana and undead upkeep, always in view while it lives.
+    fn necromancer_panel(&self, state: &State) -> Option<Widget> {
+        let ecs = &state.ecs;
+        let entities = ecs.entities();
+        let necromancers = ecs.read_storage::<Necromancer>();
+        let (entity, necromancer) = (&entities, &necromancers).join().next()?;
+
+        let name = ecs.read_storage::<Name>().get(entity).map_or_else(|| tr("unit.unnamed"), |name| name.name.clone());
+        let (health, max_health) = ecs.read_storage::<Living>().get(entity).map_or((0, 0), |living| (living.health, living.max_health));
+        let bar = |label: String, value: i32, max: i32, color| vec![
+            Widget::label(label, (150, 150, 150)),
+            Widget::Spacer(1),
+            Widget::ProgressBar { value: value.max(0) as f32 / max.max(1) as f32, width: 10, color },
+            Widget::Spacer(1),
+            Widget::label(format!("{}/{}", value, max), (200, 200, 200))
+        ];
+        let mut stats = bar(tr("necromancer.health"), health, max_health, (200, 0, 0));
+        stats.push(Widget::Spacer(2));
+        stats.extend(bar(tr("necromancer.mana"), necromancer.mana, necromancer.max_mana, MANA_COLOR));
+        let (count, cap) = (ecs.read_storage::<Unit>().join().count(), necromancer.upkeep_cap());
+        let upkeep_color = if count > cap { (200, 50, 50) } else { (150, 150, 150) };
+        let upkeep = Widget::label(tr_args("necromancer.upkeep", &[("count", count.to_string()), ("cap", cap.to_string())]), upkeep_color);
+        let aim = self.aim.and_then(|aim| aim_preview(ecs, aim));
+        let status = if let Some(preview) = aim {
+            match preview.check {
+                Ok(()) => Widget::label(tr_args("aim.spell", &[("spell", Spell::get_text(&preview.id)), ("cost", preview.spell.cost.to_string()),
+                                                               ("range", preview.spell.range.to_string())]), (170, 60, 220)),
+                Err(reason) => Widget::label(reason, (200, 50, 50))
             }
+        } else if state.possessing {
+            Widget::label(tr("necromancer.possessed"), (170, 60, 220))
+        } else {
+            Widget::label(tr("necromancer.free"), (150, 150, 150))
+        };
+
+        Some(Widget::panel(Some(name), DARK_BG_COLOR, Widget::Column(vec![Widget::Row(stats), upkeep, status], Align::Left)))
+    }
+
+    fn draw_minimap(&mut self, state: &State, area: Area) {
+        self.draw_widget(&Widget::panel(Some(tr("minimap.title")), DARK_BG_COLOR, Widget::Fill), area);
+
+        let map = state.ecs.fetch::<Map>();
+        let (width, height) = self.renderer.cell_size();
+        let inner = area.inset(1);
+        let scale = (inner.width * width / map.width.max(1)).min(inner.height * height / map.height.max(1));
+        if scale == 0 {
+            self.minimap_rect = None;
+            return;
         }
 
-        self.canvas.set_draw_color(DARK_BG_COLOR.clone());
-        self.canvas.fill_rect(Rect::new((TILE_SIZE * MAP_SIZE) as i32, 0, width - MAP_SIZE * TILE_SIZE, TILE_SIZE)).unwrap();
+        let rect = Rect::new(inner.x * width as i32, inner.y * height as i32, map.width * scale, map.height * scale);
+        let key = minimap_key(&state.ecs, self.map_generation);
+        self.renderer.image("minimap", key, (map.width, map.height), &|| minimap_pixels(&state.ecs), rect);
+        self.renderer.outline(Rect::new(rect.x() + (self.camera.0 * scale) as i32, rect.y() + (self.camera.1 * scale) as i32,
+                                        MAP_SIZE.min(map.width) * scale, MAP_SIZE.min(map.height) * scale), (200, 200, 200));
+
+        self.minimap_rect = Some((rect, scale));
+        self.ui.add_hit(inner, Hit { id: "minimap", index: 0 });
+    }
+
+    /// Map tile under the mouse on the minimap, clamped to the map.
+    pub fn minimap_cell(&self, state: &State, x: i32, y: i32) -> Option<(u32, u32)> {
+        let (rect, scale) = self.minimap_rect?;
+        let map = state.ecs.fetch::<Map>();
+        let cell_x = ((x - rect.x()).max(0) as u32 / scale).min(map.width.saturating_sub(1));
+        let cell_y = ((y - rect.y()).max(0) as u32 / scale).min(map.height.saturating_sub(1));
+        Some((cell_x, cell_y))
+    }
 
-        for i in 0..GameMenuTab::variants_count() {
-            let tab = GameMenuTab::from_u8(i as u8);
+    /// Forgets the cached map layers, running effects and aimed spell for a new or loaded game.
+    pub fn invalidate_map(&mut self) {
+        self.map_generation += 1;
+        self.effects.clear();
+        self.aim = None;
+    }
 
-            let name = tab_name(&tab);
-            let icon = tab_default_icon(&tab);
+    fn log_view(&self, state: &State) -> Widget {
+        let log = state.ecs.fetch::<Gamelog>();
 
-            if tab == current_tab {
-                self.canvas.set_draw_color(BG_COLOR.clone());
-                self.canvas.fill_rect(Rect::new((TILE_SIZE * (MAP_SIZE + i as u32 * 3)) as i32, 0, 3 * TILE_SIZE, TILE_SIZE)).unwrap();
-                self.tileset.set_color_mod(200, 200, 200);
-            } else {
-                self.tileset.set_color_mod(125, 125, 125);
-            }
+        let mut filters = vec![];
+        for category in LogCategory::all().iter() {
+            let (mark, color) = if log.shows(*category) { ('x', category.get_color()) } else { (' ', (100, 100, 100)) };
+            filters.push(Widget::Button { id: category.filter_id(), text: format!("[{}] {}", mark, category.get_text()), color });
+            filters.push(Widget::Spacer(1));
+        }
 
-            match tab {
-                GameMenuTab::Unit => {
-                    if let Some(renderable) = renderable {
-                        if tab == current_tab { self.tileset.set_color_mod(renderable.color.0 * 2, renderable.color.1 * 2, renderable.color.2 * 2); }
-                        else { self.tileset.set_color_mod(renderable.color.0, renderable.color.1, renderable.color.2); }
-                        self.draw_tile(MAP_SIZE + i as u32 * 3 + 1, 0, renderable.glyph);
-                    } else {
-                        self.draw_tile(MAP_SIZE + i as u32 * 3 + 1, 0, icon);
-                    }
-                },
-                _ => {
-                    self.draw_tile(MAP_SIZE + i as u32 * 3 + 1, 0, icon);
-                }
+        Widget::Column(vec![
+            Widget::Row(filters),
+            Widget::ScrollView { id: "log", lines: log.visible().map(|entry| (entry.get_text(), entry.get_color())).collect() }
+        ], Align::Left)
+    }
+
+    pub fn scroll_log(&mut self, lines: i32) {
+        self.ui.scroll("log", lines);
+    }
+
+    /// Everything known about the selected entity, whatever kind it is.
+    fn inspector(&self, state: &State) -> Widget {
+        let ecs = &state.ecs;
+        let entity = match state.selected.filter(|entity| ecs.entities().is_alive(*entity)) {
+            Some(entity) => entity,
+            None => return Widget::label(tr("inspector.nothing_selected"), (150, 150, 150))
+        };
+
+        let field = |label: String, value: String| Widget::Row(vec![
+            Widget::label(format!("{:<10}", label), (150, 150, 150)),
+            Widget::label(value, (200, 200, 200))
+        ]);
+        let mut rows = vec![];
+
+        let kind = entity_kind(ecs, entity);
+        let is_unit = ecs.read_storage::<Unit>().contains(entity);
+        let name = match ecs.read_storage::<Name>().get(entity) {
+            Some(name) => name.name.clone(),
+            None if is_unit => tr("unit.unnamed"),
+            None => kind.clone()
+        };
+        let color = ecs.read_storage::<Renderable>().get(entity).map_or((200, 200, 200), |render| bright(render.color));
+        let mut title = vec![Widget::label(name, color)];
+        if let Some(position) = ecs.read_storage::<Position>().get(entity) {
+            title.push(Widget::Spacer(1));
+            title.push(Widget::label(format!("{}:{}", position.x, position.y), (200, 200, 200)));
+        }
+        rows.push(Widget::Row(title));
+        rows.push(field(tr("inspector.type"), kind));
+
+        if let Some(faction) = ecs.read_storage::<Faction>().get(entity) {
+            rows.push(field(tr("inspector.faction"), faction.faction.get_text()));
+        }
+
+        if let Some(living) = ecs.read_storage::<Living>().get(entity) {
+            let value = living.health.max(0) as f32 / living.max_health.max(1) as f32;
+            let color = if value > 0.5 { (0, 150, 0) } else if value > 0.25 { (200, 150, 0) } else { (200, 0, 0) };
+            rows.push(Widget::Row(vec![
+                Widget::label(format!("{:<10}", tr("inspector.health")), (150, 150, 150)),
+                Widget::ProgressBar { value, width: 10, color },
+                Widget::Spacer(1),
+                Widget::label(format!("{}/{}", living.health, living.max_health), (200, 200, 200))
+            ]));
+        }
+
+        if let Some(necromancer) = ecs.read_storage::<Necromancer>().get(entity) {
+            rows.push(Widget::Row(vec![
+                Widget::label(format!("{:<10}", tr("inspector.mana")), (150, 150, 150)),
+                Widget::ProgressBar { value: necromancer.mana.max(0) as f32 / necromancer.max_mana.max(1) as f32, width: 10, color: MANA_COLOR },
+                Widget::Spacer(1),
+                Widget::label(format!("{}/{}", necromancer.mana, necromancer.max_mana), (200, 200, 200))
+            ]));
+        }
+
+        if let Some(integrity) = ecs.read_storage::<Integrity>().get(entity) {
+            let value = integrity.integrity.max(0) as f32 / integrity.max_integrity.max(1) as f32;
+            let color = if value > 0.25 { (140, 130, 100) } else { (200, 0, 0) };
+            rows.push(Widget::Row(vec![
+                Widget::label(format!("{:<10}", tr("inspector.integrity")), (150, 150, 150)),
+                Widget::ProgressBar { value, width: 10, color },
+                Widget::Spacer(1),
+                Widget::label(format!("{}/{}", integrity.integrity, integrity.max_integrity), (200, 200, 200))
+            ]));
+        }
+
+        if let Some(unit) = ecs.read_storage::<Unit>().get(entity) {
+            rows.push(field(tr("inspector.mission"), unit.mission.get_description()));
+        }
+        if let Some(queue) = ecs.read_storage::<MissionQueue>().get(entity) {
+            for (i, mission) in queue.missions.iter().enumerate() {
+                let label = if i == 0 { tr("inspector.queue") } else { String::new() };
+                rows.push(field(label, format!("{}. {}", i + 1, mission.get_description())));
             }
         }
 
-        match current_tab {
-            GameMenuTab::Log => {
-                self.draw_log(state, MAP_SIZE, 1);
-            },
-            GameMenuTab::Unit => {
-                self.draw_unit_info(state, MAP_SIZE, 1);
-            },
-            _ => {}
+        if let Some(physical) = ecs.read_storage::<Physical>().get(entity) {
+            rows.push(field(tr("inspector.weight"), physical.weight.to_string()));
+            rows.push(field(tr("inspector.size"), physical.size.to_string()));
+        }
+
+        if let Some(inventory) = ecs.read_storage::<Inventory>().get(entity) {
+            let items: Vec<String> = inventory.items.iter().map(|(material, amount)| format!("{} {}", amount, material.get_text())).collect();
+            let value = if items.is_empty() { tr("inspector.nothing") } else { items.join(", ") };
+            rows.push(field(tr("inspector.carrying"), value));
+        }
+
+        if let Some(effects) = ecs.read_storage::<StatusEffects>().get(entity) {
+            let active: Vec<String> = effects.effects.iter().map(|(effect, ticks)| format!("{} ({})", effect.get_text(), ticks)).collect();
+            let value = if active.is_empty() { tr("inspector.none") } else { active.join(", ") };
+            rows.push(field(tr("inspector.effects"), value));
         }
+
+        if let Some(kills) = ecs.read_storage::<Kills>().get(entity) {
+            rows.push(field(tr("inspector.kills"), kills.count.to_string()));
+        }
+
+        Widget::Column(rows, Align::Left)
     }
 
-    fn draw_log(&mut self, state: &mut State, x: u32, y: u32) {
-        self.tileset.set_color_mod(100, 200, 200);
-        for (i, entry) in state.log.entries.iter().rev().enumerate() {
-            self.draw_text(x, y + i as u32, entry);
+    fn draw_statusline(&mut self, state: &mut State, current_tab: GameMenuTab) {
+        let clock = state.ecs.fetch::<GameClock>();
+        let time = clock.get_description();
+        let (sky, sky_color) = if clock.daylight() > 0. { (15, (230, 200, 80)) } else { (9, (150, 160, 220)) };
+        drop(clock);
+        let corruption = (state.ecs.fetch::<Map>().corrupted_share() * 100.).round() as u32;
+        let speed = &state.speed;
+
+        let mut items = vec![
+            Widget::Icon { id: None, glyph: 7, color: (200, 200, 200) },
+            Widget::Spacer(1),
+            Widget::label(tab_name(&current_tab), (200, 200, 200)),
+            Widget::Fill,
+            Widget::label(tr_args("corruption.share", &[("percent", corruption.to_string())]), FactionType::Undead.get_color()),
+            Widget::Spacer(1),
+            Widget::Icon { id: None, glyph: sky, color: sky_color },
+            Widget::Spacer(1),
+            Widget::label(time, (200, 200, 200)),
+            Widget::Spacer(1)
+        ];
+        if speed.paused {
+            items.push(Widget::label(tr("clock.paused"), (200, 100, 0)));
+            items.push(Widget::Spacer(1));
         }
+        let color = if speed.paused { (200, 100, 0) } else { (200, 200, 200) };
+        items.push(Widget::ProgressBar { value: speed.level_fraction(), width: SPEED_LEVELS.len() as u32, color });
+        items.push(Widget::Spacer(1));
+
+        let screen = self.screen();
+        let area = Area::new(0, screen.height as i32 - 1, screen.width, 1);
+        self.draw_widget(&Widget::Panel { title: None, border: false, background: Some(DARK_BG_COLOR), child: Box::new(Widget::Row(items)) }, area);
     }
 
-    fn draw_unit_info(&mut self, state: &mut State, x: u32, y: u32) {
-        let units = state.ecs.read_storage::<Unit>();
-        let positions = state.ecs.read_storage::<Position>();
-        let renderables = state.ecs.read_storage::<Renderable>();
-        let entities = state.ecs.entities();
-        let names = state.ecs.read_storage::<Name>();
-
-        for (i, (unit, entity, position, render)) in (&units, &entities, &positions, &renderables).join().enumerate() {
-            if i == state.selected_unit_index {
-                self.tileset.set_color_mod(render.color.0 * 2, render.color.1 * 2, render.color.2 * 2);
-                let mut name = "Unnamed";
-                self.draw_text(x, y, "Unnamed");
-                self.tileset.set_color_mod(200, 200, 200);
-                self.draw_text(x + name.len() as u32 + 1, y, format!("{}:{}", position.x, position.y));
-                self.tileset.set_color_mod(150, 150, 150);
-                self.draw_text(x, y + 1, unit.mission.get_description());
-
-                break;
-            }
+    fn draw_timings(&mut self, state: &mut State) {
+        let timings = state.ecs.fetch::<SystemTimings>().snapshot();
+        let (frame_time, draw_calls) = self.last_frame;
+
+        let mut lines = vec![Widget::label(format!("frame {}us", frame_time.as_micros()), (200, 150, 100)),
+                             Widget::label(format!("draws {}", draw_calls), (200, 150, 100))];
+        lines.extend(timings.iter().map(|(name, time)| Widget::label(format!("{} {}us", name, time.as_micros()), (200, 200, 100))));
+        let area = Area::new(0, 0, MAP_SIZE, lines.len() as u32);
+        self.draw_widget(&Widget::Column(lines, Align::Left), area);
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::GameSpeed;
+    use crate::components::{FactionType, MaterialType, Mission, Position, Profession, RenderLayer, StatusEffect, UndeadType};
+    use crate::dispatcher;
+    use crate::input::{Action, KeyBindings};
+    use crate::locale;
+    use crate::renderer::MemoryRenderer;
+    use crate::rules::{Condition, Outcome, Rule};
+    use std::fs;
+
+    /// Compares a screen with `tests/snapshots/<name>.txt`. Run with `UPDATE_SNAPSHOTS=1` to rewrite the file.
+    fn assert_snapshot(name: &str, text: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(format!("{}.txt", name));
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, text).unwrap();
         }
+        let expected = fs::read_to_string(&path).unwrap_or_default();
+        assert!(expected == text, "{} differs from the snapshot:\n{}", path.display(), text);
     }
 
-    fn draw_statusline(&mut self, state: &mut State, current_tab: GameMenuTab) {
-        let (width, height) = self.canvas.output_size().unwrap();
-        self.canvas.set_draw_color(DARK_BG_COLOR.clone());
-        self.canvas.fill_rect(Rect::new(0, (height - TILE_SIZE) as i32, width, TILE_SIZE)).unwrap();
+    fn test_state() -> State {
+        locale::set_language("en").unwrap();
+        let mut state = State {
+            ecs: World::new(),
+            selected: None,
+            speed: GameSpeed::new(13),
+            unsaved: false,
+            config: Config::default(),
+            bindings: KeyBindings::defaults(),
+            possessing: false
+        };
+        dispatcher::register_components(&mut state.ecs);
+        dispatcher::build_dispatcher(&mut state.ecs);
+        let mut map = Map::new(15, 15);
+        map.revealed.clear();
+        state.ecs.insert(map);
+        state.ecs.insert(GameClock::default());
+
+        let unit = state.ecs.create_entity()
+                            .with(Position{ x: 2, y: 3 })
+                            .with(Renderable::new(UndeadType::Skeleton.get_glyph(), (100, 100, 100), RenderLayer::Unit))
+                            .with(Name{ name: "Morrow".to_string() })
+                            .with(Unit{ mission: Mission::Stay, kind: UndeadType::Skeleton })
+                            .with(Faction{ faction: FactionType::Undead })
+                            .with(Living::new(10))
+                            .with(Integrity::new(UndeadType::Skeleton.max_integrity()))
+                            .build();
+        let villager = Villager{ given_name: "Hild".to_string(), profession: Profession::Farmer };
+        state.ecs.create_entity()
+                 .with(Position{ x: 9, y: 7 })
+                 .with(Renderable::new(2, (150, 120, 80), RenderLayer::Unit))
+                 .with(Name{ name: villager.title() })
+                 .with(villager)
+                 .with(Faction{ faction: FactionType::Villagers })
+                 .build();
+        state.ecs.create_entity()
+                 .with(Position{ x: 7, y: 8 })
+                 .with(Renderable::new('@' as u32, (170, 60, 220), RenderLayer::Unit))
+                 .with(Name{ name: "Vesna the Necromancer".to_string() })
+                 .with(Necromancer{ mana: 20, ..Necromancer::new(50, vec![]) })
+                 .with(Faction{ faction: FactionType::Undead })
+                 .with(Living::new(30))
+                 .build();
+        state.selected = Some(unit);
+        state
+    }
+
+    #[test]
+    fn higher_layers_are_drawn_on_top() {
+        let mut world = World::new();
+        world.insert(Map::new(3, 3));
+        world.register::<Renderable>();
+        world.register::<Position>();
+        world.create_entity().with(Position{ x: 1, y: 1 }).with(Renderable::new('u' as u32, (200, 0, 0), RenderLayer::Unit)).build();
+        world.create_entity().with(Position{ x: 1, y: 1 })
+             .with(Renderable{ background: Some((9, 9, 9)), ..Renderable::new('=' as u32, (0, 200, 0), RenderLayer::Item) }).build();
+        world.create_entity().with(Position{ x: 0, y: 0 })
+             .with(Renderable{ frames: vec!['a' as u32, 'b' as u32], ..Renderable::new('a' as u32, (0, 0, 200), RenderLayer::Effect) }).build();
+
+        let mut renderer = MemoryRenderer::new(3, 3);
+        let view = MapView { camera: (0, 0), size: (3, 3), fog: false, lit: true };
+        draw_entities(&mut renderer, &world, &world.fetch::<Map>(), view, None, 1, RenderLayer::Decal..=RenderLayer::Marker);
+
+        let stacked = renderer.cell(1, 1).unwrap();
+        assert_eq!((stacked.c, stacked.color, stacked.background), ('u', (200, 0, 0), (9, 9, 9)));
+        assert_eq!(renderer.cell(0, 0).unwrap().c, 'b');
+    }
 
-        self.tileset.set_color_mod(200, 200, 200);
-        self.draw_tile_real_xy(0, height - TILE_SIZE, 7);
-        self.draw_text_real_xy(2 * TILE_SIZE, height - TILE_SIZE, tab_name(&current_tab));
+    #[test]
+    fn main_menu_snapshot() {
+        let mut state = test_state();
+        let mut gui = GUI::new(MemoryRenderer::new(60, 24));
+        gui.render(&mut state);
+        assert_snapshot("main_menu", &gui.renderer.text());
     }
 
+    #[test]
+    fn game_screen_snapshot() {
+        let mut state = test_state();
+        let mut gui = GUI::new(MemoryRenderer::new(60, 24));
+        gui.menu = GuiMenu::GameMenu(GameMenuTab::Unit);
+        gui.render(&mut state);
+        assert_snapshot("game_unit_tab", &gui.renderer.text());
+        assert_eq!(gui.renderer.cell(2, 3).unwrap().c, crate::cp437::to_char(UndeadType::Skeleton.get_glyph()));
+    }
+
+    #[test]
+    fn narrow_windows_cut_the_status_line() {
+        let mut state = test_state();
+        state.speed.paused = true;
+        let expected = [
+            ((1, 1), "•"),
+            ((8, 2), "• UnitCo"),
+            ((20, 4), "• UnitCorruption 0%"),
+            ((30, 10), "• UnitCorruption 0% ☼ Day 1 08"),
+            ((60, 12), "• Unit             Corruption 0% ☼ Day 1 08:00 PAUSED ██░░░")
+        ];
+        for ((width, height), status) in expected {
+            let mut gui = GUI::new(MemoryRenderer::new(width, height));
+            gui.menu = GuiMenu::GameMenu(GameMenuTab::Unit);
+            gui.render(&mut state);
+            let text = gui.renderer.text();
+            assert_eq!(text.lines().count(), height as usize, "{}x{}:\n{}", width, height, text);
+            assert_eq!(text.lines().last(), Some(status), "{}x{}:\n{}", width, height, text);
+        }
+    }
+
+    #[test]
+    fn inspector_lists_what_the_entity_has() {
+        let mut state = test_state();
+        let unit = state.selected.unwrap();
+        state.ecs.write_storage::<MissionQueue>().insert(unit, MissionQueue { missions: vec![Mission::GoTo(4, 5), Mission::Stay] }).unwrap();
+        state.ecs.write_storage::<Inventory>().insert(unit, Inventory { items: vec![(MaterialType::Logs, 2)] }).unwrap();
+        let mut effects = StatusEffects::default();
+        effects.add(StatusEffect::Slowed, 12);
+        state.ecs.write_storage::<StatusEffects>().insert(unit, effects).unwrap();
+        state.ecs.write_storage::<Kills>().insert(unit, Kills { count: 3 }).unwrap();
+        let tree = state.ecs.create_entity()
+                            .with(Position{ x: 5, y: 5 })
+                            .with(Renderable::new(6, (0, 100, 0), RenderLayer::Building))
+                            .with(Choppable{ chops_into: MaterialType::Logs })
+                            .with(Faction{ faction: FactionType::Nature })
+                            .with(Physical{ weight: 500, size: 3 })
+                            .build();
+
+        let lines = |state: &State| {
+            let mut gui = GUI::new(MemoryRenderer::new(40, 14));
+            let inspector = gui.inspector(state);
+            gui.draw_widget(&inspector, Area::new(0, 0, 40, 14));
+            gui.renderer.text()
+        };
+
+        assert_snapshot("inspector_unit", &lines(&state));
+        state.selected = Some(tree);
+        assert_snapshot("inspector_tree", &lines(&state));
+    }
+
+    #[test]
+    fn key_bindings_menu_prompts_for_a_key() {
+        let mut state = test_state();
+        let rows = state.bindings.describe_all();
+        let order = rows.iter().position(|(_, action, _)| *action == Action::Order).unwrap();
+        let mut gui = GUI::new(MemoryRenderer::new(60, 24));
+        gui.menu = GuiMenu::KeysMenu(order, true, None);
+        gui.render(&mut state);
+        assert_snapshot("keys_menu", &gui.renderer.text());
+
+        // Enter opens the list from the Keys row instead of saving
+        gui.menu = GuiMenu::SettingsMenu(SettingsField::Keys, None);
+        gui.render(&mut state);
+        assert!(gui.renderer.text().contains("Enter: edit keys"));
+        gui.menu = GuiMenu::SettingsMenu(SettingsField::Language, None);
+        gui.render(&mut state);
+        assert!(gui.renderer.text().contains("Enter: save"));
+    }
+
+    #[test]
+    fn game_over_snapshot() {
+        let mut state = test_state();
+        state.ecs.insert(GameResult { ended: Some(Rule { outcome: Outcome::Victory, condition: Condition::Corruption { percent: 70 } }) });
+        state.ecs.insert(GameStats { undead_lost: 2, villagers_slain: 5 });
+        let mut gui = GUI::new(MemoryRenderer::new(60, 24));
+        gui.menu = GuiMenu::GameOverMenu(GameOverButton::Restart, GameMenuTab::Unit);
+        gui.render(&mut state);
+        assert_snapshot("game_over", &gui.renderer.text());
+    }
+
+    #[test]
+    fn aimed_spells_preview_their_area() {
+        let mut state = test_state();
+        state.ecs.insert(Spells::load().0);
+        for necromancer in (&mut state.ecs.write_storage::<Necromancer>()).join() {
+            necromancer.spellbook = vec!["fear".to_string()];
+        }
+        let mut gui = GUI::new(MemoryRenderer::new(60, 24));
+        gui.menu = GuiMenu::GameMenu(GameMenuTab::Unit);
+        gui.aim = Some(Aim { slot: 0, x: 7, y: 5 });
+        gui.render(&mut state);
+        assert_eq!(gui.renderer.cell(7, 5).unwrap().background, bright(AIM_COLOR));
+        assert_eq!(gui.renderer.cell(7, 3).unwrap().background, AIM_COLOR);
+        assert_ne!(gui.renderer.cell(7, 2).unwrap().background, AIM_COLOR);
+        assert!(gui.renderer.text().contains("Fear: 12 MP, range 6"));
+
+        gui.aim = Some(Aim { slot: 0, x: 0, y: 0 });
+        gui.render(&mut state);
+        assert_eq!(gui.renderer.cell(0, 0).unwrap().background, bright(AIM_BLOCKED_COLOR));
+        assert!(gui.renderer.text().contains("Out of range for Fear"));
+    }
+
+    #[test]
+    fn minimap_shows_factions_and_hides_fog() {
+        let mut world = World::new();
+        let mut map = Map::new(4, 4);
+        map.reveal_around(0, 0, 1);
+        world.insert(map);
+        world.register::<Faction>();
+        world.register::<Position>();
+        world.create_entity().with(Position{ x: 1, y: 0 }).with(Faction{ faction: FactionType::Undead }).build();
+        world.create_entity().with(Position{ x: 3, y: 3 }).with(Faction{ faction: FactionType::Villagers }).build();
+
+        let pixels = minimap_pixels(&world);
+        assert_eq!(pixels.len(), 16);
+        assert_eq!(pixels[1], FactionType::Undead.get_color());
+        assert_eq!(pixels[4], (40, 60, 45));
+        assert_eq!(pixels[15], (0, 0, 0));
+    }
+
+    #[test]
+    fn minimap_is_only_redrawn_when_it_changes() {
+        let mut world = World::new();
+        world.insert(Map::new(4, 4));
+        world.insert(GameClock::default());
+        world.register::<Faction>();
+        world.register::<Position>();
+        let unit = world.create_entity().with(Position{ x: 1, y: 0 }).with(Faction{ faction: FactionType::Undead }).build();
+        let key = minimap_key(&world, 0);
+
+        world.write_resource::<GameClock>().tick += 1;
+        assert_eq!(minimap_key(&world, 0), key);
+        assert_ne!(minimap_key(&world, 1), key);
+
+        world.write_storage::<Position>().get_mut(unit).unwrap().x = 2;
+        let moved = minimap_key(&world, 0);
+        assert_ne!(moved, key);
+        world.write_resource::<Map>().reveal_around(0, 0, 1);
+        assert_ne!(minimap_key(&world, 0), moved);
+    }
 }
@@ -0,0 +1,378 @@
+use sdl2::keyboard::{Keycode, Mod};
+use std::collections::BTreeMap;
+
+use super::locale::{tr, tr_args};
+
+/// Where a key press happens. The same key can mean different things in different contexts.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum InputContext { Game, Possess, Aim, Menu, Settings, Confirm, Text }
+
+impl InputContext {
+    pub fn all() -> [InputContext; 7] {
+        [InputContext::Game, InputContext::Possess, InputContext::Aim, InputContext::Menu, InputContext::Settings, InputContext::Confirm, InputContext::Text]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            InputContext::Game => "game",
+            InputContext::Possess => "possess",
+            InputContext::Aim => "aim",
+            InputContext::Menu => "menu",
+            InputContext::Settings => "settings",
+            InputContext::Confirm => "confirm",
+            InputContext::Text => "text"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<InputContext> {
+        InputContext::all().iter().copied().find(|context| context.name() == name)
+    }
+
+    pub fn get_text(&self) -> String {
+        tr(&format!("context.{}", self.name()))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Action {
+    NextUnit, PrevUnit, Order, QueueOrder, Rename, NextTab,
+    Pause, SpeedUp, SlowDown, ToggleTimings, NextTileset, Screenshot, ExportMap,
+    CameraUp, CameraDown, CameraLeft, CameraRight,
+    Possess, WalkUp, WalkDown, WalkLeft, WalkRight,
+    Cast, CursorUp, CursorDown, CursorLeft, CursorRight,
+    Next, Prev, Up, Down, Erase, Confirm, Back
+}
+
+impl Action {
+    pub fn all() -> [Action; 34] {
+        [Action::NextUnit, Action::PrevUnit, Action::Order, Action::QueueOrder, Action::Rename, Action::NextTab,
+         Action::Pause, Action::SpeedUp, Action::SlowDown, Action::ToggleTimings, Action::NextTileset, Action::Screenshot, Action::ExportMap,
+         Action::CameraUp, Action::CameraDown, Action::CameraLeft, Action::CameraRight,
+         Action::Possess, Action::WalkUp, Action::WalkDown, Action::WalkLeft, Action::WalkRight,
+         Action::Cast, Action::CursorUp, Action::CursorDown, Action::CursorLeft, Action::CursorRight,
+         Action::Next, Action::Prev, Action::Up, Action::Down, Action::Erase, Action::Confirm, Action::Back]
+    }
+
+    /// Name used in the config file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::NextUnit => "next_unit",
+            Action::PrevUnit => "prev_unit",
+            Action::Order => "order",
+            Action::QueueOrder => "queue_order",
+            Action::Rename => "rename",
+            Action::NextTab => "next_tab",
+            Action::Pause => "pause",
+            Action::SpeedUp => "speed_up",
+            Action::SlowDown => "slow_down",
+            Action::ToggleTimings => "toggle_timings",
+            Action::NextTileset => "next_tileset",
+            Action::Screenshot => "screenshot",
+            Action::ExportMap => "export_map",
+            Action::CameraUp => "camera_up",
+            Action::CameraDown => "camera_down",
+            Action::CameraLeft => "camera_left",
+            Action::CameraRight => "camera_right",
+            Action::Possess => "possess",
+            Action::WalkUp => "walk_up",
+            Action::WalkDown => "walk_down",
+            Action::WalkLeft => "walk_left",
+            Action::WalkRight => "walk_right",
+            Action::Cast => "cast",
+            Action::CursorUp => "cursor_up",
+            Action::CursorDown => "cursor_down",
+            Action::CursorLeft => "cursor_left",
+            Action::CursorRight => "cursor_right",
+            Action::Next => "next",
+            Action::Prev => "prev",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Erase => "erase",
+            Action::Confirm => "confirm",
+            Action::Back => "back"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::all().iter().copied().find(|action| action.name() == name)
+    }
+
+    pub fn get_text(&self) -> String {
+        tr(&format!("action.{}", self.name()))
+    }
+
+    /// Whether holding the key down should repeat the action.
+    pub fn repeats(&self) -> bool {
+        matches!(self, Action::NextUnit | Action::PrevUnit | Action::SpeedUp | Action::SlowDown |
+                       Action::CameraUp | Action::CameraDown | Action::CameraLeft | Action::CameraRight |
+                       Action::WalkUp | Action::WalkDown | Action::WalkLeft | Action::WalkRight |
+                       Action::CursorUp | Action::CursorDown | Action::CursorLeft | Action::CursorRight |
+                       Action::Next | Action::Prev | Action::Up | Action::Down | Action::Erase)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct KeyBinding {
+    pub key: Keycode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool
+}
+
+impl KeyBinding {
+    pub fn new(key: Keycode) -> KeyBinding {
+        KeyBinding { key, ctrl: false, shift: false, alt: false }
+    }
+
+    pub fn shifted(key: Keycode) -> KeyBinding {
+        KeyBinding { shift: true, ..KeyBinding::new(key) }
+    }
+
+    /// The binding a key press would match.
+    pub fn pressed(key: Keycode, keymod: Mod) -> KeyBinding {
+        KeyBinding {
+            key,
+            ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+            shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+            alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD)
+        }
+    }
+
+    /// Whether the key only modifies others, and cannot be bound on its own.
+    pub fn is_modifier(key: Keycode) -> bool {
+        matches!(key, Keycode::LCtrl | Keycode::RCtrl | Keycode::LShift | Keycode::RShift |
+                      Keycode::LAlt | Keycode::RAlt | Keycode::LGui | Keycode::RGui | Keycode::Mode)
+    }
+
+    pub fn matches(&self, key: Keycode, keymod: Mod) -> bool {
+        self.key == key
+            && self.ctrl == keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+            && self.shift == keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD)
+            && self.alt == keymod.intersects(Mod::LALTMOD | Mod::RALTMOD)
+    }
+
+    /// Parses bindings like `M`, `Ctrl+S` or `Shift+Keypad +`, using SDL key names.
+    pub fn parse(text: &str) -> Option<KeyBinding> {
+        let (mut ctrl, mut shift, mut alt) = (false, false, false);
+        let mut rest = text.trim();
+
+        loop {
+            let lower = rest.to_lowercase();
+            if lower.starts_with("ctrl+") && rest.len() > 5 {
+                ctrl = true;
+                rest = &rest[5..];
+            } else if lower.starts_with("shift+") && rest.len() > 6 {
+                shift = true;
+                rest = &rest[6..];
+            } else if lower.starts_with("alt+") && rest.len() > 4 {
+                alt = true;
+                rest = &rest[4..];
+            } else {
+                break;
+            }
+        }
+
+        Some(KeyBinding { key: Keycode::from_name(rest)?, ctrl, shift, alt })
+    }
+
+    pub fn get_text(&self) -> String {
+        let mut text = String::new();
+        if self.ctrl { text.push_str("Ctrl+"); }
+        if self.shift { text.push_str("Shift+"); }
+        if self.alt { text.push_str("Alt+"); }
+        text.push_str(&self.key.name());
+        text
+    }
+}
+
+/// Maps key presses to actions, per input context.
+pub struct KeyBindings {
+    bindings: Vec<(InputContext, Action, Vec<KeyBinding>)>
+}
+
+impl KeyBindings {
+    pub fn defaults() -> KeyBindings {
+        use InputContext::*;
+        let key = KeyBinding::new;
+
+        KeyBindings { bindings: vec![
+            (Game, Action::NextUnit, vec![key(Keycode::D)]),
+            (Game, Action::PrevUnit, vec![key(Keycode::A)]),
+            (Game, Action::Order, vec![key(Keycode::M)]),
+            (Game, Action::QueueOrder, vec![KeyBinding::shifted(Keycode::M)]),
+            (Game, Action::Rename, vec![key(Keycode::F2)]),
+            (Game, Action::NextTab, vec![key(Keycode::Tab)]),
+            (Game, Action::Pause, vec![key(Keycode::Space)]),
+            (Game, Action::SpeedUp, vec![key(Keycode::Plus), key(Keycode::Equals), KeyBinding::shifted(Keycode::Equals), key(Keycode::KpPlus)]),
+            (Game, Action::SlowDown, vec![key(Keycode::Minus), key(Keycode::KpMinus)]),
+            (Game, Action::ToggleTimings, vec![key(Keycode::F3)]),
+            (Game, Action::NextTileset, vec![key(Keycode::F4)]),
+            (Game, Action::Screenshot, vec![key(Keycode::F12)]),
+            (Game, Action::ExportMap, vec![KeyBinding::shifted(Keycode::F12)]),
+            (Game, Action::CameraUp, vec![key(Keycode::Up)]),
+            (Game, Action::CameraDown, vec![key(Keycode::Down)]),
+            (Game, Action::CameraLeft, vec![key(Keycode::Left)]),
+            (Game, Action::CameraRight, vec![key(Keycode::Right)]),
+            (Game, Action::Possess, vec![key(Keycode::P)]),
+            (Game, Action::Cast, vec![key(Keycode::C)]),
+            (Game, Action::Back, vec![key(Keycode::Escape)]),
+
+            // Checked before the game context while possessing the necromancer
+            (Possess, Action::WalkUp, vec![key(Keycode::W)]),
+            (Possess, Action::WalkDown, vec![key(Keycode::S)]),
+            (Possess, Action::WalkLeft, vec![key(Keycode::A)]),
+            (Possess, Action::WalkRight, vec![key(Keycode::D)]),
+            (Possess, Action::Possess, vec![key(Keycode::P), key(Keycode::Escape)]),
+
+            // Checked before the others while aiming a spell
+            (Aim, Action::CursorUp, vec![key(Keycode::W), key(Keycode::Up)]),
+            (Aim, Action::CursorDown, vec![key(Keycode::S), key(Keycode::Down)]),
+            (Aim, Action::CursorLeft, vec![key(Keycode::A), key(Keycode::Left)]),
+            (Aim, Action::CursorRight, vec![key(Keycode::D), key(Keycode::Right)]),
+            (Aim, Action::Next, vec![key(Keycode::Tab)]),
+            (Aim, Action::Prev, vec![KeyBinding::shifted(Keycode::Tab)]),
+            (Aim, Action::Confirm, vec![key(Keycode::Return), key(Keycode::KpEnter)]),
+            (Aim, Action::Cast, vec![key(Keycode::C), key(Keycode::Escape)]),
+
+            (Menu, Action::Next, vec![key(Keycode::D), key(Keycode::Right)]),
+            (Menu, Action::Prev, vec![key(Keycode::A), key(Keycode::Left)]),
+            (Menu, Action::Confirm, vec![key(Keycode::Return)]),
+            (Menu, Action::Back, vec![key(Keycode::Escape)]),
+
+            (Settings, Action::Up, vec![key(Keycode::W), key(Keycode::Up)]),
+            (Settings, Action::Down, vec![key(Keycode::S), key(Keycode::Down)]),
+            (Settings, Action::Next, vec![key(Keycode::D), key(Keycode::Right)]),
+            (Settings, Action::Prev, vec![key(Keycode::A), key(Keycode::Left)]),
+            (Settings, Action::Erase, vec![key(Keycode::Backspace), key(Keycode::Delete)]),
+            (Settings, Action::Confirm, vec![key(Keycode::Return)]),
+            (Settings, Action::Back, vec![key(Keycode::Escape)]),
+
+            (Confirm, Action::Confirm, vec![key(Keycode::Y), key(Keycode::Return)]),
+            (Confirm, Action::Back, vec![key(Keycode::N), key(Keycode::Escape)]),
+
+            (Text, Action::Erase, vec![key(Keycode::Backspace)]),
+            (Text, Action::Confirm, vec![key(Keycode::Return), key(Keycode::KpEnter)]),
+            (Text, Action::Back, vec![key(Keycode::Escape)])
+        ] }
+    }
+
+    /// Default bindings with the user's overrides from the config file applied.
+    /// Each overridden action loses its default keys, but keeps its place in the list. An override without a single
+    /// usable key is ignored, so a typo cannot leave an action unbound. Returns warnings about entries that could not be used.
+    pub fn with_overrides(overrides: &BTreeMap<String, BTreeMap<String, Vec<String>>>) -> (KeyBindings, Vec<String>) {
+        let mut bindings = KeyBindings::defaults();
+        let mut warnings = vec![];
+
+        for (context_name, actions) in overrides.iter() {
+            let context = match InputContext::from_name(context_name) {
+                Some(context) => context,
+                None => {
+                    warnings.push(tr_args("warning.unknown_context", &[("context", context_name.clone())]));
+                    continue;
+                }
+            };
+
+            for (action_name, keys) in actions.iter() {
+                let action = match Action::from_name(action_name) {
+                    Some(action) => action,
+                    None => {
+                        warnings.push(tr_args("warning.unknown_action", &[("action", action_name.clone())]));
+                        continue;
+                    }
+                };
+
+                let mut parsed = vec![];
+                for key in keys.iter() {
+                    match KeyBinding::parse(key) {
+                        Some(binding) => parsed.push(binding),
+                        None => warnings.push(tr_args("warning.unknown_key", &[("key", key.clone()), ("action", action_name.clone())]))
+                    }
+                }
+
+                if parsed.is_empty() {
+                    warnings.push(tr_args("warning.no_usable_keys", &[("action", action_name.clone())]));
+                    continue;
+                }
+                match bindings.bindings.iter_mut().find(|(c, a, _)| *c == context && *a == action) {
+                    Some(binding) => binding.2 = parsed,
+                    None => bindings.bindings.push((context, action, parsed))
+                }
+            }
+        }
+
+        (bindings, warnings)
+    }
+
+    pub fn action(&self, context: InputContext, key: Keycode, keymod: Mod) -> Option<Action> {
+        self.bindings.iter()
+                     .find(|(c, _, keys)| *c == context && keys.iter().any(|k| k.matches(key, keymod)))
+                     .map(|(_, action, _)| *action)
+    }
+
+    /// Lists actions of a context with their keys, in the order they were bound.
+    pub fn describe(&self, context: InputContext) -> Vec<(Action, String)> {
+        self.bindings.iter()
+                     .filter(|(c, _, _)| *c == context)
+                     .map(|(_, action, keys)| (*action, keys.iter().map(|k| k.get_text()).collect::<Vec<String>>().join(", ")))
+                     .collect()
+    }
+
+    /// Every action of every context with its keys, as listed in the key bindings menu.
+    pub fn describe_all(&self) -> Vec<(InputContext, Action, String)> {
+        InputContext::all().iter()
+                           .flat_map(|context| self.describe(*context).into_iter().map(move |(action, keys)| (*context, action, keys)))
+                           .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_key_depends_on_context() {
+        let bindings = KeyBindings::defaults();
+
+        assert_eq!(bindings.action(InputContext::Game, Keycode::D, Mod::NOMOD), Some(Action::NextUnit));
+        assert_eq!(bindings.action(InputContext::Menu, Keycode::D, Mod::NOMOD), Some(Action::Next));
+        assert_eq!(bindings.action(InputContext::Possess, Keycode::D, Mod::NOMOD), Some(Action::WalkRight));
+        assert_eq!(bindings.action(InputContext::Confirm, Keycode::D, Mod::NOMOD), None);
+    }
+
+    #[test]
+    fn modifiers_must_match() {
+        let bindings = KeyBindings::defaults();
+
+        assert_eq!(bindings.action(InputContext::Game, Keycode::Equals, Mod::LSHIFTMOD), Some(Action::SpeedUp));
+        assert_eq!(bindings.action(InputContext::Game, Keycode::M, Mod::LCTRLMOD), None);
+        assert_eq!(bindings.action(InputContext::Game, Keycode::M, Mod::NUMMOD), Some(Action::Order));
+    }
+
+    #[test]
+    fn bindings_parse_with_modifiers() {
+        assert_eq!(KeyBinding::parse("M"), Some(KeyBinding::new(Keycode::M)));
+        assert_eq!(KeyBinding::parse(" ctrl+Shift+F12 "), Some(KeyBinding { ctrl: true, ..KeyBinding::shifted(Keycode::F12) }));
+        assert_eq!(KeyBinding::parse("Alt+Keypad +"), Some(KeyBinding { alt: true, ..KeyBinding::new(Keycode::KpPlus) }));
+        assert_eq!(KeyBinding::parse("Shift++"), Some(KeyBinding::shifted(Keycode::Plus)));
+        assert_eq!(KeyBinding::parse("Ctrl+"), None);
+        assert_eq!(KeyBinding::parse("Hyper+M"), None);
+        assert_eq!(KeyBinding::parse("NoSuchKey"), None);
+
+        let binding = KeyBinding { ctrl: true, alt: true, ..KeyBinding::shifted(Keycode::G) };
+        assert_eq!(KeyBinding::parse(&binding.get_text()), Some(binding));
+    }
+
+    #[test]
+    fn bad_overrides_are_reported_and_keep_the_defaults() {
+        crate::locale::set_language("en").unwrap();
+        let overrides: BTreeMap<String, BTreeMap<String, Vec<String>>> = toml::from_str(
+            "[game]\norder = [\"G\", \"NoSuchKey\"]\nteleport = [\"T\"]\n[menu]\nback = [\"Esc\"]\n[lobby]\nconfirm = [\"Y\"]").unwrap();
+        let (bindings, warnings) = KeyBindings::with_overrides(&overrides);
+
+        assert_eq!(warnings.len(), 5, "{:?}", warnings);
+        assert!(warnings.contains(&"Unknown key NoSuchKey for order".to_string()), "{:?}", warnings);
+        assert_eq!(bindings.action(InputContext::Game, Keycode::G, Mod::NOMOD), Some(Action::Order));
+        assert_eq!(bindings.action(InputContext::Game, Keycode::M, Mod::NOMOD), None);
+        assert_eq!(bindings.action(InputContext::Menu, Keycode::Escape, Mod::NOMOD), Some(Action::Back));
+    }
+}
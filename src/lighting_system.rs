@@ -0,0 +1,132 @@
+use specs::prelude::*;
+
+use super::{LightSource, Position, map::Map};
+use super::clock::GameClock;
+use super::widgets::Color;
+
+const FULL_LIGHT: Color = (255, 255, 255);
+
+/// Light on every map tile, worked out once per tick. Without tiles, everything is in full light.
+pub struct Lighting {
+    width: u32,
+    /// Light falling everywhere, from the sky.
+    pub ambient: Color,
+    /// Light cast on each tile by light sources.
+    glow: Vec<Color>,
+    /// Tiles in holy light.
+    holy: Vec<bool>,
+    /// Goes up whenever the light changes, so views can tell when to redraw.
+    pub revision: u64
+}
+
+impl Default for Lighting {
+    fn default() -> Lighting {
+        Lighting { width: 0, ambient: FULL_LIGHT, glow: vec![], holy: vec![], revision: 0 }
+    }
+}
+
+/// A color as seen under a light.
+pub fn lit(color: Color, light: Color) -> Color {
+    let scale = |c: u8, l: u8| (c as u32 * l as u32 / 255) as u8;
+    (scale(color.0, light.0), scale(color.1, light.1), scale(color.2, light.2))
+}
+
+impl Lighting {
+    fn idx(&self, x: u32, y: u32) -> Option<usize> {
+        if x >= self.width {
+            return None;
+        }
+        Some((y * self.width + x) as usize)
+    }
+
+    pub fn glow_at(&self, x: u32, y: u32) -> Color {
+        self.idx(x, y).and_then(|idx| self.glow.get(idx)).copied().unwrap_or((0, 0, 0))
+    }
+
+    /// The brighter of the ambient light and the glow of light sources.
+    pub fn light_at(&self, x: u32, y: u32) -> Color {
+        let glow = self.glow_at(x, y);
+        (self.ambient.0.max(glow.0), self.ambient.1.max(glow.1), self.ambient.2.max(glow.2))
+    }
+
+    pub fn is_holy(&self, x: u32, y: u32) -> bool {
+        self.idx(x, y).and_then(|idx| self.holy.get(idx)).copied().unwrap_or(false)
+    }
+}
+
+/// Works out the light of every tile from the time of day and the light sources.
+pub struct LightingSystem {}
+
+impl<'a> System<'a> for LightingSystem {
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        Read<'a, GameClock>,
+        ReadStorage<'a, LightSource>,
+        ReadStorage<'a, Position>,
+        Write<'a, Lighting>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (map, clock, lights, positions, mut lighting) = data;
+
+        let size = (map.width * map.height) as usize;
+        let mut glow = vec![(0, 0, 0); size];
+        let mut holy = vec![false; size];
+        for (light, pos) in (&lights, &positions).join() {
+            let radius = light.radius;
+            for y in pos.y.saturating_sub(radius)..(pos.y + radius + 1).min(map.height) {
+                for x in pos.x.saturating_sub(radius)..(pos.x + radius + 1).min(map.width) {
+                    let (dx, dy) = (x.abs_diff(pos.x), y.abs_diff(pos.y));
+                    if dx * dx + dy * dy > radius * radius {
+                        continue;
+                    }
+                    // Fades out towards the edge of the radius
+                    let strength = 1. - ((dx * dx + dy * dy) as f32).sqrt() / (radius + 1) as f32;
+                    let fade = |c: u8| (c as f32 * strength).round() as u8;
+                    let idx = map.xy_idx(x, y) as usize;
+                    let tile = glow[idx];
+                    glow[idx] = (tile.0.max(fade(light.color.0)), tile.1.max(fade(light.color.1)), tile.2.max(fade(light.color.2)));
+                    holy[idx] |= light.holy;
+                }
+            }
+        }
+
+        let ambient = clock.ambient_light();
+        if lighting.width != map.width || lighting.ambient != ambient || lighting.glow != glow || lighting.holy != holy {
+            let revision = lighting.revision + 1;
+            *lighting = Lighting { width: map.width, ambient, glow, holy, revision };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn light_sources_light_a_radius() {
+        let mut world = World::new();
+        world.register::<LightSource>();
+        world.register::<Position>();
+        world.insert(Map::new(9, 9));
+        world.insert(GameClock { tick: 16 * 60 });
+        world.insert(Lighting::default());
+        world.create_entity().with(Position{ x: 2, y: 2 }).with(LightSource{ radius: 2, color: (255, 150, 0), holy: false }).build();
+        world.create_entity().with(Position{ x: 7, y: 7 }).with(LightSource{ radius: 1, color: (200, 200, 255), holy: true }).build();
+
+        LightingSystem {}.run_now(&world);
+        let lighting = world.fetch::<Lighting>();
+        assert_eq!(lighting.ambient, GameClock { tick: 16 * 60 }.ambient_light());
+        assert_eq!(lighting.glow_at(2, 2), (255, 150, 0));
+        assert!(lighting.glow_at(3, 2).0 < 255 && lighting.glow_at(3, 2).0 > 0);
+        assert_eq!(lighting.glow_at(5, 2), (0, 0, 0));
+        assert_eq!(lighting.light_at(2, 2), (255, 150, lighting.ambient.2));
+        assert!(lighting.is_holy(7, 8));
+        assert!(!lighting.is_holy(2, 2));
+        assert_eq!(lighting.revision, 1);
+        drop(lighting);
+
+        LightingSystem {}.run_now(&world);
+        assert_eq!(world.fetch::<Lighting>().revision, 1);
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+pub const DEFAULT_LANGUAGE: &str = "en";
+const LANG_DIR: &str = "./resources/lang";
+
+/// Translations currently in use: the chosen language and English for anything it lacks.
+struct Locale {
+    current: Catalog,
+    fallback: Catalog
+}
+
+static LOCALE: RwLock<Option<Locale>> = RwLock::new(None);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entry {
+    Text(String),
+    Plural { one: String, other: String }
+}
+
+/// String table of one language, loaded from `resources/lang/<language>.toml`.
+/// Tables nest into dotted ids, and a table with an `other` key is a plural entry:
+///
+/// ```toml
+/// [mission]
+/// goto = "GoTo {x}:{y}"
+///
+/// [settings.frames]
+/// one = "{n} frame"
+/// other = "{n} frames"
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Catalog {
+    pub language: String,
+    pub entries: BTreeMap<String, Entry>
+}
+
+impl Catalog {
+    pub fn path(language: &str) -> PathBuf {
+        PathBuf::from(LANG_DIR).join(format!("{}.toml", language))
+    }
+
+    pub fn load(language: &str) -> io::Result<Catalog> {
+        let text = fs::read_to_string(Catalog::path(language))?;
+        Catalog::parse(language, &text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn parse(language: &str, text: &str) -> Result<Catalog, String> {
+        let table: toml::value::Table = toml::from_str(text).map_err(|e| e.to_string())?;
+        let mut entries = BTreeMap::new();
+        flatten("", &table, &mut entries)?;
+        Ok(Catalog { language: language.to_string(), entries })
+    }
+
+    pub fn text(&self, id: &str, count: Option<u64>) -> Option<&str> {
+        match (self.entries.get(id)?, count) {
+            (Entry::Text(text), _) => Some(text),
+            (Entry::Plural { one, .. }, Some(1)) => Some(one),
+            (Entry::Plural { other, .. }, _) => Some(other)
+        }
+    }
+}
+
+fn flatten(prefix: &str, table: &toml::value::Table, entries: &mut BTreeMap<String, Entry>) -> Result<(), String> {
+    for (key, value) in table.iter() {
+        let id = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match value {
+            toml::Value::String(text) => {
+                entries.insert(id, Entry::Text(text.clone()));
+            },
+            toml::Value::Table(table) if table.contains_key("other") => {
+                let form = |name: &str| table.get(name).and_then(|v| v.as_str()).map(|s| s.to_string());
+                let other = form("other").ok_or(format!("{}.other must be a string", id))?;
+                let one = form("one").unwrap_or_else(|| other.clone());
+                entries.insert(id, Entry::Plural { one, other });
+            },
+            toml::Value::Table(table) => flatten(&id, table, entries)?,
+            _ => return Err(format!("{} must be a string or a table", id))
+        }
+    }
+    Ok(())
+}
+
+/// Replaces `{name}` placeholders with argument values in one pass, so braces inside the values are left alone.
+/// Placeholders without an argument are kept as they are.
+pub fn substitute(template: &str, args: &[(&str, String)]) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let value = placeholder.find('}').and_then(|end| {
+            let name = &placeholder[1..end];
+            args.iter().find(|(arg, _)| *arg == name).map(|(_, value)| (value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                text.push_str(value);
+                rest = &placeholder[end + 1..];
+            },
+            None => {
+                text.push('{');
+                rest = &placeholder[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+/// Lists languages that have a catalog in the resources directory.
+pub fn available_languages() -> Vec<String> {
+    let mut languages: Vec<String> = match fs::read_dir(LANG_DIR) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok())
+                              .map(|entry| entry.path())
+                              .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+                              .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+                              .collect(),
+        Err(_) => vec![]
+    };
+    languages.sort();
+    languages
+}
+
+pub fn set_language(language: &str) -> io::Result<()> {
+    let current = Catalog::load(language)?;
+    let fallback = Catalog::load(DEFAULT_LANGUAGE)?;
+    *LOCALE.write().unwrap() = Some(Locale { current, fallback });
+    Ok(())
+}
+
+fn lookup(id: &str, count: Option<u64>, args: &[(&str, String)]) -> String {
+    let locale = LOCALE.read().unwrap();
+    let template = locale.as_ref().and_then(|locale| locale.current.text(id, count).or_else(|| locale.fallback.text(id, count)));
+    match template {
+        Some(template) => substitute(template, args),
+        None => id.to_string()
+    }
+}
+
+/// Text of a string id in the current language. Unknown ids are shown as is.
+pub fn tr(id: &str) -> String {
+    lookup(id, None, &[])
+}
+
+pub fn tr_args(id: &str, args: &[(&str, String)]) -> String {
+    lookup(id, None, args)
+}
+
+/// Picks the plural form for `n`, which is also available as the `{n}` placeholder.
+pub fn tr_count(id: &str, n: u64, args: &[(&str, String)]) -> String {
+    let mut args = args.to_vec();
+    args.push(("n", n.to_string()));
+    lookup(id, Some(n), &args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cp437;
+    use std::collections::BTreeSet;
+
+    fn texts(entry: &Entry) -> Vec<&String> {
+        match entry {
+            Entry::Text(text) => vec![text],
+            Entry::Plural { one, other } => vec![one, other]
+        }
+    }
+
+    fn placeholders(entry: &Entry) -> BTreeSet<String> {
+        texts(entry).iter()
+             .flat_map(|text| text.split('{').skip(1).filter_map(|part| part.split_once('}')).map(|(name, _)| name.to_string()))
+             .collect()
+    }
+
+    #[test]
+    fn every_catalog_has_every_key() {
+        let reference = Catalog::load(DEFAULT_LANGUAGE).unwrap();
+        let languages = available_languages();
+        assert!(languages.len() > 1);
+
+        for language in languages.iter() {
+            let catalog = Catalog::load(language).unwrap();
+            for (id, entry) in reference.entries.iter() {
+                let translated = catalog.entries.get(id).unwrap_or_else(|| panic!("{} is missing {}", language, id));
+                assert_eq!(placeholders(translated), placeholders(entry), "{} has other placeholders in {}", language, id);
+            }
+            for (id, entry) in catalog.entries.iter() {
+                for c in texts(entry).iter().flat_map(|text| text.chars()) {
+                    assert!(cp437::to_glyph(c).is_some(), "{} has {} in {}, which tilesets cannot show", language, c, id);
+                }
+            }
+            for id in catalog.entries.keys() {
+                assert!(reference.entries.contains_key(id), "{} has unknown key {}", language, id);
+            }
+        }
+    }
+
+    #[test]
+    fn plurals_and_parameters() {
+        let catalog = Catalog::parse("test", "[mission]\ngoto = \"GoTo {x}:{y}\"\n\n[units]\none = \"{n} unit\"\nother = \"{n} units\"").unwrap();
+
+        assert_eq!(substitute(catalog.text("mission.goto", None).unwrap(), &[("x", "3".to_string()), ("y", "14".to_string())]), "GoTo 3:14");
+        assert_eq!(catalog.text("units", Some(1)), Some("{n} unit"));
+        assert_eq!(catalog.text("units", Some(0)), Some("{n} units"));
+        assert_eq!(catalog.text("missing", None), None);
+    }
+
+    #[test]
+    fn braces_in_values_are_not_placeholders() {
+        let args = [("old", "{name}".to_string()), ("name", "Morrow {old}".to_string())];
+        assert_eq!(substitute("{old} is now called {name}", &args), "{name} is now called Morrow {old}");
+        assert_eq!(substitute("{unknown} {name", &args), "{unknown} {name");
+        assert_eq!(substitute("{{name}}", &args), "{Morrow {old}}");
+    }
+}
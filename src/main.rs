@@ -3,145 +3,1159 @@ mod gamelog;
 mod map;
 mod components;
 mod mission_system;
+mod movement_system;
+mod visibility_system;
+mod effect_system;
+mod lighting_system;
+mod sunlight_system;
+mod status_system;
+mod death_system;
+mod corruption_system;
+mod mana_system;
+mod spells;
+mod spell_system;
+mod decay_system;
+mod rules;
+mod rules_system;
+mod dispatcher;
+mod clock;
+mod saveload_system;
+mod config;
+mod input;
+mod tileset;
+mod cp437;
+mod locale;
+mod widgets;
+mod names;
+mod effects;
+mod renderer;
+mod frontend;
+mod sdl_renderer;
+mod terminal;
 
 use sdl2::event::Event;
+use sdl2::mouse::MouseButton;
 use sdl2::keyboard::Keycode;
-use sdl2::image::{InitFlag, LoadTexture};
+use sdl2::image::InitFlag;
 use specs::prelude::*;
-use std::time::Duration;
+use specs::saveload::{SimpleMarker, MarkedBuilder};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 use rand::Rng;
 
 use components::*;
-use mission_system::MissionSystem;
+use input::{Action, InputContext};
+use gamelog::{log_system, Gamelog, Severity};
+use locale::{tr, tr_args};
+use frontend::{Frontend, HeadlessFrontend};
+use renderer::{MemoryRenderer, Renderer};
+use sdl_renderer::{SdlFrontend, SdlRenderer};
+use terminal::{TerminalFrontend, TerminalRenderer};
+use widgets::Choice;
 
 pub const TICK_SIZE: u32 = 13;
+const CAPTURE_DIR: &str = "./screenshots";
 
 
 pub struct State {
-    log: gamelog::Gamelog,
     ecs: World,
-    selected_unit_index: usize
+    /// Entity shown in the Unit tab. Any entity on the map can be selected, not only units.
+    selected: Option<Entity>,
+    speed: clock::GameSpeed,
+    unsaved: bool,
+    config: config::Config,
+    bindings: input::KeyBindings,
+    /// Whether the player is walking the necromancer around directly.
+    possessing: bool
+}
+
+
+fn new_game(state: &mut State) {
+    state.ecs.delete_all();
+    state.ecs.maintain();
+    state.ecs.insert(map::Map::new(15, 15));
+    state.ecs.insert(clock::GameClock::default());
+    state.ecs.insert(rules::GameResult::default());
+    state.ecs.insert(rules::GameStats::default());
+
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..10 {
+        let kind = UndeadType::all()[rng.gen_range(0..3)];
+        let name = state.ecs.fetch::<names::NameGenerator>().generate(kind.name_style(), &mut rng).unwrap_or_else(|| tr("unit.unnamed"));
+        state.ecs.create_entity()
+                 .with(Position{ x: rng.gen_range(0..15), y: rng.gen_range(0..15) })
+                 .with(Renderable::new(kind.get_glyph(), (50 * rng.gen_range(0..3), 50 * rng.gen_range(0..3), 50 * rng.gen_range(0..3)), RenderLayer::Unit))
+                 .with(Name{ name })
+                 .with(Unit{ mission: Mission::GoTo(rng.gen_range(0..15), rng.gen_range(0..15)), kind })
+                 .with(MissionQueue::default())
+                 .with(Faction{ faction: FactionType::Undead })
+                 .with(Living::new(10))
+                 .with(Integrity::new(kind.max_integrity()))
+                 .with(Physical{ weight: 60, size: 2 })
+                 .with(Inventory::default())
+                 .with(StatusEffects::default())
+                 .with(Kills::default())
+                 .marked::<SimpleMarker<SerializeMe>>()
+                 .build();
+    }
+
+    for _ in 0..4 {
+        let given_name = state.ecs.fetch::<names::NameGenerator>().generate(names::NameStyle::Villager, &mut rng).unwrap_or_else(|| tr("unit.unnamed"));
+        let villager = Villager{ given_name, profession: Profession::all()[rng.gen_range(0..3)] };
+        state.ecs.create_entity()
+                 .with(Position{ x: rng.gen_range(0..15), y: rng.gen_range(0..15) })
+                 .with(Renderable::new(2, (150, 120, 80), RenderLayer::Unit))
+                 .with(Name{ name: villager.title() })
+                 .with(villager)
+                 .with(Faction{ faction: FactionType::Villagers })
+                 .with(Living::new(10))
+                 .with(Physical{ weight: 70, size: 2 })
+                 .with(Inventory::default())
+                 .with(StatusEffects::default())
+                 .marked::<SimpleMarker<SerializeMe>>()
+                 .build();
+    }
+
+    for _ in 0..8 {
+        state.ecs.create_entity()
+                 .with(Position{ x: rng.gen_range(0..15), y: rng.gen_range(0..15) })
+                 .with(Renderable::new(6, (0, 100, 0), RenderLayer::Building))
+                 .with(Choppable{ chops_into: MaterialType::Logs })
+                 .with(Faction{ faction: FactionType::Nature })
+                 .with(Living::new(20))
+                 .with(Physical{ weight: 500, size: 3 })
+                 .marked::<SimpleMarker<SerializeMe>>()
+                 .build();
+    }
+
+    for _ in 0..3 {
+        state.ecs.create_entity()
+                 .with(Position{ x: rng.gen_range(0..15), y: rng.gen_range(0..15) })
+                 .with(Renderable::new('=' as u32, (100, 60, 0), RenderLayer::Item))
+                 .with(Material{ material_type: MaterialType::Logs })
+                 .with(Physical{ weight: 20, size: 1 })
+                 .marked::<SimpleMarker<SerializeMe>>()
+                 .build();
+    }
+
+    state.ecs.create_entity()
+             .with(Position{ x: 7, y: 7 })
+             .with(Renderable{ background: Some((30, 30, 45)), ..Renderable::new(127, (80, 80, 100), RenderLayer::Building) })
+             .with(Name{ name: tr("building.crypt") })
+             .with(Building{})
+             .with(Faction{ faction: FactionType::Undead })
+             .with(Living::new(100))
+             .with(BlocksTile{})
+             .marked::<SimpleMarker<SerializeMe>>()
+             .build();
+
+    let necromancer_name = state.ecs.fetch::<names::NameGenerator>().generate(names::NameStyle::Villager, &mut rng).unwrap_or_else(|| tr("unit.unnamed"));
+    let spellbook = ["raise_dead", "bone_spear", "fear", "corpse_explosion", "summon"].iter().map(|spell| spell.to_string()).collect();
+    state.ecs.create_entity()
+             .with(Position{ x: 7, y: 8 })
+             .with(Renderable::new('@' as u32, (170, 60, 220), RenderLayer::Unit))
+             .with(Name{ name: tr_args("name.necromancer", &[("name", necromancer_name)]) })
+             .with(Necromancer::new(50, spellbook))
+             .with(Faction{ faction: FactionType::Undead })
+             .with(Living::new(30))
+             .with(Physical{ weight: 60, size: 2 })
+             .with(Inventory::default())
+             .with(StatusEffects::default())
+             .with(Kills::default())
+             .marked::<SimpleMarker<SerializeMe>>()
+             .build();
+
+    state.ecs.create_entity()
+             .with(Position{ x: rng.gen_range(0..15), y: rng.gen_range(0..15) })
+             .with(Renderable::new(234, (230, 220, 150), RenderLayer::Building))
+             .with(Name{ name: tr("building.shrine") })
+             .with(Building{})
+             .with(Faction{ faction: FactionType::Villagers })
+             .with(Living::new(50))
+             .with(LightSource{ radius: 3, color: (255, 245, 190), holy: true })
+             .marked::<SimpleMarker<SerializeMe>>()
+             .build();
+
+    state.ecs.create_entity()
+             .with(Position{ x: rng.gen_range(0..15), y: rng.gen_range(0..15) })
+             .with(Renderable{ frames: vec![15, '*' as u32], ..Renderable::new(15, (255, 140, 40), RenderLayer::Item) })
+             .with(Name{ name: tr("object.campfire") })
+             .with(LightSource{ radius: 3, color: (255, 150, 60), holy: false })
+             .marked::<SimpleMarker<SerializeMe>>()
+             .build();
+
+    for _ in 0..2 {
+        state.ecs.create_entity()
+                 .with(Position{ x: rng.gen_range(0..15), y: rng.gen_range(0..15) })
+                 .with(Renderable::new(173, (255, 190, 80), RenderLayer::Item))
+                 .with(Name{ name: tr("object.torch") })
+                 .with(LightSource{ radius: 2, color: (255, 180, 90), holy: false })
+                 .marked::<SimpleMarker<SerializeMe>>()
+                 .build();
+    }
+    lighting_system::LightingSystem {}.run_now(&state.ecs);
+
+    state.selected = None;
+    state.possessing = false;
+    cycle_unit(state, true);
+    state.speed = clock::GameSpeed::new(state.config.tick_size);
+    state.unsaved = false;
+}
+
+
+/// Loads the saved game and logs how it went. Returns whether it was loaded.
+fn load<R: Renderer>(state: &mut State, gui: &mut gui::GUI<R>) -> bool {
+    match saveload_system::load_game(&mut state.ecs) {
+        Ok(()) => {
+            state.selected = None;
+            state.possessing = false;
+            cycle_unit(state, true);
+            state.unsaved = false;
+            state.ecs.insert(rules::GameResult::default());
+            lighting_system::LightingSystem {}.run_now(&state.ecs);
+            gui.invalidate_map();
+            log_system(&state.ecs, Severity::Info, tr("log.game_loaded"));
+            true
+        },
+        Err(e) => {
+            log_system(&state.ecs, Severity::Error, tr_args("log.load_failed", &[("error", e.to_string())]));
+            false
+        }
+    }
+}
+
+/// Carries out a game over screen choice.
+fn apply_game_over_action<R: Renderer>(button: gui::GameOverButton, state: &mut State, gui: &mut gui::GUI<R>) {
+    match button {
+        gui::GameOverButton::Restart => {
+            new_game(state);
+            gui.invalidate_map();
+            gui.menu = gui::GuiMenu::GameMenu(gui::GameMenuTab::Unit);
+        },
+        gui::GameOverButton::Load => {
+            if !saveload_system::save_exists() {
+                log_system(&state.ecs, Severity::Warning, tr("log.no_save"));
+            } else if load(state, gui) {
+                gui.menu = gui::GuiMenu::GameMenu(gui::GameMenuTab::Unit);
+            }
+        },
+        gui::GameOverButton::MainMenu => {
+            gui.menu = gui::GuiMenu::MainMenu(gui::MainMenuButton::Start);
+        }
+    }
+}
+
+/// Carries out a pause menu choice. Returns false when the game should exit.
+fn apply_pause_action<R: Renderer>(button: gui::PauseMenuButton, tab: gui::GameMenuTab, state: &mut State, gui: &mut gui::GUI<R>) -> bool {
+    match button {
+        gui::PauseMenuButton::Resume => {
+            gui.menu = gui::GuiMenu::GameMenu(tab);
+        },
+        gui::PauseMenuButton::Save => {
+            match saveload_system::save_game(&mut state.ecs) {
+                Ok(()) => {
+                    state.unsaved = false;
+                    log_system(&state.ecs, Severity::Info, tr("log.game_saved"));
+                },
+                Err(e) => log_system(&state.ecs, Severity::Error, tr_args("log.save_failed", &[("error", e.to_string())]))
+            }
+            gui.menu = gui::GuiMenu::GameMenu(tab);
+        },
+        gui::PauseMenuButton::Load => {
+            load(state, gui);
+            gui.menu = gui::GuiMenu::GameMenu(tab);
+        },
+        gui::PauseMenuButton::Settings => {
+            gui.menu = gui::GuiMenu::SettingsMenu(gui::SettingsField::WindowMode, Some(tab));
+        },
+        gui::PauseMenuButton::QuitToMenu => {
+            gui.menu = gui::GuiMenu::MainMenu(gui::MainMenuButton::Start);
+        },
+        gui::PauseMenuButton::QuitGame => {
+            return false;
+        }
+    }
+    true
+}
+
+/// Asks for confirmation first when the choice would throw away unsaved progress.
+fn choose_pause_action<R: Renderer>(button: gui::PauseMenuButton, tab: gui::GameMenuTab, state: &mut State, gui: &mut gui::GUI<R>) -> bool {
+    if button == gui::PauseMenuButton::Load && !saveload_system::save_exists() {
+        log_system(&state.ecs, Severity::Warning, tr("log.no_save"));
+        gui.menu = gui::GuiMenu::GameMenu(tab);
+        return true;
+    }
+
+    if state.unsaved && button.discards_progress() {
+        gui.menu = gui::GuiMenu::ConfirmMenu(button, tab);
+        return true;
+    }
+
+    apply_pause_action(button, tab, state, gui)
+}
+
+
+/// Applies the config to the running game, so changes in the settings menu preview immediately.
+fn apply_config<F: Frontend>(state: &mut State, gui: &mut gui::GUI<F::Renderer>, frontend: &mut F) {
+    for error in frontend.configure(&state.config, &mut gui.renderer) {
+        log_system(&state.ecs, Severity::Error, error);
+    }
+
+    if let Err(e) = locale::set_language(&state.config.language) {
+        log_system(&state.ecs, Severity::Error, tr_args("log.language_failed", &[("language", state.config.language.clone()), ("error", e.to_string())]));
+    }
+
+    state.speed.tick_size = state.config.tick_size;
+    let (bindings, warnings) = input::KeyBindings::with_overrides(&state.config.keys);
+    state.bindings = bindings;
+    for warning in warnings {
+        log_system(&state.ecs, Severity::Warning, warning);
+    }
+}
+
+/// A new file in the screenshots directory named after the current time, like `map-20240131-235959.png`.
+fn capture_path(prefix: &str) -> PathBuf {
+    let _ = fs::create_dir_all(CAPTURE_DIR);
+    let stem = format!("{}-{}", prefix, clock::file_timestamp(SystemTime::now()));
+    let mut path = PathBuf::from(CAPTURE_DIR).join(format!("{}.png", stem));
+    let mut n = 2;
+    while path.exists() {
+        path = PathBuf::from(CAPTURE_DIR).join(format!("{}-{}.png", stem, n));
+        n += 1;
+    }
+    path
+}
+
+/// Selects the next or previous unit, or the first one when nothing is selected.
+fn cycle_unit(state: &mut State, forward: bool) {
+    let entities = state.ecs.entities();
+    let units = state.ecs.read_storage::<Unit>();
+    let list: Vec<Entity> = (&entities, &units).join().map(|(entity, _)| entity).collect();
+    if list.is_empty() {
+        return;
+    }
+
+    let next = match state.selected.and_then(|selected| list.iter().position(|entity| *entity == selected)) {
+        Some(i) if forward => (i + 1) % list.len(),
+        Some(i) => (i + list.len() - 1) % list.len(),
+        None => 0
+    };
+    state.selected = Some(list[next]);
+}
+
+fn find_necromancer(ecs: &World) -> Option<Entity> {
+    let entities = ecs.entities();
+    let necromancers = ecs.read_storage::<Necromancer>();
+    (&entities, &necromancers).join().map(|(entity, _)| entity).next()
+}
+
+/// Takes or gives up direct control of the necromancer. Taking it selects the necromancer and looks at it.
+fn toggle_possession<R: Renderer>(state: &mut State, gui: &mut gui::GUI<R>) {
+    if state.possessing {
+        state.possessing = false;
+        return;
+    }
+    let necromancer = match find_necromancer(&state.ecs) {
+        Some(necromancer) => necromancer,
+        None => {
+            log_system(&state.ecs, Severity::Warning, tr("log.no_necromancer"));
+            return;
+        }
+    };
+    state.possessing = true;
+    state.selected = Some(necromancer);
+    let position = state.ecs.read_storage::<Position>().get(necromancer).cloned();
+    if let Some(position) = position {
+        gui.center_camera(state, position.x, position.y);
+    }
+}
+
+/// Starts aiming the necromancer's first spell at where it stands, or stops aiming.
+fn toggle_aim<R: Renderer>(state: &mut State, gui: &mut gui::GUI<R>) {
+    if gui.aim.take().is_some() {
+        return;
+    }
+    let necromancer = find_necromancer(&state.ecs);
+    let position = necromancer.and_then(|entity| state.ecs.read_storage::<Position>().get(entity).cloned());
+    let knows_spells = necromancer.and_then(|entity| state.ecs.read_storage::<Necromancer>().get(entity).map(|n| !n.spellbook.is_empty()));
+    match (position, knows_spells) {
+        (Some(position), Some(true)) => gui.aim = Some(gui::Aim { slot: 0, x: position.x, y: position.y }),
+        _ => log_system(&state.ecs, Severity::Warning, tr("log.no_caster"))
+    }
+}
+
+/// Aims the next or previous spell of the spellbook instead.
+fn cycle_spell<R: Renderer>(state: &State, gui: &mut gui::GUI<R>, forward: bool) {
+    let count = find_necromancer(&state.ecs)
+        .and_then(|entity| state.ecs.read_storage::<Necromancer>().get(entity).map(|necromancer| necromancer.spellbook.len()))
+        .unwrap_or(0);
+    if let (Some(aim), true) = (gui.aim.as_mut(), count > 0) {
+        aim.slot = if forward { (aim.slot + 1) % count } else { (aim.slot + count - 1) % count };
+    }
+}
+
+/// Casts the aimed spell on the next tick, or logs why it cannot be cast there.
+fn cast<R: Renderer>(state: &mut State, gui: &mut gui::GUI<R>) {
+    let (aim, necromancer) = match (gui.aim, find_necromancer(&state.ecs)) {
+        (Some(aim), Some(necromancer)) => (aim, necromancer),
+        _ => return
+    };
+    let checked = {
+        let necromancers = state.ecs.read_storage::<Necromancer>();
+        let caster = necromancers.get(necromancer).unwrap();
+        let position = state.ecs.read_storage::<Position>().get(necromancer).cloned();
+        let spells = state.ecs.fetch::<spells::Spells>();
+        match (caster.spellbook.get(aim.slot), position) {
+            (Some(id), Some(position)) => match spells.get(id) {
+                Some(spell) => spell.check(id, caster, (position.x, position.y), (aim.x, aim.y), state.ecs.fetch::<clock::GameClock>().tick)
+                                    .map(|()| id.clone()),
+                None => return
+            },
+            _ => return
+        }
+    };
+    match checked {
+        Ok(spell) => {
+            state.ecs.write_storage::<WantsToCast>().insert(necromancer, WantsToCast{ spell, x: aim.x, y: aim.y }).unwrap();
+            gui.aim = None;
+        },
+        Err(reason) => log_system(&state.ecs, Severity::Warning, reason)
+    }
+}
+
+/// Steps the possessed necromancer one tile, on the next tick.
+fn walk(state: &mut State, dx: i32, dy: i32) {
+    let necromancer = match find_necromancer(&state.ecs) {
+        Some(necromancer) => necromancer,
+        None => return
+    };
+    let map = state.ecs.fetch::<map::Map>();
+    let positions = state.ecs.read_storage::<Position>();
+    if let Some(position) = positions.get(necromancer) {
+        let (x, y) = (position.x as i32 + dx, position.y as i32 + dy);
+        if x >= 0 && y >= 0 && (x as u32) < map.width && (y as u32) < map.height {
+            state.ecs.write_storage::<WantsToMove>().insert(necromancer, WantsToMove{ x: x as u32, y: y as u32 }).unwrap();
+        }
+    }
+}
+
+/// Selects what is shown at a map cell, preferring units over whatever they stand on, then what is drawn on top.
+fn select_at(state: &mut State, x: u32, y: u32) {
+    let entities = state.ecs.entities();
+    let positions = state.ecs.read_storage::<Position>();
+    let renderables = state.ecs.read_storage::<Renderable>();
+    let units = state.ecs.read_storage::<Unit>();
+
+    let mut found: Vec<(Entity, bool, RenderLayer)> = (&entities, &positions, &renderables).join()
+        .filter(|(_, position, _)| position.x == x && position.y == y)
+        .map(|(entity, _, render)| (entity, units.contains(entity), render.layer))
+        .collect();
+    found.sort_by_key(|(_, is_unit, layer)| (!is_unit, std::cmp::Reverse(*layer)));
+    state.selected = found.first().map(|(entity, _, _)| *entity);
+}
+
+/// Where the `index`th shown log entry happened: where its entity is now, or where it was logged.
+fn log_entry_location(ecs: &World, index: usize) -> Option<(u32, u32)> {
+    let log = ecs.fetch::<Gamelog>();
+    let entry = log.visible().nth(index)?;
+    let positions = ecs.read_storage::<Position>();
+
+    entry.entity.filter(|entity| ecs.entities().is_alive(*entity))
+                .and_then(|entity| positions.get(entity))
+                .map(|position| (position.x, position.y))
+                .or(entry.location)
+}
+
+/// Gives the entity the typed name. An empty name keeps the old one.
+fn rename(state: &mut State, entity: Entity, name: &str) {
+    let name = name.trim();
+    if name.is_empty() || !state.ecs.entities().is_alive(entity) {
+        return;
+    }
+
+    let old = state.ecs.read_storage::<Name>().get(entity).map_or_else(|| tr("unit.unnamed"), |old| old.name.clone());
+    state.ecs.write_storage::<Name>().insert(entity, Name{ name: name.to_string() }).unwrap();
+    state.unsaved = true;
+    log_system(&state.ecs, Severity::Info, tr_args("log.renamed", &[("old", old), ("name", name.to_string())]));
+}
+
+/// Translates a key press into an action of the given context.
+/// Held keys only repeat actions that make sense to repeat.
+fn key_action(bindings: &input::KeyBindings, context: InputContext, event: &Event) -> Option<Action> {
+    match *event {
+        Event::KeyDown { keycode: Some(key), keymod, repeat, .. } => {
+            bindings.action(context, key, keymod).filter(|action| !repeat || action.repeats())
+        },
+        _ => None
+    }
+}
+
+fn change_setting(config: &mut config::Config, field: gui::SettingsField, forward: bool) {
+    match field {
+        gui::SettingsField::WindowMode => {
+            config.window_mode = if forward { config.window_mode.next() } else { config.window_mode.prev() };
+        },
+        gui::SettingsField::Tileset => {
+            let tilesets = tileset::available_tilesets();
+            if tilesets.is_empty() {
+                return;
+            }
+            let current = tilesets.iter().position(|t| *t == config.tileset).unwrap_or(0);
+            let next = if forward { current + 1 } else { current + tilesets.len() - 1 };
+            config.tileset = tilesets[next % tilesets.len()].clone();
+        },
+        gui::SettingsField::TileSize => {
+            let sizes = config::TILE_SIZES;
+            let current = sizes.iter().position(|s| *s == config.tile_size).unwrap_or(0);
+            config.tile_size = if forward { sizes[(current + 1).min(sizes.len() - 1)] } else { sizes[current.saturating_sub(1)] };
+        },
+        gui::SettingsField::TickSize => {
+            config.tick_size = if forward { config.tick_size + 1 } else { config.tick_size - 1 }
+                .clamp(config::MIN_TICK_SIZE, config::MAX_TICK_SIZE);
+        },
+        gui::SettingsField::Language => {
+            let languages = locale::available_languages();
+            if languages.is_empty() {
+                return;
+            }
+            let current = languages.iter().position(|l| *l == config.language).unwrap_or(0);
+            let next = if forward { current + 1 } else { current + languages.len() - 1 };
+            config.language = languages[next % languages.len()].clone();
+        },
+        // Edited in its own menu
+        gui::SettingsField::Keys => {}
+    }
+}
+
+/// Closing the window from the settings drops the settings not yet saved. A game in progress asks first
+/// when it has unsaved progress. Returns false when the game should exit.
+fn quit_from_settings<F: Frontend>(return_tab: Option<gui::GameMenuTab>, saved_config: &config::Config, state: &mut State,
+                                   gui: &mut gui::GUI<F::Renderer>, frontend: &mut F) -> bool {
+    if state.config != *saved_config {
+        state.config = saved_config.clone();
+        apply_config(state, gui, frontend);
+    }
+    match return_tab {
+        Some(tab) => choose_pause_action(gui::PauseMenuButton::QuitGame, tab, state, gui),
+        None => false
+    }
 }
 
 
 fn main() {
+    let (config, config_warnings) = config::Config::load();
+    locale::set_language(&config.language).unwrap();
+
+    let args: Vec<String> = std::env::args().collect();
+    let frames = args.iter().position(|arg| arg == "--frames").and_then(|i| args.get(i + 1)).and_then(|n| n.parse().ok());
+    let result = if let Some(frames) = frames {
+        let mut gui = gui::GUI::new(MemoryRenderer::new(80, 24));
+        run(&mut HeadlessFrontend::new(frames), &mut gui, config, config_warnings)
+    } else if args.iter().any(|arg| arg == "--terminal") {
+        run_in_terminal(config, config_warnings)
+    } else {
+        run_in_window(config, config_warnings)
+    };
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+/// The terminal is given back to the shell before any error is reported.
+fn run_in_terminal(config: config::Config, config_warnings: Vec<String>) -> Result<(), String> {
+    let (width, height) = terminal::terminal_size();
+    let mut frontend = TerminalFrontend::new().map_err(|e| tr_args("log.terminal_failed", &[("error", e.to_string())]))?;
+    let mut gui = gui::GUI::new(TerminalRenderer::new(std::io::stdout(), width, height));
+    run(&mut frontend, &mut gui, config, config_warnings)
+}
+
+fn run_in_window(config: config::Config, config_warnings: Vec<String>) -> Result<(), String> {
     let ctx = sdl2::init().unwrap();
 
     let video_subsystem = ctx.video().unwrap();
     let _image_context = sdl2::image::init(InitFlag::PNG | InitFlag::JPG).unwrap();
 
-    let window = video_subsystem.window("Necronix", 0, 0)
-                                .position_centered()
-                                .fullscreen_desktop()
-                                .build()
-                                .unwrap();
+    let mut window_builder = video_subsystem.window("Necronix", 1280, 720);
+    window_builder.position_centered();
+    match config.window_mode {
+        config::WindowMode::FullscreenDesktop => { window_builder.fullscreen_desktop(); },
+        config::WindowMode::Fullscreen => { window_builder.fullscreen(); },
+        config::WindowMode::Windowed => {}
+    }
+    let window = window_builder.build().unwrap();
 
-    let mut canvas = window.into_canvas().build().unwrap();
+    let canvas = window.into_canvas().build().unwrap();
     let texture_creator = canvas.texture_creator();
-    let mut tileset = texture_creator.load_texture("./resources/16x16-RogueYun-AgmEdit.png").unwrap();
+    let (tileset, glyphs) = sdl_renderer::load_tileset(&config.tileset, &texture_creator)
+                                 .or_else(|_| sdl_renderer::load_tileset(config::DEFAULT_TILESET, &texture_creator))
+                                 .unwrap();
 
-    let mut gui = gui::GUI { canvas: canvas, tileset: tileset, menu: gui::GuiMenu::MainMenu(gui::MainMenuButton::Start) };
+    let mut gui = gui::GUI::new(SdlRenderer::new(canvas, &texture_creator, tileset, glyphs, config.tile_size));
+    let mut frontend = SdlFrontend::new(ctx, video_subsystem, &texture_creator);
+    run(&mut frontend, &mut gui, config, config_warnings)
+}
 
+/// Runs the game until the player quits or the frontend is finished.
+fn run<F: Frontend>(frontend: &mut F, gui: &mut gui::GUI<F::Renderer>, config: config::Config, config_warnings: Vec<String>) -> Result<(), String> {
     let mut state = State{
         ecs: World::new(),
-        log: gamelog::Gamelog{ entries: vec!["Welcome to necronix!".to_string()] },
-        selected_unit_index: 0
+        selected: None,
+        speed: clock::GameSpeed::new(config.tick_size),
+        unsaved: false,
+        config: config.clone(),
+        bindings: input::KeyBindings::defaults(),
+        possessing: false
     };
+    let (bindings, binding_warnings) = input::KeyBindings::with_overrides(&config.keys);
+    state.bindings = bindings;
+    let mut saved_config = config;
 
-    let map = map::Map::new(15, 15);
+    dispatcher::register_components(&mut state.ecs);
+    let mut dispatcher = dispatcher::build_dispatcher(&mut state.ecs);
+    let (name_generator, name_warnings) = names::NameGenerator::load();
+    state.ecs.insert(name_generator);
+    let (effects, effect_warnings) = effects::Effects::load();
+    gui.effects = effects;
+    let (rules, rule_warnings) = rules::Rules::load();
+    state.ecs.insert(rules);
+    let (spells, spell_warnings) = spells::Spells::load();
+    state.ecs.insert(spells);
+    new_game(&mut state);
 
-    state.ecs.insert(map);
-
-    state.ecs.register::<Renderable>();
-    state.ecs.register::<Position>();
-    state.ecs.register::<Unit>();
-    state.ecs.register::<Name>();
-    state.ecs.register::<BlocksTile>();
-
-    let mut rng = rand::thread_rng();
-
-    for _ in 0..10 {
-        state.ecs.create_entity()
-                 .with(Position{ x: rng.gen_range(0..15), y: rng.gen_range(0..15) })
-                 .with(Renderable{ glyph: 139 + rng.gen_range(0..3), color: (50 * rng.gen_range(0..3), 50 * rng.gen_range(0..3), 50 * rng.gen_range(0..3)) })
-                 .with(Unit{ mission: Mission::GoTo(rng.gen_range(0..15), rng.gen_range(0..15)) })
-                 .build();
+    log_system(&state.ecs, Severity::Info, tr("log.welcome"));
+    for warning in config_warnings.into_iter().chain(binding_warnings).chain(name_warnings).chain(effect_warnings).chain(rule_warnings).chain(spell_warnings) {
+        log_system(&state.ecs, Severity::Warning, warning);
     }
 
-    let mut events = ctx.event_pump().unwrap();
-    let mut mission_system = MissionSystem {};
-
-    ctx.mouse().show_cursor(false);
+    let mut rng = rand::thread_rng();
 
-    let mut tick = 0;
+    frontend.show_cursor(false);
 
+    let mut result = Ok(());
     'running: loop {
-        let units = state.ecs.read_storage::<Unit>().count();
+        if let Some(finished) = frontend.finished(&mut gui.renderer) {
+            result = finished;
+            break;
+        }
 
         match gui.menu {
             gui::GuiMenu::GameMenu(tab) => {
-                for event in events.poll_iter() {
+                for event in frontend.poll_events(&mut gui.renderer) {
                     match event {
-                        Event::Quit {..} |
-                        Event::KeyDown { keycode: Some(Keycode::Escape), repeat: false, .. } => {
-                            break 'running
+                        Event::Quit {..} => {
+                            let keep_running = choose_pause_action(gui::PauseMenuButton::QuitGame, tab, &mut state, gui);
+                            if !keep_running {
+                                break 'running
+                            }
                         },
-                        Event::KeyDown { keycode: Some(Keycode::Tab), .. } => {
-                            gui.menu = gui::GuiMenu::GameMenu(tab.next());
+                        Event::KeyDown {..} => match key_action(&state.bindings, InputContext::Aim, &event)
+                                                         .filter(|_| gui.aim.is_some())
+                                                         .or_else(|| key_action(&state.bindings, InputContext::Possess, &event).filter(|_| state.possessing))
+                                                         .or_else(|| key_action(&state.bindings, InputContext::Game, &event)) {
+                            Some(Action::Cast) => toggle_aim(&mut state, gui),
+                            Some(Action::CursorUp) => gui.move_aim(&state, 0, -1),
+                            Some(Action::CursorDown) => gui.move_aim(&state, 0, 1),
+                            Some(Action::CursorLeft) => gui.move_aim(&state, -1, 0),
+                            Some(Action::CursorRight) => gui.move_aim(&state, 1, 0),
+                            Some(Action::Next) => cycle_spell(&state, gui, true),
+                            Some(Action::Prev) => cycle_spell(&state, gui, false),
+                            Some(Action::Confirm) => cast(&mut state, gui),
+                            Some(Action::Possess) => toggle_possession(&mut state, gui),
+                            Some(Action::WalkUp) => walk(&mut state, 0, -1),
+                            Some(Action::WalkDown) => walk(&mut state, 0, 1),
+                            Some(Action::WalkLeft) => walk(&mut state, -1, 0),
+                            Some(Action::WalkRight) => walk(&mut state, 1, 0),
+                            Some(Action::Back) => {
+                                gui.menu = gui::GuiMenu::PauseMenu(gui::PauseMenuButton::Resume, tab);
+                            },
+                            Some(Action::NextTab) => {
+                                gui.menu = gui::GuiMenu::GameMenu(tab.next());
+                            },
+                            Some(Action::Pause) => {
+                                state.speed.toggle_pause();
+                            },
+                            Some(Action::SpeedUp) => {
+                                state.speed.faster();
+                            },
+                            Some(Action::SlowDown) => {
+                                state.speed.slower();
+                            },
+                            Some(Action::ToggleTimings) => {
+                                gui.show_timings = !gui.show_timings;
+                            },
+                            Some(Action::NextTileset) => {
+                                change_setting(&mut state.config, gui::SettingsField::Tileset, true);
+                                apply_config(&mut state, gui, frontend);
+                                if let Some(name) = frontend.tileset_name(&gui.renderer) {
+                                    log_system(&state.ecs, Severity::Info, tr_args("log.tileset", &[("name", name)]));
+                                }
+                            },
+                            Some(Action::Screenshot) => gui.request_screenshot(capture_path("screenshot")),
+                            Some(Action::ExportMap) => {
+                                let path = capture_path("map");
+                                match gui.export_map(&state, &path) {
+                                    Ok(()) => log_system(&state.ecs, Severity::Info, tr_args("log.map_exported", &[("path", path.display().to_string())])),
+                                    Err(e) => log_system(&state.ecs, Severity::Error, tr_args("log.export_failed", &[("error", e)]))
+                                }
+                            },
+                            Some(Action::CameraUp) => gui.pan_camera(&state, 0, -1),
+                            Some(Action::CameraDown) => gui.pan_camera(&state, 0, 1),
+                            Some(Action::CameraLeft) => gui.pan_camera(&state, -1, 0),
+                            Some(Action::CameraRight) => gui.pan_camera(&state, 1, 0),
+                            Some(Action::NextUnit) => cycle_unit(&mut state, true),
+                            Some(Action::PrevUnit) => cycle_unit(&mut state, false),
+                            Some(Action::Order) => {
+                                let mut units = state.ecs.write_storage::<Unit>();
+                                if let Some(unit) = state.selected.and_then(|entity| units.get_mut(entity)) {
+                                    unit.mission = Mission::GoTo(rng.gen_range(0..15), rng.gen_range(0..15));
+                                    state.unsaved = true;
+                                }
+                            },
+                            Some(Action::QueueOrder) => {
+                                let mut queues = state.ecs.write_storage::<MissionQueue>();
+                                if let Some(queue) = state.selected.and_then(|entity| queues.get_mut(entity)) {
+                                    queue.missions.push(Mission::GoTo(rng.gen_range(0..15), rng.gen_range(0..15)));
+                                    state.unsaved = true;
+                                }
+                            },
+                            Some(Action::Rename) => {
+                                if let Some(entity) = state.selected.filter(|entity| state.ecs.read_storage::<Unit>().contains(*entity)) {
+                                    gui.text_input = state.ecs.read_storage::<Name>().get(entity).map_or(String::new(), |name| name.name.clone());
+                                    gui.menu = gui::GuiMenu::RenameMenu(entity, tab);
+                                    frontend.set_text_input(true);
+                                    break;
+                                }
+                            },
+                            _ => {}
                         },
-                        Event::KeyDown { keycode: Some(Keycode::D), .. } => {
-                            state.selected_unit_index = (state.selected_unit_index + 1) % units;
+                        Event::MouseWheel { y, .. } if tab == gui::GameMenuTab::Log => {
+                            gui.scroll_log(-y);
                         },
-                        Event::KeyDown { keycode: Some(Keycode::A), .. } => {
-                            if state.selected_unit_index == 0 { state.selected_unit_index = units - 1; }
-                            else { state.selected_unit_index = state.selected_unit_index - 1; }
+                        Event::MouseMotion { x, y, .. } if gui.aim.is_some() && gui.map_cell(x, y).is_some() => {
+                            let cell = gui.map_cell(x, y);
+                            if let (Some(aim), Some((x, y))) = (gui.aim.as_mut(), cell) {
+                                (aim.x, aim.y) = (x, y);
+                            }
                         },
-                        Event::KeyDown { keycode: Some(Keycode::M), repeat: false, .. } => {
-                            let mut units = state.ecs.write_storage::<Unit>();
-                            for (i, unit) in (&mut units).join().enumerate() {
-                                if i == state.selected_unit_index {
-                                    unit.mission = Mission::GoTo(rng.gen_range(0..15), rng.gen_range(0..15));
+                        Event::MouseButtonDown { mouse_btn: MouseButton::Right, .. } if gui.aim.is_some() => {
+                            gui.aim = None;
+                        },
+                        Event::MouseMotion { mousestate, x, y, .. } if mousestate.left() => {
+                            if let Some(widgets::Hit { id: "minimap", .. }) = gui.hit(x, y) {
+                                if let Some((x, y)) = gui.minimap_cell(&state, x, y) {
+                                    gui.center_camera(&state, x, y);
                                 }
                             }
-                        }
+                        },
+                        Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => match gui.hit(x, y) {
+                            Some(widgets::Hit { id: "tabs", index }) => {
+                                gui.menu = gui::GuiMenu::GameMenu(gui::GameMenuTab::all()[index]);
+                            },
+                            Some(widgets::Hit { id: "map", .. }) if gui.aim.is_some() => {
+                                let cell = gui.map_cell(x, y);
+                                if let (Some(aim), Some((x, y))) = (gui.aim.as_mut(), cell) {
+                                    (aim.x, aim.y) = (x, y);
+                                    cast(&mut state, gui);
+                                }
+                            },
+                            Some(widgets::Hit { id: "map", .. }) => {
+                                if let Some((x, y)) = gui.map_cell(x, y) {
+                                    select_at(&mut state, x, y);
+                                    gui.menu = gui::GuiMenu::GameMenu(gui::GameMenuTab::Unit);
+                                }
+                            },
+                            Some(widgets::Hit { id: "minimap", .. }) => {
+                                if let Some((x, y)) = gui.minimap_cell(&state, x, y) {
+                                    gui.center_camera(&state, x, y);
+                                }
+                            },
+                            Some(widgets::Hit { id: "log", index }) => {
+                                if let Some((x, y)) = log_entry_location(&state.ecs, index) {
+                                    gui.center_camera(&state, x, y);
+                                }
+                            },
+                            Some(widgets::Hit { id, .. }) => {
+                                if let Some(category) = gamelog::LogCategory::from_filter_id(id) {
+                                    state.ecs.write_resource::<Gamelog>().toggle(category);
+                                }
+                            },
+                            None => {}
+                        },
                         _ => {}
                     }
                 }
 
-                tick = (tick + 1) % TICK_SIZE;
-                if tick == 0 {
-                    mission_system.run_now(&mut state.ecs);
+                if state.speed.advance_frame() {
+                    dispatcher.dispatch(&state.ecs);
                     state.ecs.maintain();
+                    state.unsaved = true;
+                    if state.possessing {
+                        let position = find_necromancer(&state.ecs).and_then(|entity| state.ecs.read_storage::<Position>().get(entity).cloned());
+                        match position {
+                            Some(position) => gui.center_camera(&state, position.x, position.y),
+                            None => state.possessing = false
+                        }
+                    }
+                    if state.ecs.fetch::<rules::GameResult>().ended.is_some() {
+                        gui.menu = gui::GuiMenu::GameOverMenu(gui::GameOverButton::Restart, tab);
+                    }
+                }
+            },
+            gui::GuiMenu::GameOverMenu(button, tab) => {
+                for event in frontend.poll_events(&mut gui.renderer) {
+                    let choice = match event {
+                        Event::Quit {..} => break 'running,
+                        Event::KeyDown {..} => match key_action(&state.bindings, InputContext::Menu, &event) {
+                            Some(Action::Confirm) => Some(button),
+                            Some(Action::Next) => {
+                                gui.menu = gui::GuiMenu::GameOverMenu(button.next(), tab);
+                                None
+                            },
+                            Some(Action::Prev) => {
+                                gui.menu = gui::GuiMenu::GameOverMenu(button.prev(), tab);
+                                None
+                            },
+                            _ => None
+                        },
+                        Event::MouseWheel { y, .. } => {
+                            gui.menu = gui::GuiMenu::GameOverMenu(if y > 0 { button.prev() } else { button.next() }, tab);
+                            None
+                        },
+                        Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => {
+                            match gui.carousel_hit(x, y) {
+                                Some(gui::CarouselHit::Prev) => {
+                                    gui.menu = gui::GuiMenu::GameOverMenu(button.prev(), tab);
+                                    None
+                                },
+                                Some(gui::CarouselHit::Next) => {
+                                    gui.menu = gui::GuiMenu::GameOverMenu(button.next(), tab);
+                                    None
+                                },
+                                Some(gui::CarouselHit::Select) => Some(button),
+                                None => None
+                            }
+                        },
+                        _ => None
+                    };
+
+                    if let Some(choice) = choice {
+                        apply_game_over_action(choice, &mut state, gui);
+                        break;
+                    }
+                }
+            },
+            gui::GuiMenu::PauseMenu(button, tab) => {
+                for event in frontend.poll_events(&mut gui.renderer) {
+                    let choice = match event {
+                        Event::Quit {..} => Some(gui::PauseMenuButton::QuitGame),
+                        Event::KeyDown {..} => match key_action(&state.bindings, InputContext::Menu, &event) {
+                            Some(Action::Back) => Some(gui::PauseMenuButton::Resume),
+                            Some(Action::Confirm) => Some(button),
+                            Some(Action::Next) => {
+                                gui.menu = gui::GuiMenu::PauseMenu(button.next(), tab);
+                                None
+                            },
+                            Some(Action::Prev) => {
+                                gui.menu = gui::GuiMenu::PauseMenu(button.prev(), tab);
+                                None
+                            },
+                            _ => None
+                        },
+                        Event::MouseWheel { y, .. } => {
+                            gui.menu = gui::GuiMenu::PauseMenu(if y > 0 { button.prev() } else { button.next() }, tab);
+                            None
+                        },
+                        Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => {
+                            match gui.carousel_hit(x, y) {
+                                Some(gui::CarouselHit::Prev) => {
+                                    gui.menu = gui::GuiMenu::PauseMenu(button.prev(), tab);
+                                    None
+                                },
+                                Some(gui::CarouselHit::Next) => {
+                                    gui.menu = gui::GuiMenu::PauseMenu(button.next(), tab);
+                                    None
+                                },
+                                Some(gui::CarouselHit::Select) => Some(button),
+                                None => None
+                            }
+                        },
+                        _ => None
+                    };
+
+                    if let Some(choice) = choice {
+                        if !choose_pause_action(choice, tab, &mut state, gui) {
+                            break 'running
+                        }
+                        break;
+                    }
+                }
+            },
+            gui::GuiMenu::ConfirmMenu(button, tab) => {
+                for event in frontend.poll_events(&mut gui.renderer) {
+                    let answer = match event {
+                        Event::Quit {..} if button == gui::PauseMenuButton::QuitGame => Some(gui::ConfirmHit::Yes),
+                        Event::Quit {..} => {
+                            gui.menu = gui::GuiMenu::ConfirmMenu(gui::PauseMenuButton::QuitGame, tab);
+                            break;
+                        },
+                        Event::KeyDown {..} => match key_action(&state.bindings, InputContext::Confirm, &event) {
+                            Some(Action::Confirm) => Some(gui::ConfirmHit::Yes),
+                            Some(Action::Back) => Some(gui::ConfirmHit::No),
+                            _ => None
+                        },
+                        Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => gui.confirm_hit(x, y),
+                        _ => None
+                    };
+
+                    match answer {
+                        Some(gui::ConfirmHit::Yes) => {
+                            if !apply_pause_action(button, tab, &mut state, gui) {
+                                break 'running
+                            }
+                            break;
+                        },
+                        Some(gui::ConfirmHit::No) => {
+                            gui.menu = gui::GuiMenu::PauseMenu(button, tab);
+                            break;
+                        },
+                        None => {}
+                    }
+                }
+            },
+            gui::GuiMenu::RenameMenu(entity, tab) => {
+                for event in frontend.poll_events(&mut gui.renderer) {
+                    match event {
+                        Event::Quit {..} => {
+                            gui.menu = gui::GuiMenu::ConfirmMenu(gui::PauseMenuButton::QuitGame, tab);
+                        },
+                        Event::TextInput { text, .. } => {
+                            for c in text.chars().filter(|c| !c.is_control() && cp437::to_glyph(*c).is_some()) {
+                                if gui.text_input.chars().count() < names::MAX_LENGTH {
+                                    gui.text_input.push(c);
+                                }
+                            }
+                        },
+                        Event::KeyDown {..} => match key_action(&state.bindings, InputContext::Text, &event) {
+                            Some(Action::Erase) => {
+                                gui.text_input.pop();
+                            },
+                            Some(Action::Confirm) => {
+                                let name = std::mem::take(&mut gui.text_input);
+                                rename(&mut state, entity, &name);
+                                gui.menu = gui::GuiMenu::GameMenu(tab);
+                            },
+                            Some(Action::Back) => {
+                                gui.menu = gui::GuiMenu::GameMenu(tab);
+                            },
+                            _ => {}
+                        },
+                        _ => {}
+                    }
+                    if gui.menu != gui::GuiMenu::RenameMenu(entity, tab) {
+                        frontend.set_text_input(false);
+                        break;
+                    }
                 }
             },
             gui::GuiMenu::MainMenu(button) => {
-                for event in events.poll_iter() {
+                for event in frontend.poll_events(&mut gui.renderer) {
                     match event {
-                        Event::Quit {..} |
-                        Event::KeyDown { keycode: Some(Keycode::Escape), repeat: false, .. } => {
+                        Event::Quit {..} => {
                             break 'running
                         },
-                        Event::KeyDown { keycode: Some(Keycode::D), .. } => {
-                            gui.menu = gui::GuiMenu::MainMenu(button.next());
+                        Event::KeyDown {..} => match key_action(&state.bindings, InputContext::Menu, &event) {
+                            Some(Action::Back) => {
+                                break 'running
+                            },
+                            Some(Action::Next) => {
+                                gui.menu = gui::GuiMenu::MainMenu(button.next());
+                            },
+                            Some(Action::Prev) => {
+                                gui.menu = gui::GuiMenu::MainMenu(button.prev());
+                            },
+                            Some(Action::Confirm) => {
+                                if button == gui::MainMenuButton::Start {
+                                    new_game(&mut state);
+                                    gui.invalidate_map();
+                                }
+                                gui.menu = button.get_menu();
+                                break;
+                            },
+                            _ => {}
                         },
-                        Event::KeyDown { keycode: Some(Keycode::A), .. } => {
-                            gui.menu = gui::GuiMenu::MainMenu(button.prev());
+                        _ => {}
+                    }
+                }
+            },
+            gui::GuiMenu::SettingsMenu(field, return_tab) => {
+                let back = match return_tab {
+                    Some(tab) => gui::GuiMenu::PauseMenu(gui::PauseMenuButton::Settings, tab),
+                    None => gui::GuiMenu::MainMenu(gui::MainMenuButton::Settings)
+                };
+
+                for event in frontend.poll_events(&mut gui.renderer) {
+                    match event {
+                        Event::Quit {..} => {
+                            if !quit_from_settings(return_tab, &saved_config, &mut state, gui, frontend) {
+                                break 'running
+                            }
+                            break;
+                        },
+                        Event::KeyDown {..} => match key_action(&state.bindings, InputContext::Settings, &event) {
+                            Some(Action::Prev | Action::Next | Action::Confirm) if field == gui::SettingsField::Keys => {
+                                gui.menu = gui::GuiMenu::KeysMenu(0, false, return_tab);
+                                break;
+                            },
+                            Some(Action::Up) => {
+                                gui.menu = gui::GuiMenu::SettingsMenu(field.prev(), return_tab);
+                            },
+                            Some(Action::Down) => {
+                                gui.menu = gui::GuiMenu::SettingsMenu(field.next(), return_tab);
+                            },
+                            Some(Action::Prev) => {
+                                change_setting(&mut state.config, field, false);
+                                apply_config(&mut state, gui, frontend);
+                            },
+                            Some(Action::Next) => {
+                                change_setting(&mut state.config, field, true);
+                                apply_config(&mut state, gui, frontend);
+                            },
+                            Some(Action::Confirm) => {
+                                let warnings = state.config.validate();
+                                for warning in warnings {
+                                    log_system(&state.ecs, Severity::Warning, warning);
+                                }
+                                match state.config.save() {
+                                    Ok(()) => log_system(&state.ecs, Severity::Info, tr("log.settings_saved")),
+                                    Err(e) => log_system(&state.ecs, Severity::Error, tr_args("log.settings_failed", &[("error", e.to_string())]))
+                                }
+                                saved_config = state.config.clone();
+                                gui.menu = back;
+                                break;
+                            },
+                            Some(Action::Back) => {
+                                if state.config != saved_config {
+                                    state.config = saved_config.clone();
+                                    apply_config(&mut state, gui, frontend);
+                                }
+                                gui.menu = back;
+                                break;
+                            },
+                            _ => {}
+                        },
+                        Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => match gui.settings_hit(x, y) {
+                            Some(gui::SettingsField::Keys) if field == gui::SettingsField::Keys => {
+                                gui.menu = gui::GuiMenu::KeysMenu(0, false, return_tab);
+                                break;
+                            },
+                            Some(field) => {
+                                gui.menu = gui::GuiMenu::SettingsMenu(field, return_tab);
+                            },
+                            None => {}
+                        },
+                        _ => {}
+                    }
+                }
+            },
+            gui::GuiMenu::KeysMenu(selected, capturing, return_tab) => {
+                let rows = state.bindings.describe_all();
+                let (context, action, _) = rows[selected.min(rows.len() - 1)];
+
+                for event in frontend.poll_events(&mut gui.renderer) {
+                    match event {
+                        Event::Quit {..} => {
+                            if !quit_from_settings(return_tab, &saved_config, &mut state, gui, frontend) {
+                                break 'running
+                            }
+                            break;
+                        },
+                        // Escape always cancels, so a bad binding cannot lock the player in
+                        Event::KeyDown { keycode: Some(key), keymod, repeat: false, .. } if capturing => {
+                            if key == Keycode::Escape {
+                                gui.menu = gui::GuiMenu::KeysMenu(selected, false, return_tab);
+                                break;
+                            }
+                            if !input::KeyBinding::is_modifier(key) {
+                                state.config.rebind(context, action, Some(input::KeyBinding::pressed(key, keymod)));
+                                apply_config(&mut state, gui, frontend);
+                                gui.menu = gui::GuiMenu::KeysMenu(selected, false, return_tab);
+                                break;
+                            }
+                        },
+                        Event::KeyDown {..} if !capturing => match key_action(&state.bindings, InputContext::Settings, &event) {
+                            Some(Action::Up) => {
+                                gui.menu = gui::GuiMenu::KeysMenu(selected.saturating_sub(1), false, return_tab);
+                                break;
+                            },
+                            Some(Action::Down) => {
+                                gui.menu = gui::GuiMenu::KeysMenu((selected + 1).min(rows.len() - 1), false, return_tab);
+                                break;
+                            },
+                            Some(Action::Confirm) => {
+                                gui.menu = gui::GuiMenu::KeysMenu(selected, true, return_tab);
+                                break;
+                            },
+                            Some(Action::Erase) => {
+                                state.config.rebind(context, action, None);
+                                apply_config(&mut state, gui, frontend);
+                            },
+                            Some(Action::Back) => {
+                                gui.menu = gui::GuiMenu::SettingsMenu(gui::SettingsField::Keys, return_tab);
+                                break;
+                            },
+                            _ => {}
+                        },
+                        Event::MouseWheel { y, .. } if !capturing => {
+                            let next = if y > 0 { selected.saturating_sub(1) } else { (selected + 1).min(rows.len() - 1) };
+                            gui.menu = gui::GuiMenu::KeysMenu(next, false, return_tab);
+                            break;
+                        },
+                        Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } if !capturing => {
+                            if let Some(row) = gui.keys_hit(x, y) {
+                                gui.menu = gui::GuiMenu::KeysMenu(row, row == selected, return_tab);
+                                break;
+                            }
+                        },
+                        _ => {}
+                    }
+                }
+            },
+            gui::GuiMenu::HelpMenu(page) => {
+                for event in frontend.poll_events(&mut gui.renderer) {
+                    match event {
+                        Event::Quit {..} => {
+                            break 'running
+                        },
+                        Event::KeyDown {..} => match key_action(&state.bindings, InputContext::Menu, &event) {
+                            Some(Action::Next) => {
+                                gui.menu = gui::GuiMenu::HelpMenu(page.next());
+                            },
+                            Some(Action::Prev) => {
+                                gui.menu = gui::GuiMenu::HelpMenu(page.prev());
+                            },
+                            Some(Action::Back) => {
+                                gui.menu = gui::GuiMenu::MainMenu(gui::MainMenuButton::Help);
+                            },
+                            _ => {}
                         },
-                        Event::KeyDown { keycode: Some(Keycode::Return), repeat: false, .. } => {
-                            gui.menu = button.get_menu();
-                        }
                         _ => {}
                     }
                 }
             },
             _ => {
-                for event in events.poll_iter() {
+                for event in frontend.poll_events(&mut gui.renderer) {
                     match event {
                         Event::Quit {..} => {
                             break 'running
                         },
-                        Event::KeyDown { keycode: Some(Keycode::Escape), repeat: false, .. } => {
-                            gui.menu = gui::GuiMenu::MainMenu(gui::MainMenuButton::Start)
+                        Event::KeyDown {..} => {
+                            if let Some(Action::Back) = key_action(&state.bindings, InputContext::Menu, &event) {
+                                gui.menu = gui::GuiMenu::MainMenu(gui::MainMenuButton::Start)
+                            }
                         },
                         _ => {}
                     }
@@ -149,9 +1163,11 @@ fn main() {
             }
         }
 
+        frontend.show_cursor(gui.menu.shows_cursor());
         gui.render(&mut state);
         std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
 
-    ctx.mouse().show_cursor(true);
+    frontend.show_cursor(true);
+    result
 }
@@ -0,0 +1,58 @@
+use specs::prelude::*;
+
+use super::{Necromancer, Position, map::Map};
+use super::clock::GameClock;
+
+/// Ticks between the necromancer regaining mana. Corrupted ground gives twice as much.
+const MANA_INTERVAL: u64 = 5;
+
+pub struct ManaSystem {}
+
+impl<'a> System<'a> for ManaSystem {
+    type SystemData = (
+        WriteStorage<'a, Necromancer>,
+        ReadStorage<'a, Position>,
+        ReadExpect<'a, Map>,
+        Read<'a, GameClock>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut necromancers, positions, map, clock) = data;
+        if clock.tick % MANA_INTERVAL != 0 {
+            return;
+        }
+
+        for (necromancer, pos) in (&mut necromancers, &positions).join() {
+            let gain = if map.is_corrupted(pos.x, pos.y) { 2 } else { 1 };
+            necromancer.mana = (necromancer.mana + gain).min(necromancer.max_mana);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::MAX_CORRUPTION;
+
+    #[test]
+    fn mana_comes_back_faster_on_corrupted_ground() {
+        let mut world = World::new();
+        world.register::<Necromancer>();
+        world.register::<Position>();
+        let mut map = Map::new(2, 1);
+        map.corruption[1] = MAX_CORRUPTION;
+        world.insert(map);
+        world.insert(GameClock::default());
+        let plain = world.create_entity().with(Position{ x: 0, y: 0 }).with(Necromancer{ mana: 0, ..Necromancer::new(3, vec![]) }).build();
+        let corrupted = world.create_entity().with(Position{ x: 1, y: 0 }).with(Necromancer{ mana: 0, ..Necromancer::new(3, vec![]) }).build();
+
+        for _ in 0..2 * MANA_INTERVAL {
+            ManaSystem {}.run_now(&world);
+            world.write_resource::<GameClock>().tick += 1;
+        }
+
+        let necromancers = world.read_storage::<Necromancer>();
+        assert_eq!(necromancers.get(plain).unwrap().mana, 2);
+        assert_eq!(necromancers.get(corrupted).unwrap().mana, 3);
+    }
+}
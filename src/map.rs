@@ -1,9 +1,24 @@
 use rand::Rng;
+use serde::{Serialize, Deserialize};
 
+pub const MAX_CORRUPTION: u8 = 100;
+/// Corruption from which a tile belongs to the undead.
+pub const CORRUPTED: u8 = 50;
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Map {
     pub width: u32,
     pub height: u32,
-    pub tiles: Vec<u32>
+    pub tiles: Vec<u32>,
+    /// Tiles the undead have seen. Saves from before fog of war have none and show everything.
+    #[serde(default)]
+    pub revealed: Vec<bool>,
+    /// How corrupted each tile is, up to `MAX_CORRUPTION`. Saves from before corruption have none.
+    #[serde(default)]
+    pub corruption: Vec<u8>,
+    /// Goes up whenever tiles change, so views can tell when to redraw them.
+    #[serde(skip)]
+    pub revision: u64
 }
 
 impl Map {
@@ -19,11 +34,49 @@ impl Map {
         Map {
             width: width,
             height: height,
-            tiles: tiles
+            tiles: tiles,
+            revealed: vec![false; (width * height) as usize],
+            corruption: vec![0; (width * height) as usize],
+            revision: 0
         }
     }
 
     pub fn xy_idx(&self, x: u32, y: u32) -> u32 {
         y * self.width + x
     }
+
+    pub fn is_revealed(&self, x: u32, y: u32) -> bool {
+        self.revealed.get(self.xy_idx(x, y) as usize).copied().unwrap_or(true)
+    }
+
+    pub fn corruption_at(&self, x: u32, y: u32) -> u8 {
+        self.corruption.get(self.xy_idx(x, y) as usize).copied().unwrap_or(0)
+    }
+
+    pub fn is_corrupted(&self, x: u32, y: u32) -> bool {
+        self.corruption_at(x, y) >= CORRUPTED
+    }
+
+    /// Part of the map that belongs to the undead, from 0 to 1.
+    pub fn corrupted_share(&self) -> f32 {
+        let corrupted = self.corruption.iter().filter(|corruption| **corruption >= CORRUPTED).count();
+        corrupted as f32 / (self.width * self.height).max(1) as f32
+    }
+
+    /// Reveals the tiles within `radius` of a tile.
+    pub fn reveal_around(&mut self, x: u32, y: u32, radius: u32) {
+        if self.revealed.is_empty() {
+            return;
+        }
+        for ty in y.saturating_sub(radius)..(y + radius + 1).min(self.height) {
+            for tx in x.saturating_sub(radius)..(x + radius + 1).min(self.width) {
+                let (dx, dy) = (tx.abs_diff(x), ty.abs_diff(y));
+                let idx = self.xy_idx(tx, ty) as usize;
+                if dx * dx + dy * dy <= radius * radius && !self.revealed[idx] {
+                    self.revealed[idx] = true;
+                    self.revision += 1;
+                }
+            }
+        }
+    }
 }
@@ -1,5 +1,8 @@
 use specs::prelude::*;
-use super::{Unit, Position, Mission};
+use super::{Unit, MissionQueue, Position, Mission, WantsToMove};
+use super::clock::GameClock;
+use super::gamelog::{Gamelog, LogCategory, LogEntry};
+use super::locale::tr_args;
 
 pub struct MissionSystem {}
 
@@ -7,13 +10,23 @@ impl<'a> System<'a> for MissionSystem {
     type SystemData = (
         Entities<'a>,
         WriteStorage<'a, Unit>,
-        WriteStorage<'a, Position>
+        WriteStorage<'a, MissionQueue>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, WantsToMove>,
+        Read<'a, GameClock>,
+        Write<'a, Gamelog>
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (_entities, mut units, mut positions) = data;
+        let (entities, mut units, mut queues, positions, mut wants_to_move, clock, mut log) = data;
+
+        for (entity, unit, queue, pos) in (&entities, &mut units, (&mut queues).maybe(), &positions).join()  {
+            if let (Mission::Stay, Some(queue)) = (&unit.mission, queue) {
+                if !queue.missions.is_empty() {
+                    unit.mission = queue.missions.remove(0);
+                }
+            }
 
-        for (unit, pos) in (&mut units, &mut positions).join()  {
             match unit.mission {
                 Mission::GoTo(x, y) => {
                     let dx = (x as i32 - pos.x as i32) as f32;
@@ -21,6 +34,9 @@ impl<'a> System<'a> for MissionSystem {
 
                     if dx == 0. && dy == 0. {
                         unit.mission = Mission::Stay;
+                        let text = tr_args("log.arrived", &[("x", x.to_string()), ("y", y.to_string())]);
+                        log.add(LogEntry::new(clock.tick, LogCategory::Work, text).about(entity).at(x, y));
+                        continue;
                     }
 
                     let length = (dx * dx + dy * dy).sqrt();
@@ -28,8 +44,10 @@ impl<'a> System<'a> for MissionSystem {
                     let nx = (dx / length).round() as i32;
                     let ny = (dy / length).round() as i32;
 
-                    pos.x = (pos.x as i32 + nx) as u32;
-                    pos.y = (pos.y as i32 + ny) as u32;
+                    wants_to_move.insert(entity, WantsToMove {
+                        x: (pos.x as i32 + nx) as u32,
+                        y: (pos.y as i32 + ny) as u32
+                    }).expect("Unable to insert move intent");
                 },
                 _ => {}
             }
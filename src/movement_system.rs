@@ -0,0 +1,32 @@
+use specs::prelude::*;
+use super::{Position, StatusEffect, StatusEffects, WantsToMove, clock::GameClock, map::Map};
+
+pub struct MovementSystem {}
+
+impl<'a> System<'a> for MovementSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Map>,
+        Read<'a, GameClock>,
+        ReadStorage<'a, StatusEffects>,
+        WriteStorage<'a, WantsToMove>,
+        WriteStorage<'a, Position>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, map, clock, statuses, mut wants_to_move, mut positions) = data;
+
+        for (_entity, step, effects, pos) in (&entities, &wants_to_move, statuses.maybe(), &mut positions).join() {
+            // Slowed entities only move every other tick
+            if clock.tick % 2 == 1 && effects.is_some_and(|effects| effects.has(StatusEffect::Slowed)) {
+                continue;
+            }
+            if step.x < map.width && step.y < map.height {
+                pos.x = step.x;
+                pos.y = step.y;
+            }
+        }
+
+        wants_to_move.clear();
+    }
+}
@@ -0,0 +1,147 @@
+use rand::Rng;
+use rand::seq::SliceRandom;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use super::locale::tr_args;
+
+const NAMES_DIR: &str = "./resources/names";
+/// Letters of context used to pick the next letter.
+const ORDER: usize = 2;
+const MIN_LENGTH: usize = 3;
+/// Longest name generated or typed in by the player.
+pub const MAX_LENGTH: usize = 16;
+const START: char = '^';
+const END: char = '$';
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum NameStyle { Skeleton, Zombie, Wraith, Villager }
+
+impl NameStyle {
+    pub fn all() -> [NameStyle; 4] {
+        [NameStyle::Skeleton, NameStyle::Zombie, NameStyle::Wraith, NameStyle::Villager]
+    }
+
+    pub fn path(&self) -> PathBuf {
+        let name = match self {
+            NameStyle::Skeleton => "skeleton",
+            NameStyle::Zombie => "zombie",
+            NameStyle::Wraith => "wraith",
+            NameStyle::Villager => "villager"
+        };
+        PathBuf::from(NAMES_DIR).join(format!("{}.txt", name))
+    }
+}
+
+/// Markov chain over letters, trained on a list of example names.
+#[derive(Default)]
+pub struct NameChain {
+    examples: Vec<String>,
+    next: BTreeMap<String, Vec<char>>
+}
+
+impl NameChain {
+    /// Builds a chain from one name per line. Empty lines and lines starting with `#` are skipped.
+    pub fn parse(text: &str) -> NameChain {
+        let mut chain = NameChain::default();
+        for line in text.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let name = line.to_lowercase();
+            let letters: Vec<char> = std::iter::repeat_n(START, ORDER).chain(name.chars()).chain(Some(END)).collect();
+            for window in letters.windows(ORDER + 1) {
+                let context: String = window[..ORDER].iter().collect();
+                chain.next.entry(context).or_default().push(window[ORDER]);
+            }
+            chain.examples.push(name);
+        }
+        chain
+    }
+
+    /// A name that sounds like the examples, preferably one that is not among them.
+    pub fn generate<R: Rng>(&self, rng: &mut R) -> Option<String> {
+        for _ in 0..20 {
+            if let Some(name) = self.walk(rng).filter(|name| !self.examples.contains(name)) {
+                return Some(capitalize(&name));
+            }
+        }
+        self.examples.choose(rng).map(|name| capitalize(name))
+    }
+
+    fn walk<R: Rng>(&self, rng: &mut R) -> Option<String> {
+        let mut letters: Vec<char> = std::iter::repeat_n(START, ORDER).collect();
+        loop {
+            let context: String = letters[letters.len() - ORDER..].iter().collect();
+            match *self.next.get(&context)?.choose(rng)? {
+                END => break,
+                c => letters.push(c)
+            }
+            if letters.len() - ORDER > MAX_LENGTH {
+                return None;
+            }
+        }
+        let name: String = letters[ORDER..].iter().collect();
+        if name.chars().count() < MIN_LENGTH { None } else { Some(name) }
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new()
+    }
+}
+
+/// Name chains for every style, loaded from `resources/names/<style>.txt`.
+#[derive(Default)]
+pub struct NameGenerator {
+    chains: BTreeMap<NameStyle, NameChain>
+}
+
+impl NameGenerator {
+    /// Loads every corpus it can. Returns warnings about the ones that could not be read.
+    pub fn load() -> (NameGenerator, Vec<String>) {
+        let mut generator = NameGenerator::default();
+        let mut warnings = vec![];
+        for style in NameStyle::all().iter() {
+            match fs::read_to_string(style.path()) {
+                Ok(text) => { generator.chains.insert(*style, NameChain::parse(&text)); },
+                Err(e) => warnings.push(tr_args("log.file_failed", &[("path", style.path().display().to_string()), ("error", e.to_string())]))
+            }
+        }
+        (generator, warnings)
+    }
+
+    pub fn generate<R: Rng>(&self, style: NameStyle, rng: &mut R) -> Option<String> {
+        self.chains.get(&style)?.generate(rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cp437;
+
+    #[test]
+    fn generated_names_fit() {
+        let (generator, warnings) = NameGenerator::load();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+
+        let mut rng = rand::thread_rng();
+        for style in NameStyle::all().iter() {
+            for _ in 0..50 {
+                let name = generator.generate(*style, &mut rng).unwrap();
+                assert!(name.chars().count() >= MIN_LENGTH && name.chars().count() <= MAX_LENGTH, "{}", name);
+                assert!(name.chars().next().unwrap().is_uppercase(), "{}", name);
+                assert!(name.chars().all(|c| cp437::to_glyph(c).is_some()), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn single_example_is_reproduced() {
+        let chain = NameChain::parse("# comment\n\nGregor\n");
+        assert_eq!(chain.generate(&mut rand::thread_rng()), Some("Gregor".to_string()));
+        assert_eq!(NameChain::default().generate(&mut rand::thread_rng()), None);
+    }
+}
@@ -0,0 +1,210 @@
+//! Drawing backends. The GUI works in cells and leaves pixels to a `Renderer`.
+
+use sdl2::rect::Rect;
+use std::path::Path;
+
+use super::cp437;
+use super::locale::tr;
+use super::widgets::{Area, Color, Symbol};
+
+pub trait Renderer {
+    /// Drawing surface size in cells.
+    fn size(&self) -> (u32, u32);
+
+    /// Pixels per cell. Rectangles given to `fill_rects`, `outline` and `image` are in pixels.
+    fn cell_size(&self) -> (u32, u32);
+
+    fn clear(&mut self, color: Color);
+
+    /// Draws a symbol at a cell, over a background when one is given.
+    fn draw_glyph(&mut self, x: i32, y: i32, symbol: Symbol, color: Color, background: Option<Color>);
+
+    fn fill_rects(&mut self, rects: &[Rect], color: Color);
+
+    fn outline(&mut self, rect: Rect, color: Color);
+
+    /// Darkens everything drawn so far, behind dialogs.
+    fn shade(&mut self, alpha: u8);
+
+    /// Draws a small picture scaled into `rect`. `pixels` is only asked for when `key`
+    /// differs from what was last drawn under the same id.
+    fn image(&mut self, id: &'static str, key: u64, size: (u32, u32), pixels: &dyn Fn() -> Vec<Color>, rect: Rect);
+
+    /// Draws cells of `area` through `draw`, which backends may keep and reuse while `key` stays the same.
+    fn layer(&mut self, id: &'static str, key: u64, area: Area, draw: &mut dyn FnMut(&mut dyn Renderer));
+
+    fn present(&mut self);
+
+    /// Saves what has been drawn since the last `clear` as a PNG image.
+    fn save_screen(&mut self, path: &Path) -> Result<(), String>;
+
+    /// Draws cells of `area` offscreen through `draw`, however large the area is, and saves them as a PNG image.
+    fn export(&mut self, area: Area, path: &Path, draw: &mut dyn FnMut(&mut dyn Renderer)) -> Result<(), String>;
+
+    /// Draw calls issued since the last call.
+    fn take_draw_calls(&mut self) -> u32;
+}
+
+/// Catalog id of the error of backends that keep characters rather than pixels.
+pub const NO_IMAGES: &str = "log.no_images";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryCell {
+    pub c: char,
+    pub color: Color,
+    pub background: Color
+}
+
+/// Keeps a grid of characters instead of pixels, so tests can check what would be on screen.
+#[derive(Clone)]
+pub struct MemoryRenderer {
+    width: u32,
+    height: u32,
+    cells: Vec<MemoryCell>,
+    draw_calls: u32
+}
+
+impl MemoryRenderer {
+    pub fn new(width: u32, height: u32) -> MemoryRenderer {
+        let blank = MemoryCell { c: ' ', color: (0, 0, 0), background: (0, 0, 0) };
+        MemoryRenderer { width, height, cells: vec![blank; (width * height) as usize], draw_calls: 0 }
+    }
+
+    pub fn cell(&self, x: u32, y: u32) -> Option<MemoryCell> {
+        if x < self.width && y < self.height { Some(self.cells[(y * self.width + x) as usize]) } else { None }
+    }
+
+    /// The screen as lines of text, without trailing blanks.
+    #[cfg(test)]
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for row in self.cells.chunks(self.width.max(1) as usize) {
+            let line: String = row.iter().map(|cell| cell.c).collect();
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        text
+    }
+
+    fn cell_mut(&mut self, x: i32, y: i32) -> Option<&mut MemoryCell> {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return None;
+        }
+        self.cells.get_mut((y as u32 * self.width + x as u32) as usize)
+    }
+
+    fn cells_in(rect: Rect) -> impl Iterator<Item = (i32, i32)> {
+        (rect.y()..rect.y() + rect.height() as i32).flat_map(move |y| (rect.x()..rect.x() + rect.width() as i32).map(move |x| (x, y)))
+    }
+}
+
+impl Renderer for MemoryRenderer {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn cell_size(&self) -> (u32, u32) {
+        (1, 1)
+    }
+
+    fn clear(&mut self, color: Color) {
+        for cell in self.cells.iter_mut() {
+            *cell = MemoryCell { c: ' ', color, background: color };
+        }
+        self.draw_calls += 1;
+    }
+
+    fn draw_glyph(&mut self, x: i32, y: i32, symbol: Symbol, color: Color, background: Option<Color>) {
+        if let Some(cell) = self.cell_mut(x, y) {
+            cell.c = match symbol {
+                Symbol::Char(c) => c,
+                Symbol::Glyph(glyph) => cp437::to_char(glyph)
+            };
+            cell.color = color;
+            if let Some(background) = background {
+                cell.background = background;
+            }
+        }
+        self.draw_calls += 1;
+    }
+
+    fn fill_rects(&mut self, rects: &[Rect], color: Color) {
+        for (x, y) in rects.iter().flat_map(|rect| MemoryRenderer::cells_in(*rect)) {
+            if let Some(cell) = self.cell_mut(x, y) {
+                *cell = MemoryCell { c: ' ', color, background: color };
+            }
+        }
+        self.draw_calls += 1;
+    }
+
+    fn outline(&mut self, _rect: Rect, _color: Color) {
+        self.draw_calls += 1;
+    }
+
+    fn shade(&mut self, alpha: u8) {
+        let dim = |c: u8| (c as u32 * (255 - alpha as u32) / 255) as u8;
+        for cell in self.cells.iter_mut() {
+            cell.color = (dim(cell.color.0), dim(cell.color.1), dim(cell.color.2));
+            cell.background = (dim(cell.background.0), dim(cell.background.1), dim(cell.background.2));
+        }
+        self.draw_calls += 1;
+    }
+
+    fn image(&mut self, _id: &'static str, _key: u64, size: (u32, u32), pixels: &dyn Fn() -> Vec<Color>, rect: Rect) {
+        let pixels = pixels();
+        for (x, y) in MemoryRenderer::cells_in(rect) {
+            let px = (x - rect.x()) as u32 * size.0 / rect.width();
+            let py = (y - rect.y()) as u32 * size.1 / rect.height();
+            if let (Some(color), Some(cell)) = (pixels.get((py * size.0 + px) as usize).copied(), self.cell_mut(x, y)) {
+                *cell = MemoryCell { c: ' ', color, background: color };
+            }
+        }
+        self.draw_calls += 1;
+    }
+
+    fn layer(&mut self, _id: &'static str, _key: u64, _area: Area, draw: &mut dyn FnMut(&mut dyn Renderer)) {
+        draw(self);
+    }
+
+    fn present(&mut self) {}
+
+    fn save_screen(&mut self, _path: &Path) -> Result<(), String> {
+        Err(tr(NO_IMAGES))
+    }
+
+    fn export(&mut self, _area: Area, _path: &Path, _draw: &mut dyn FnMut(&mut dyn Renderer)) -> Result<(), String> {
+        Err(tr(NO_IMAGES))
+    }
+
+    fn take_draw_calls(&mut self) -> u32 {
+        std::mem::take(&mut self.draw_calls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_renderer_keeps_text_and_colors() {
+        let mut renderer = MemoryRenderer::new(6, 2);
+        renderer.clear((1, 1, 1));
+        renderer.fill_rects(&[Rect::new(0, 1, 3, 1)], (9, 9, 9));
+        renderer.draw_glyph(1, 0, Symbol::Char('A'), (255, 0, 0), None);
+        renderer.draw_glyph(2, 1, Symbol::Glyph(1), (0, 255, 0), Some((5, 5, 5)));
+        renderer.draw_glyph(7, 0, Symbol::Char('X'), (0, 0, 0), None);
+
+        assert_eq!(renderer.text(), " A\n  ☺\n");
+        assert_eq!(renderer.cell(1, 0), Some(MemoryCell { c: 'A', color: (255, 0, 0), background: (1, 1, 1) }));
+        assert_eq!(renderer.cell(1, 1).unwrap().background, (9, 9, 9));
+        assert_eq!(renderer.cell(2, 1).unwrap().background, (5, 5, 5));
+        assert_eq!(renderer.take_draw_calls(), 5);
+    }
+
+    #[test]
+    fn saving_images_explains_why_it_cannot() {
+        crate::locale::set_language("en").unwrap();
+        let mut renderer = MemoryRenderer::new(2, 2);
+        assert_eq!(renderer.save_screen(Path::new("screen.png")), Err("images can only be saved from the game window".to_string()));
+    }
+}
@@ -0,0 +1,115 @@
+//! How a game is won or lost. The rules are loaded from `resources/rules.toml` and checked every tick
+//! by the `RulesSystem`; the first one that holds ends the game.
+
+use serde::{Serialize, Deserialize};
+use std::fs;
+
+use super::locale::{tr, tr_args, tr_count};
+
+const RULES_PATH: &str = "./resources/rules.toml";
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome { Victory, Defeat }
+
+impl Outcome {
+    pub fn get_text(&self) -> String {
+        match self {
+            Outcome::Victory => tr("outcome.victory"),
+            Outcome::Defeat => tr("outcome.defeat")
+        }
+    }
+
+    pub fn get_color(&self) -> (u8, u8, u8) {
+        match self {
+            Outcome::Victory => (170, 60, 220),
+            Outcome::Defeat => (200, 50, 50)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(tag = "condition", rename_all = "snake_case")]
+pub enum Condition {
+    /// Every undead unit is destroyed.
+    NoUnits,
+    /// The necromancer is slain.
+    NecromancerSlain,
+    /// Every villager is slain.
+    NoVillagers,
+    /// At least this much of the map is corrupted.
+    Corruption { percent: u32 },
+    /// This many whole days of 24 hours have passed since the start, so 1 holds at 08:00 on day 2.
+    Survive { days: u64 }
+}
+
+impl Condition {
+    /// What happened, for the game over screen.
+    pub fn get_description(&self) -> String {
+        match self {
+            Condition::NoUnits => tr("rule.no_units"),
+            Condition::NecromancerSlain => tr("rule.necromancer_slain"),
+            Condition::NoVillagers => tr("rule.no_villagers"),
+            Condition::Corruption { percent } => tr_args("rule.corruption", &[("percent", percent.to_string())]),
+            Condition::Survive { days } => tr_count("rule.survive", *days, &[])
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Rule {
+    pub outcome: Outcome,
+    #[serde(flatten)]
+    pub condition: Condition
+}
+
+#[derive(Deserialize, Default)]
+pub struct Rules {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>
+}
+
+impl Rules {
+    /// Loads the rules. Returns a warning when they could not be read, and the game never ends then.
+    pub fn load() -> (Rules, Vec<String>) {
+        let rules = fs::read_to_string(RULES_PATH).map_err(|e| e.to_string()).and_then(|text| Rules::parse(&text));
+        match rules {
+            Ok(rules) => (rules, vec![]),
+            Err(e) => (Rules::default(), vec![tr_args("log.file_failed", &[("path", RULES_PATH.to_string()), ("error", e.to_string())])])
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Rules, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+}
+
+/// How the game ended, once it has.
+#[derive(Default)]
+pub struct GameResult {
+    pub ended: Option<Rule>
+}
+
+/// Counts kept over a game for the game over screen.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct GameStats {
+    pub undead_lost: u32,
+    pub villagers_slain: u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_rules_load() {
+        let (rules, warnings) = Rules::load();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert!(rules.rules.iter().any(|rule| rule.outcome == Outcome::Victory));
+        assert!(rules.rules.iter().any(|rule| rule.outcome == Outcome::Defeat));
+
+        let rules = Rules::parse("[[rule]]\noutcome = \"victory\"\ncondition = \"corruption\"\npercent = 70").unwrap();
+        assert_eq!(rules.rules, vec![Rule { outcome: Outcome::Victory, condition: Condition::Corruption { percent: 70 } }]);
+        assert!(Rules::parse("[[rule]]\noutcome = \"victory\"\ncondition = \"corruption\"").is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use specs::prelude::*;
+
+use super::{Living, Necromancer, Unit, Villager, map::Map};
+use super::clock::{GameClock, MINUTES_PER_DAY};
+use super::gamelog::{Gamelog, LogCategory, LogEntry};
+use super::locale::tr_args;
+use super::rules::{Condition, GameResult, Rules};
+
+/// Ends the game as soon as one of the rules holds.
+pub struct RulesSystem {}
+
+impl<'a> System<'a> for RulesSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Unit>,
+        ReadStorage<'a, Villager>,
+        ReadStorage<'a, Necromancer>,
+        ReadStorage<'a, Living>,
+        ReadExpect<'a, Map>,
+        Read<'a, GameClock>,
+        Read<'a, Rules>,
+        Write<'a, GameResult>,
+        Write<'a, Gamelog>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, units, villagers, necromancers, livings, map, clock, rules, mut result, mut log) = data;
+        if result.ended.is_some() {
+            return;
+        }
+
+        // The dead are only removed at the end of the tick
+        let alive = |entity: Entity| livings.get(entity).is_none_or(|living| living.health > 0);
+        let unit_count = (&entities, &units).join().filter(|(entity, _)| alive(*entity)).count();
+        let villager_count = (&entities, &villagers).join().filter(|(entity, _)| alive(*entity)).count();
+        let necromancer_alive = (&entities, &necromancers).join().any(|(entity, _)| alive(entity));
+
+        for rule in rules.rules.iter() {
+            let holds = match rule.condition {
+                Condition::NoUnits => unit_count == 0,
+                Condition::NecromancerSlain => !necromancer_alive,
+                Condition::NoVillagers => villager_count == 0,
+                Condition::Corruption { percent } => map.corrupted_share() * 100. >= percent as f32,
+                Condition::Survive { days } => clock.tick >= days * MINUTES_PER_DAY
+            };
+            if holds {
+                let text = tr_args("log.game_over", &[("outcome", rule.outcome.get_text()), ("reason", rule.condition.get_description())]);
+                log.add(LogEntry::new(clock.tick, LogCategory::System, text));
+                result.ended = Some(rule.clone());
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dispatcher, Mission, Position, Profession, UndeadType};
+    use crate::map::CORRUPTED;
+    use crate::rules::{Outcome, Rule};
+
+    fn world(rules: Vec<Rule>) -> World {
+        crate::locale::set_language("en").unwrap();
+        let mut world = World::new();
+        dispatcher::register_components(&mut world);
+        world.insert(Map::new(4, 4));
+        world.insert(GameClock::default());
+        world.insert(GameResult::default());
+        world.insert(Gamelog::default());
+        world.insert(Rules { rules });
+        world
+    }
+
+    fn ended(world: &World) -> Option<Condition> {
+        world.fetch::<GameResult>().ended.as_ref().map(|rule| rule.condition.clone())
+    }
+
+    #[test]
+    fn first_rule_that_holds_ends_the_game() {
+        let mut world = world(vec![
+            Rule { outcome: Outcome::Defeat, condition: Condition::NecromancerSlain },
+            Rule { outcome: Outcome::Defeat, condition: Condition::NoUnits },
+            Rule { outcome: Outcome::Victory, condition: Condition::NoVillagers },
+            Rule { outcome: Outcome::Victory, condition: Condition::Survive { days: 2 } }
+        ]);
+        world.create_entity().with(Necromancer::new(10, vec![])).with(Living::new(10)).build();
+        let unit = world.create_entity().with(Position{ x: 0, y: 0 }).with(Unit{ mission: Mission::Stay, kind: UndeadType::Zombie }).with(Living::new(10)).build();
+        world.create_entity().with(Villager{ given_name: "Hild".to_string(), profession: Profession::Priest }).build();
+
+        RulesSystem {}.run_now(&world);
+        assert!(world.fetch::<GameResult>().ended.is_none());
+
+        world.write_resource::<GameClock>().tick = 2 * 24 * 60;
+        world.write_storage::<Living>().get_mut(unit).unwrap().health = 0;
+        RulesSystem {}.run_now(&world);
+        assert_eq!(world.fetch::<GameResult>().ended.as_ref().map(|rule| rule.outcome), Some(Outcome::Defeat));
+        assert_eq!(world.fetch::<Gamelog>().visible().count(), 1);
+    }
+
+    #[test]
+    fn survival_counts_whole_days_from_the_start() {
+        let world = world(vec![Rule { outcome: Outcome::Victory, condition: Condition::Survive { days: 2 } }]);
+
+        // Day 3 begins at midnight, 16 hours before two whole days are over
+        world.write_resource::<GameClock>().tick = 2 * MINUTES_PER_DAY - 1;
+        assert_eq!(world.fetch::<GameClock>().day(), 3);
+        RulesSystem {}.run_now(&world);
+        assert_eq!(ended(&world), None);
+
+        world.write_resource::<GameClock>().tick = 2 * MINUTES_PER_DAY;
+        RulesSystem {}.run_now(&world);
+        assert_eq!(ended(&world), Some(Condition::Survive { days: 2 }));
+    }
+
+    #[test]
+    fn corruption_needs_the_whole_share() {
+        let world = world(vec![Rule { outcome: Outcome::Victory, condition: Condition::Corruption { percent: 70 } }]);
+
+        // 11 of 16 tiles is just under 70%, and tiles below CORRUPTED do not count
+        world.write_resource::<Map>().corruption[..11].fill(CORRUPTED);
+        world.write_resource::<Map>().corruption[11] = CORRUPTED - 1;
+        RulesSystem {}.run_now(&world);
+        assert_eq!(ended(&world), None);
+
+        world.write_resource::<Map>().corruption[11] = CORRUPTED;
+        RulesSystem {}.run_now(&world);
+        assert_eq!(ended(&world), Some(Condition::Corruption { percent: 70 }));
+    }
+}
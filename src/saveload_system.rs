@@ -0,0 +1,248 @@
+use specs::prelude::*;
+use specs::saveload::{SimpleMarker, SimpleMarkerAllocator, SerializeComponents, DeserializeComponents, MarkedBuilder};
+use std::convert::Infallible;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::components::*;
+use super::{dispatcher, map::Map, clock::GameClock, rules::GameStats};
+
+/// Bumped whenever what is saved changes, since older saves cannot be read then.
+const SAVE_VERSION: u32 = 1;
+
+macro_rules! serialize_individually {
+    ($ecs:expr, $ser:expr, $data:expr, $( $type:ty),*) => {
+        $(
+        SerializeComponents::<Infallible, SimpleMarker<SerializeMe>>::serialize(
+            &( $ecs.read_storage::<$type>(), ),
+            &$data.0,
+            &$data.1,
+            &mut $ser,
+        )?;
+        )*
+    };
+}
+
+macro_rules! deserialize_individually {
+    ($ecs:expr, $de:expr, $data:expr, $( $type:ty),*) => {
+        $(
+        DeserializeComponents::<Infallible, _>::deserialize(
+            &mut ( &mut $ecs.write_storage::<$type>(), ),
+            &$data.0,
+            &mut $data.1,
+            &mut $data.2,
+            &mut $de,
+        )?;
+        )*
+    };
+}
+
+/// Where the game is saved, with the player's other data rather than wherever the game was started from.
+pub fn save_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("necronix").join("savegame.json"))
+}
+
+pub fn save_exists() -> bool {
+    save_path().is_some_and(|path| path.exists())
+}
+
+pub fn save_game(ecs: &mut World) -> io::Result<()> {
+    let path = save_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    save_to(ecs, &path)
+}
+
+/// Writes the save next to `path` and only then moves it over the old one, so a failed save
+/// leaves the last good one in place.
+fn save_to(ecs: &mut World, path: &Path) -> io::Result<()> {
+    let temp = path.with_extension("json.tmp");
+    let result = File::create(&temp).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        write_save(ecs, &mut writer)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()
+    });
+    match result {
+        Ok(()) => fs::rename(&temp, path),
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
+
+fn write_save<W: io::Write>(ecs: &mut World, writer: W) -> io::Result<()> {
+    let helper = {
+        let map = (*ecs.fetch::<Map>()).clone();
+        let tick = ecs.fetch::<GameClock>().tick;
+        let stats = (*ecs.fetch::<GameStats>()).clone();
+        ecs.create_entity()
+           .with(SerializationHelper{ version: SAVE_VERSION, map, tick, stats })
+           .marked::<SimpleMarker<SerializeMe>>()
+           .build()
+    };
+
+    let result: io::Result<()> = (|| {
+        let mut serializer = serde_json::Serializer::new(writer);
+        let data = (ecs.entities(), ecs.read_storage::<SimpleMarker<SerializeMe>>());
+        // The helper goes first, so the version is known before anything else is read
+        serialize_individually!(ecs, serializer, data, SerializationHelper, Renderable, Position, Name, Unit, MissionQueue, BlocksTile, Physical, Living,
+                               Faction, Material, Choppable, Building, Inventory, StatusEffects, Kills, Villager, LightSource, Necromancer, Corpse, Integrity);
+        Ok(())
+    })();
+
+    ecs.delete_entity(helper).expect("Unable to delete serialization helper");
+    ecs.maintain();
+    result
+}
+
+/// Replaces the world with the saved game. The save is read into a scratch world first,
+/// so the running game is left alone when it cannot be read.
+pub fn load_game(ecs: &mut World) -> io::Result<()> {
+    let path = save_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+    let data = fs::read_to_string(path)?;
+    load_from(ecs, &data)
+}
+
+fn load_from(ecs: &mut World, data: &str) -> io::Result<()> {
+    let mut scratch = World::new();
+    dispatcher::register_components(&mut scratch);
+    read_save(&mut scratch, data)?;
+
+    ecs.delete_all();
+    ecs.maintain();
+    let result = read_save(ecs, data);
+
+    let mut helper_entity = None;
+    {
+        let entities = ecs.entities();
+        let helpers = ecs.read_storage::<SerializationHelper>();
+        for (entity, helper) in (&entities, &helpers).join() {
+            *ecs.write_resource::<Map>() = helper.map.clone();
+            ecs.write_resource::<GameClock>().tick = helper.tick;
+            *ecs.write_resource::<GameStats>() = helper.stats.clone();
+            helper_entity = Some(entity);
+        }
+    }
+
+    if let Some(entity) = helper_entity {
+        ecs.delete_entity(entity).expect("Unable to delete serialization helper");
+    }
+    ecs.maintain();
+    result
+}
+
+fn read_save(ecs: &mut World, data: &str) -> io::Result<()> {
+    let mut de = serde_json::Deserializer::from_str(data);
+    let mut d = (&mut ecs.entities(), &mut ecs.write_storage::<SimpleMarker<SerializeMe>>(), &mut ecs.write_resource::<SimpleMarkerAllocator<SerializeMe>>());
+
+    let helper: io::Result<()> = (|| {
+        deserialize_individually!(ecs, de, d, SerializationHelper);
+        Ok(())
+    })();
+    let version = ecs.read_storage::<SerializationHelper>().join().next().map(|helper| helper.version);
+    if helper.is_err() || version != Some(SAVE_VERSION) {
+        let found = version.map_or_else(|| "an older version".to_string(), |version| format!("version {}", version));
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("the save is from {}, this game reads version {}", found, SAVE_VERSION)));
+    }
+
+    deserialize_individually!(ecs, de, d, Renderable, Position, Name, Unit, MissionQueue, BlocksTile, Physical, Living, Faction,
+                             Material, Choppable, Building, Inventory, StatusEffects, Kills, Villager, LightSource, Necromancer, Corpse, Integrity);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_world() -> World {
+        let mut world = World::new();
+        dispatcher::register_components(&mut world);
+        world.insert(Map::new(10, 10));
+        world.insert(GameClock { tick: 42 });
+        world.insert(GameStats { undead_lost: 1, villagers_slain: 3 });
+        world.create_entity()
+             .with(Position{ x: 3, y: 4 })
+             .with(Name{ name: "Morrow".to_string() })
+             .with(Unit{ mission: Mission::Stay, kind: UndeadType::Skeleton })
+             .with(Integrity{ integrity: 120, ..Integrity::new(UndeadType::Skeleton.max_integrity()) })
+             .marked::<SimpleMarker<SerializeMe>>()
+             .build();
+        world.create_entity()
+             .with(Necromancer{ mana: 7, ..Necromancer::new(50, vec!["fear".to_string()]) })
+             .marked::<SimpleMarker<SerializeMe>>()
+             .build();
+        world
+    }
+
+    fn save_text(world: &mut World) -> String {
+        let mut bytes = vec![];
+        write_save(world, &mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn saved_games_load_back() {
+        let mut world = test_world();
+        let save = save_text(&mut world);
+
+        let mut loaded = test_world();
+        loaded.write_resource::<GameClock>().tick = 0;
+        loaded.create_entity().with(Name{ name: "Stray".to_string() }).marked::<SimpleMarker<SerializeMe>>().build();
+        load_from(&mut loaded, &save).unwrap();
+
+        assert_eq!(loaded.fetch::<GameClock>().tick, 42);
+        assert_eq!(loaded.fetch::<GameStats>().villagers_slain, 3);
+        let names: Vec<String> = loaded.read_storage::<Name>().join().map(|name| name.name.clone()).collect();
+        assert_eq!(names, vec!["Morrow".to_string()]);
+        let saved = (&loaded.read_storage::<Position>(), &loaded.read_storage::<Integrity>()).join()
+            .map(|(pos, integrity)| (pos.x, pos.y, integrity.integrity)).next();
+        assert_eq!(saved, Some((3, 4, 120)));
+        let necromancer = loaded.read_storage::<Necromancer>().join().next().unwrap().clone();
+        assert_eq!((necromancer.mana, necromancer.spellbook), (7, vec!["fear".to_string()]));
+        assert_eq!(loaded.read_storage::<SerializationHelper>().count(), 0);
+    }
+
+    #[test]
+    fn unreadable_saves_leave_the_game_alone() {
+        let mut world = test_world();
+        let save = save_text(&mut world);
+        let older = save.replacen(&format!("\"version\":{}", SAVE_VERSION), "\"version\":0", 1);
+        assert_ne!(older, save);
+
+        for data in [older.as_str(), "[]", "not a save"] {
+            let mut running = test_world();
+            assert!(load_from(&mut running, data).is_err());
+            assert_eq!(running.read_storage::<Name>().count(), 1);
+            assert_eq!(running.fetch::<GameClock>().tick, 42);
+        }
+        let mut running = test_world();
+        let error = load_from(&mut running, &older).unwrap_err();
+        assert!(error.to_string().contains("version 0"), "{}", error);
+    }
+
+    #[test]
+    fn saves_replace_the_old_one_whole() {
+        let dir = std::env::temp_dir().join(format!("necronix-save-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("savegame.json");
+        fs::write(&path, "an older save").unwrap();
+
+        let mut world = test_world();
+        save_to(&mut world, &path).unwrap();
+        let mut loaded = test_world();
+        load_from(&mut loaded, &fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(loaded.read_storage::<Name>().count(), 1);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        // A save that cannot be written leaves the last one as it was
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::create_dir(path.with_extension("json.tmp")).unwrap();
+        assert!(save_to(&mut world, &path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), saved);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,352 @@
+use sdl2::event::Event;
+use sdl2::image::{LoadTexture, SaveSurface};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Texture, TextureCreator, WindowCanvas};
+use sdl2::surface::Surface;
+use sdl2::video::{FullscreenType, WindowContext};
+use sdl2::{EventPump, Sdl, VideoSubsystem};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::config::{Config, WindowMode};
+use super::frontend::Frontend;
+use super::locale::{tr, tr_args};
+use super::renderer::{Renderer, NO_IMAGES};
+use super::tileset::Tileset;
+use super::widgets::{Area, Color, Symbol};
+
+pub fn load_tileset<'a>(manifest: &str, texture_creator: &'a TextureCreator<WindowContext>) -> Result<(Texture<'a>, Tileset), String> {
+    let glyphs = Tileset::load(manifest).map_err(|e| e.to_string())?;
+    let texture = texture_creator.load_texture(glyphs.image_path())?;
+    Ok((texture, glyphs))
+}
+
+/// Draws cells with glyphs from a tileset texture into the game window.
+pub struct SdlRenderer<'a> {
+    pub canvas: WindowCanvas,
+    pub glyphs: Tileset,
+    tileset: Texture<'a>,
+    textures: &'a TextureCreator<WindowContext>,
+    cell: (u32, u32),
+    /// Layers and images with the key they were drawn for.
+    cache: BTreeMap<&'static str, (Texture<'a>, u64)>,
+    /// Color the tileset is currently tinted with, to skip setting it again.
+    color_mod: Option<Color>,
+    draw_calls: u32
+}
+
+/// The window or a layer texture to draw on. Layers start at `origin`, in pixels.
+struct Target<'c, 't> {
+    canvas: &'c mut WindowCanvas,
+    tileset: &'c mut Texture<'t>,
+    glyphs: &'c Tileset,
+    textures: &'t TextureCreator<WindowContext>,
+    cell: (u32, u32),
+    origin: (i32, i32),
+    color_mod: &'c mut Option<Color>,
+    draw_calls: &'c mut u32
+}
+
+impl<'a> SdlRenderer<'a> {
+    pub fn new(canvas: WindowCanvas, textures: &'a TextureCreator<WindowContext>, tileset: Texture<'a>, glyphs: Tileset, tile_size: u32) -> SdlRenderer<'a> {
+        let mut renderer = SdlRenderer { canvas, glyphs, tileset, textures, cell: (0, 0), cache: BTreeMap::new(), color_mod: None, draw_calls: 0 };
+        renderer.set_tile_size(tile_size);
+        renderer
+    }
+
+    pub fn set_tileset(&mut self, tileset: Texture<'a>, glyphs: Tileset) {
+        self.tileset = tileset;
+        self.glyphs = glyphs;
+        self.color_mod = None;
+        self.set_tile_size(self.cell.1);
+    }
+
+    /// Cells are `tile_size` pixels high and as wide as the glyph proportions allow.
+    pub fn set_tile_size(&mut self, tile_size: u32) {
+        self.cell = ((tile_size * self.glyphs.glyph_width / self.glyphs.glyph_height).max(1), tile_size);
+        self.cache.clear();
+    }
+
+    fn target(&mut self) -> Target<'_, 'a> {
+        Target {
+            canvas: &mut self.canvas, tileset: &mut self.tileset, glyphs: &self.glyphs, textures: self.textures,
+            cell: self.cell, origin: (0, 0), color_mod: &mut self.color_mod, draw_calls: &mut self.draw_calls
+        }
+    }
+
+    fn is_cached(&self, id: &'static str, key: u64, size: (u32, u32)) -> bool {
+        match self.cache.get(id) {
+            Some((texture, cached)) => {
+                let query = texture.query();
+                *cached == key && (query.width, query.height) == size
+            },
+            None => false
+        }
+    }
+}
+
+impl<'c, 't> Target<'c, 't> {
+    fn offset(&self, rect: Rect) -> Rect {
+        Rect::new(rect.x() - self.origin.0, rect.y() - self.origin.1, rect.width(), rect.height())
+    }
+
+    fn cell_rect(&self, x: i32, y: i32) -> Rect {
+        self.offset(Rect::new(x * self.cell.0 as i32, y * self.cell.1 as i32, self.cell.0, self.cell.1))
+    }
+}
+
+fn rgb_bytes(pixels: &[Color]) -> Vec<u8> {
+    pixels.iter().flat_map(|(r, g, b)| [*r, *g, *b]).collect()
+}
+
+fn save_png(mut pixels: Vec<u8>, (width, height): (u32, u32), path: &Path) -> Result<(), String> {
+    Surface::from_data(&mut pixels, width, height, width * 3, PixelFormatEnum::RGB24)?.save(path)
+}
+
+impl<'c, 't> Renderer for Target<'c, 't> {
+    fn size(&self) -> (u32, u32) {
+        let (width, height) = self.canvas.output_size().unwrap();
+        (width / self.cell.0, height / self.cell.1)
+    }
+
+    fn cell_size(&self) -> (u32, u32) {
+        self.cell
+    }
+
+    fn clear(&mut self, color: Color) {
+        self.canvas.set_draw_color(color);
+        self.canvas.clear();
+        *self.draw_calls += 1;
+    }
+
+    fn draw_glyph(&mut self, x: i32, y: i32, symbol: Symbol, color: Color, background: Option<Color>) {
+        let rect = self.cell_rect(x, y);
+        if let Some(background) = background {
+            self.canvas.set_draw_color(background);
+            self.canvas.fill_rect(rect).unwrap();
+            *self.draw_calls += 1;
+        }
+        if *self.color_mod != Some(color) {
+            self.tileset.set_color_mod(color.0, color.1, color.2);
+            *self.color_mod = Some(color);
+        }
+        let glyph = match symbol {
+            Symbol::Char(c) => self.glyphs.glyph(c),
+            Symbol::Glyph(glyph) => glyph
+        };
+        self.canvas.copy(self.tileset, self.glyphs.tile_rect(glyph), rect).unwrap();
+        *self.draw_calls += 1;
+    }
+
+    fn fill_rects(&mut self, rects: &[Rect], color: Color) {
+        let rects: Vec<Rect> = rects.iter().map(|rect| self.offset(*rect)).collect();
+        self.canvas.set_draw_color(color);
+        self.canvas.fill_rects(&rects).unwrap();
+        *self.draw_calls += 1;
+    }
+
+    fn outline(&mut self, rect: Rect, color: Color) {
+        self.canvas.set_draw_color(color);
+        self.canvas.draw_rect(self.offset(rect)).unwrap();
+        *self.draw_calls += 1;
+    }
+
+    fn shade(&mut self, alpha: u8) {
+        let (width, height) = self.canvas.output_size().unwrap();
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color((0, 0, 0, alpha));
+        self.canvas.fill_rect(Rect::new(0, 0, width, height)).unwrap();
+        self.canvas.set_blend_mode(BlendMode::None);
+        *self.draw_calls += 1;
+    }
+
+    fn image(&mut self, _id: &'static str, _key: u64, size: (u32, u32), pixels: &dyn Fn() -> Vec<Color>, rect: Rect) {
+        if size.0 == 0 || size.1 == 0 {
+            return;
+        }
+        let mut texture = self.textures.create_texture_streaming(PixelFormatEnum::RGB24, size.0, size.1).unwrap();
+        texture.update(None, &rgb_bytes(&pixels()), size.0 as usize * 3).unwrap();
+        self.canvas.copy(&texture, None, self.offset(rect)).unwrap();
+        *self.draw_calls += 1;
+    }
+
+    fn layer(&mut self, _id: &'static str, _key: u64, _area: Area, draw: &mut dyn FnMut(&mut dyn Renderer)) {
+        draw(self);
+    }
+
+    fn present(&mut self) {
+        self.canvas.present();
+    }
+
+    fn save_screen(&mut self, path: &Path) -> Result<(), String> {
+        save_png(self.canvas.read_pixels(None, PixelFormatEnum::RGB24)?, self.canvas.output_size()?, path)
+    }
+
+    /// Layers are drawn inside a texture already, which has no room for another one.
+    fn export(&mut self, _area: Area, _path: &Path, _draw: &mut dyn FnMut(&mut dyn Renderer)) -> Result<(), String> {
+        Err(tr(NO_IMAGES))
+    }
+
+    fn take_draw_calls(&mut self) -> u32 {
+        std::mem::take(self.draw_calls)
+    }
+}
+
+impl<'a> Renderer for SdlRenderer<'a> {
+    fn size(&self) -> (u32, u32) {
+        let (width, height) = self.canvas.output_size().unwrap();
+        (width / self.cell.0, height / self.cell.1)
+    }
+
+    fn cell_size(&self) -> (u32, u32) {
+        self.cell
+    }
+
+    fn clear(&mut self, color: Color) {
+        self.target().clear(color);
+    }
+
+    fn draw_glyph(&mut self, x: i32, y: i32, symbol: Symbol, color: Color, background: Option<Color>) {
+        self.target().draw_glyph(x, y, symbol, color, background);
+    }
+
+    fn fill_rects(&mut self, rects: &[Rect], color: Color) {
+        self.target().fill_rects(rects, color);
+    }
+
+    fn outline(&mut self, rect: Rect, color: Color) {
+        self.target().outline(rect, color);
+    }
+
+    fn shade(&mut self, alpha: u8) {
+        self.target().shade(alpha);
+    }
+
+    /// Uploads the pixels to a texture only when the key changes.
+    fn image(&mut self, id: &'static str, key: u64, size: (u32, u32), pixels: &dyn Fn() -> Vec<Color>, rect: Rect) {
+        if size.0 == 0 || size.1 == 0 {
+            return;
+        }
+        if !self.is_cached(id, key, size) {
+            let mut texture = self.textures.create_texture_streaming(PixelFormatEnum::RGB24, size.0, size.1).unwrap();
+            texture.update(None, &rgb_bytes(&pixels()), size.0 as usize * 3).unwrap();
+            self.cache.insert(id, (texture, key));
+        }
+        self.canvas.copy(&self.cache[id].0, None, rect).unwrap();
+        self.draw_calls += 1;
+    }
+
+    /// Draws the layer into a texture only when the key changes, and copies that texture otherwise.
+    fn layer(&mut self, id: &'static str, key: u64, area: Area, draw: &mut dyn FnMut(&mut dyn Renderer)) {
+        let size = (area.width * self.cell.0, area.height * self.cell.1);
+        if size.0 == 0 || size.1 == 0 {
+            return;
+        }
+        if !self.is_cached(id, key, size) {
+            let mut texture = self.textures.create_texture_target(None, size.0, size.1).unwrap();
+            let origin = (area.x * self.cell.0 as i32, area.y * self.cell.1 as i32);
+            let (tileset, glyphs, textures, cell) = (&mut self.tileset, &self.glyphs, self.textures, self.cell);
+            let (color_mod, draw_calls) = (&mut self.color_mod, &mut self.draw_calls);
+            self.canvas.with_texture_canvas(&mut texture, |canvas| {
+                draw(&mut Target { canvas, tileset, glyphs, textures, cell, origin, color_mod, draw_calls });
+            }).unwrap();
+            self.cache.insert(id, (texture, key));
+        }
+        let rect = Rect::new(area.x * self.cell.0 as i32, area.y * self.cell.1 as i32, size.0, size.1);
+        self.canvas.copy(&self.cache[id].0, None, rect).unwrap();
+        self.draw_calls += 1;
+    }
+
+    fn present(&mut self) {
+        self.canvas.present();
+    }
+
+    fn save_screen(&mut self, path: &Path) -> Result<(), String> {
+        self.target().save_screen(path)
+    }
+
+    fn export(&mut self, area: Area, path: &Path, draw: &mut dyn FnMut(&mut dyn Renderer)) -> Result<(), String> {
+        let size = (area.width * self.cell.0, area.height * self.cell.1);
+        let mut texture = self.textures.create_texture_target(None, size.0, size.1).map_err(|e| e.to_string())?;
+        let origin = (area.x * self.cell.0 as i32, area.y * self.cell.1 as i32);
+        let (tileset, glyphs, textures, cell) = (&mut self.tileset, &self.glyphs, self.textures, self.cell);
+        let (color_mod, draw_calls) = (&mut self.color_mod, &mut self.draw_calls);
+        let mut pixels = Ok(vec![]);
+        self.canvas.with_texture_canvas(&mut texture, |canvas| {
+            draw(&mut Target { canvas, tileset, glyphs, textures, cell, origin, color_mod, draw_calls });
+            pixels = canvas.read_pixels(None, PixelFormatEnum::RGB24);
+        }).map_err(|e| e.to_string())?;
+        save_png(pixels?, size, path)
+    }
+
+    fn take_draw_calls(&mut self) -> u32 {
+        std::mem::take(&mut self.draw_calls)
+    }
+}
+
+/// The game window, with input from the SDL event queue.
+pub struct SdlFrontend<'a> {
+    ctx: Sdl,
+    video: VideoSubsystem,
+    events: EventPump,
+    textures: &'a TextureCreator<WindowContext>
+}
+
+impl<'a> SdlFrontend<'a> {
+    pub fn new(ctx: Sdl, video: VideoSubsystem, textures: &'a TextureCreator<WindowContext>) -> SdlFrontend<'a> {
+        let events = ctx.event_pump().unwrap();
+        // SDL starts with text input on; it is only wanted while typing a name
+        video.text_input().stop();
+        SdlFrontend { ctx, video, events, textures }
+    }
+}
+
+impl<'a> Frontend for SdlFrontend<'a> {
+    type Renderer = SdlRenderer<'a>;
+
+    fn poll_events(&mut self, _renderer: &mut SdlRenderer<'a>) -> Vec<Event> {
+        self.events.poll_iter().collect()
+    }
+
+    fn set_text_input(&mut self, enabled: bool) {
+        if enabled { self.video.text_input().start() } else { self.video.text_input().stop() }
+    }
+
+    fn show_cursor(&mut self, shown: bool) {
+        self.ctx.mouse().show_cursor(shown);
+    }
+
+    fn configure(&mut self, config: &Config, renderer: &mut SdlRenderer<'a>) -> Vec<String> {
+        let mut errors = vec![];
+
+        let window = renderer.canvas.window_mut();
+        let fullscreen = match config.window_mode {
+            WindowMode::FullscreenDesktop => FullscreenType::Desktop,
+            WindowMode::Fullscreen => FullscreenType::True,
+            WindowMode::Windowed => FullscreenType::Off
+        };
+        if window.fullscreen_state() != fullscreen {
+            if let Err(e) = window.set_fullscreen(fullscreen) {
+                errors.push(tr_args("log.window_mode_failed", &[("error", e)]));
+            }
+        }
+
+        match load_tileset(&config.tileset, self.textures) {
+            Ok((texture, glyphs)) => renderer.set_tileset(texture, glyphs),
+            Err(e) => errors.push(tr_args("log.tileset_failed", &[("tileset", config.tileset.clone()), ("error", e)]))
+        }
+
+        renderer.set_tile_size(config.tile_size);
+        errors
+    }
+
+    fn tileset_name(&self, renderer: &SdlRenderer<'a>) -> Option<String> {
+        Some(renderer.glyphs.name.clone())
+    }
+
+    /// The window runs until the player quits.
+    fn finished(&mut self, _renderer: &mut SdlRenderer<'a>) -> Option<Result<(), String>> {
+        None
+    }
+}
@@ -0,0 +1,228 @@
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+
+use super::{BlocksTile, Corpse, Faction, FactionType, Integrity, Inventory, Kills, Living, Mission, MissionQueue, Name, Necromancer,
+            Physical, Position, RenderLayer, Renderable, SerializeMe, StatusEffect, StatusEffects, UndeadType, Unit, Villager, WantsToCast};
+use super::clock::GameClock;
+use super::effects::{EffectRequest, EffectRequests};
+use super::gamelog::{Gamelog, LogCategory, LogEntry, Severity};
+use super::locale::{tr, tr_args};
+use super::map::Map;
+use super::names::NameGenerator;
+use super::spells::{Spell, SpellEffect, Spells};
+
+/// Undead called up by spells start out in this color.
+const RAISED_COLOR: (u8, u8, u8) = (120, 140, 110);
+
+/// Carries out the spells cast since the last tick. Mana is only spent when the spell finds something to work on.
+pub struct SpellSystem {}
+
+impl<'a> System<'a> for SpellSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, WantsToCast>,
+        WriteStorage<'a, Necromancer>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, Living>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, Corpse>,
+        ReadStorage<'a, Villager>,
+        ReadStorage<'a, BlocksTile>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, StatusEffects>,
+        WriteStorage<'a, Kills>,
+        ReadExpect<'a, Map>,
+        Read<'a, GameClock>,
+        Read<'a, Spells>,
+        Read<'a, NameGenerator>,
+        Read<'a, LazyUpdate>,
+        Write<'a, Gamelog>,
+        Write<'a, EffectRequests>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, mut wants_to_cast, mut necromancers, positions, mut livings, factions, corpses, villagers, blockers, names,
+             mut statuses, mut kills, map, clock, spells, name_generator, lazy, mut log, mut requests) = data;
+
+        let casts: Vec<(Entity, WantsToCast, (u32, u32))> = (&entities, wants_to_cast.drain(), &positions).join()
+            .map(|(entity, cast, pos)| (entity, cast, (pos.x, pos.y)))
+            .collect();
+        wants_to_cast.clear();
+
+        let mut rng = rand::thread_rng();
+        for (caster, WantsToCast { spell: id, x, y }, from) in casts {
+            let (necromancer, spell) = match (necromancers.get_mut(caster), spells.get(&id)) {
+                (Some(necromancer), Some(spell)) => (necromancer, spell),
+                _ => continue
+            };
+            if let Err(reason) = spell.check(&id, necromancer, from, (x, y), clock.tick) {
+                log.add(LogEntry::new(clock.tick, LogCategory::Combat, reason).severity(Severity::Warning).about(caster));
+                continue;
+            }
+
+            let area = spell.area(x, y, map.width, map.height);
+            let hostile = |entity: Entity| factions.get(entity).is_none_or(|faction| faction.faction != FactionType::Undead);
+            let worked = match spell.effect {
+                SpellEffect::RaiseDead => {
+                    match (&entities, &corpses, &positions).join().find(|(_, _, pos)| (pos.x, pos.y) == (x, y)) {
+                        Some((corpse_entity, corpse, _)) => {
+                            let kind = UndeadType::Zombie;
+                            let name = corpse.villager.as_ref().map(|villager| villager.raised_name())
+                                .or_else(|| name_generator.generate(kind.name_style(), &mut rng))
+                                .unwrap_or_else(|| tr("unit.unnamed"));
+                            log.add(LogEntry::new(clock.tick, LogCategory::Combat, tr_args("log.raised", &[("name", name.clone())])).at(x, y));
+                            entities.delete(corpse_entity).expect("Unable to delete raised corpse");
+                            raise(&lazy, &entities, kind, name, x, y);
+                            true
+                        },
+                        None => false
+                    }
+                },
+                SpellEffect::Damage { amount } => {
+                    let target = (&entities, &positions, &livings).join()
+                        .find(|(entity, pos, living)| (pos.x, pos.y) == (x, y) && living.health > 0 && hostile(*entity))
+                        .map(|(entity, _, _)| entity);
+                    match target {
+                        Some(target) => {
+                            let living = livings.get_mut(target).unwrap();
+                            living.health -= amount;
+                            if living.health <= 0 {
+                                if let Some(kills) = kills.get_mut(caster) {
+                                    kills.count += 1;
+                                }
+                            }
+                            true
+                        },
+                        None => false
+                    }
+                },
+                SpellEffect::Fear { ticks } => {
+                    let mut feared = false;
+                    for (_, pos, effects) in (&villagers, &positions, &mut statuses).join() {
+                        if area.contains(&(pos.x, pos.y)) {
+                            effects.add(StatusEffect::Feared, ticks);
+                            feared = true;
+                        }
+                    }
+                    feared
+                },
+                SpellEffect::CorpseExplosion { damage } => {
+                    let burst: Vec<(Entity, u32, u32)> = (&entities, &corpses, &positions).join()
+                        .filter(|(_, _, pos)| area.contains(&(pos.x, pos.y)))
+                        .map(|(entity, _, pos)| (entity, pos.x, pos.y))
+                        .collect();
+                    for (corpse, cx, cy) in burst.iter() {
+                        entities.delete(*corpse).expect("Unable to delete exploded corpse");
+                        requests.queue.push(EffectRequest::new("blood", *cx, *cy));
+                        for (entity, pos, living) in (&entities, &positions, &mut livings).join() {
+                            if pos.x.abs_diff(*cx) <= 1 && pos.y.abs_diff(*cy) <= 1 && living.health > 0 && hostile(entity) {
+                                living.health -= damage;
+                                if living.health <= 0 {
+                                    if let Some(kills) = kills.get_mut(caster) {
+                                        kills.count += 1;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    !burst.is_empty()
+                },
+                SpellEffect::Summon { kind } => {
+                    let occupied = (&entities, &positions).join()
+                        .any(|(entity, pos)| (pos.x, pos.y) == (x, y) && (blockers.contains(entity) || livings.contains(entity)));
+                    if occupied || x >= map.width || y >= map.height || map.tiles[map.xy_idx(x, y) as usize] == 1 {
+                        false
+                    } else {
+                        let name = name_generator.generate(kind.name_style(), &mut rng).unwrap_or_else(|| tr("unit.unnamed"));
+                        raise(&lazy, &entities, kind, name, x, y);
+                        true
+                    }
+                }
+            };
+
+            let spell_name = Spell::get_text(&id);
+            if !worked {
+                log.add(LogEntry::new(clock.tick, LogCategory::Combat, tr_args("log.no_target", &[("spell", spell_name)]))
+                    .severity(Severity::Warning).about(caster).at(x, y));
+                continue;
+            }
+
+            let necromancer = necromancers.get_mut(caster).unwrap();
+            necromancer.mana -= spell.cost;
+            necromancer.cooldowns.insert(id.clone(), clock.tick + spell.cooldown);
+            let caster_name = names.get(caster).map_or_else(|| tr("unit.unnamed"), |name| name.name.clone());
+            log.add(LogEntry::new(clock.tick, LogCategory::Combat, tr_args("log.spell_cast", &[("name", caster_name), ("spell", spell_name)]))
+                .about(caster).at(x, y));
+            requests.queue.push(EffectRequest::new("trail", x, y).from(from.0, from.1));
+            requests.queue.push(EffectRequest::new("spell_burst", x, y));
+        }
+    }
+}
+
+/// Adds an undead unit once the world is next maintained.
+fn raise(lazy: &LazyUpdate, entities: &Entities, kind: UndeadType, name: String, x: u32, y: u32) {
+    lazy.create_entity(entities)
+        .with(Position{ x, y })
+        .with(Renderable::new(kind.get_glyph(), RAISED_COLOR, RenderLayer::Unit))
+        .with(Name{ name })
+        .with(Unit{ mission: Mission::Stay, kind })
+        .with(MissionQueue::default())
+        .with(Faction{ faction: FactionType::Undead })
+        .with(Living::new(10))
+        .with(Integrity::new(kind.max_integrity()))
+        .with(Physical{ weight: 60, size: 2 })
+        .with(Inventory::default())
+        .with(StatusEffects::default())
+        .with(Kills::default())
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dispatcher, Profession};
+
+    fn cast(world: &mut World, caster: Entity, spell: &str, x: u32, y: u32) {
+        world.write_storage::<WantsToCast>().insert(caster, WantsToCast{ spell: spell.to_string(), x, y }).unwrap();
+        SpellSystem {}.run_now(world);
+        world.maintain();
+    }
+
+    #[test]
+    fn spells_spend_mana_only_when_they_work() {
+        crate::locale::set_language("en").unwrap();
+        let mut world = World::new();
+        dispatcher::register_components(&mut world);
+        world.insert(Map::new(10, 10));
+        world.insert(GameClock::default());
+        world.insert(Gamelog::default());
+        world.insert(EffectRequests::default());
+        world.insert(NameGenerator::default());
+        world.insert(Spells::parse("[spear]\ntarget = \"entity\"\ncost = 8\ncooldown = 5\nrange = 6\neffect = \"damage\"\namount = 6\n\n\
+                                    [raise]\ntarget = \"entity\"\ncost = 10\ncooldown = 0\nrange = 6\neffect = \"raise_dead\"").unwrap());
+        let caster = world.create_entity().with(Position{ x: 0, y: 0 }).with(Necromancer::new(30, vec![])).with(Kills::default()).build();
+        let villager = Villager{ given_name: "Hild".to_string(), profession: Profession::Farmer };
+        let target = world.create_entity().with(Position{ x: 3, y: 0 }).with(Living::new(10)).with(villager.clone()).build();
+        world.create_entity().with(Position{ x: 1, y: 1 }).with(Corpse{ villager: Some(villager) }).build();
+
+        cast(&mut world, caster, "spear", 3, 0);
+        assert_eq!(world.read_storage::<Living>().get(target).unwrap().health, 4);
+        assert_eq!(world.read_storage::<Necromancer>().get(caster).unwrap().mana, 22);
+
+        // Still cooling down
+        cast(&mut world, caster, "spear", 3, 0);
+        assert_eq!(world.read_storage::<Living>().get(target).unwrap().health, 4);
+
+        // Nothing to raise there
+        cast(&mut world, caster, "raise", 2, 2);
+        assert_eq!(world.read_storage::<Necromancer>().get(caster).unwrap().mana, 22);
+
+        cast(&mut world, caster, "raise", 1, 1);
+        assert_eq!(world.read_storage::<Necromancer>().get(caster).unwrap().mana, 12);
+        assert_eq!(world.read_storage::<Corpse>().count(), 0);
+        let raised: Vec<String> = (&world.read_storage::<Unit>(), &world.read_storage::<Name>()).join().map(|(_, name)| name.name.clone()).collect();
+        assert_eq!(raised, vec!["Hild's Husk".to_string()]);
+        assert_eq!(world.fetch::<EffectRequests>().queue.iter().filter(|request| request.name == "trail").count(), 2);
+    }
+}
@@ -0,0 +1,158 @@
+//! Spells the necromancer casts. They are loaded from `resources/spells.toml`, one table per spell,
+//! and carried out by the `SpellSystem` on the tick after they are cast.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+use super::{Necromancer, UndeadType};
+use super::locale::{tr, tr_args};
+
+const SPELLS_PATH: &str = "./resources/spells.toml";
+
+/// What a spell is aimed at.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Targeting {
+    /// Any tile in range.
+    Tile,
+    /// Something standing on the tile.
+    Entity,
+    /// Every tile within the radius around the tile.
+    Area
+}
+
+/// What a spell does once cast.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(tag = "effect", rename_all = "snake_case")]
+pub enum SpellEffect {
+    /// Raises a corpse as a zombie.
+    RaiseDead,
+    /// Hurts a living target that is not undead.
+    Damage { amount: i32 },
+    /// Frightens the villagers in the area, who stop holding back corruption.
+    Fear { ticks: u32 },
+    /// Bursts the corpses in the area, hurting the living next to them.
+    CorpseExplosion { damage: i32 },
+    /// Calls up a new undead on an empty tile.
+    Summon { kind: UndeadType }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Spell {
+    pub target: Targeting,
+    /// Mana spent on casting.
+    pub cost: i32,
+    /// Ticks before it can be cast again.
+    pub cooldown: u64,
+    /// Furthest tile from the caster it can be cast at.
+    pub range: u32,
+    /// Size of the area of area spells.
+    #[serde(default)]
+    pub radius: u32,
+    #[serde(flatten)]
+    pub effect: SpellEffect
+}
+
+impl Spell {
+    pub fn get_text(id: &str) -> String {
+        tr(&format!("spell.{}", id))
+    }
+
+    /// Tiles the spell would reach when cast at a tile, inside a map of the given size.
+    pub fn area(&self, x: u32, y: u32, width: u32, height: u32) -> Vec<(u32, u32)> {
+        let radius = if self.target == Targeting::Area { self.radius } else { 0 };
+        let mut tiles = vec![];
+        for ty in y.saturating_sub(radius)..(y + radius + 1).min(height) {
+            for tx in x.saturating_sub(radius)..(x + radius + 1).min(width) {
+                let (dx, dy) = (tx.abs_diff(x), ty.abs_diff(y));
+                if dx * dx + dy * dy <= radius * radius {
+                    tiles.push((tx, ty));
+                }
+            }
+        }
+        tiles
+    }
+
+    pub fn in_range(&self, from: (u32, u32), to: (u32, u32)) -> bool {
+        let (dx, dy) = (from.0.abs_diff(to.0), from.1.abs_diff(to.1));
+        dx * dx + dy * dy <= self.range * self.range
+    }
+
+    /// Whether the caster can cast the spell at a tile now. Tells why not otherwise.
+    /// Whether there is something to cast it on is only known when it is cast.
+    pub fn check(&self, id: &str, caster: &Necromancer, from: (u32, u32), to: (u32, u32), tick: u64) -> Result<(), String> {
+        let name = Spell::get_text(id);
+        if caster.mana < self.cost {
+            return Err(tr_args("log.no_mana", &[("spell", name)]));
+        }
+        if caster.cooldowns.get(id).is_some_and(|ready| *ready > tick) {
+            return Err(tr_args("log.cooldown", &[("spell", name)]));
+        }
+        if !self.in_range(from, to) {
+            return Err(tr_args("log.out_of_range", &[("spell", name)]));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct Spells {
+    #[serde(flatten)]
+    pub spells: BTreeMap<String, Spell>
+}
+
+impl Spells {
+    /// Loads the spells. Returns a warning when they could not be read, and nothing can be cast then.
+    pub fn load() -> (Spells, Vec<String>) {
+        let spells = fs::read_to_string(SPELLS_PATH).map_err(|e| e.to_string()).and_then(|text| Spells::parse(&text));
+        match spells {
+            Ok(spells) => (spells, vec![]),
+            Err(e) => (Spells::default(), vec![tr_args("log.file_failed", &[("path", SPELLS_PATH.to_string()), ("error", e.to_string())])])
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Spells, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Spell> {
+        self.spells.get(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_spells_load() {
+        let (spells, warnings) = Spells::load();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        for id in ["raise_dead", "bone_spear", "fear", "corpse_explosion", "summon"] {
+            assert!(spells.get(id).is_some(), "{} is missing", id);
+        }
+
+        let spells = Spells::parse("[nova]\ntarget = \"area\"\nradius = 1\ncost = 5\ncooldown = 10\nrange = 4\neffect = \"damage\"\namount = 3").unwrap();
+        let nova = spells.get("nova").unwrap();
+        assert_eq!(nova.effect, SpellEffect::Damage { amount: 3 });
+        assert_eq!(nova.area(0, 0, 10, 10), vec![(0, 0), (1, 0), (0, 1)]);
+        assert!(Spells::parse("[nova]\ntarget = \"area\"\ncost = 5\ncooldown = 10\nrange = 4\neffect = \"damage\"").is_err());
+    }
+
+    #[test]
+    fn casting_needs_mana_range_and_a_cooled_down_spell() {
+        crate::locale::set_language("en").unwrap();
+        let spells = Spells::parse("[spear]\ntarget = \"entity\"\ncost = 10\ncooldown = 5\nrange = 3\neffect = \"damage\"\namount = 6").unwrap();
+        let spear = spells.get("spear").unwrap();
+        let mut caster = Necromancer::new(20, vec!["spear".to_string()]);
+
+        assert!(spear.check("spear", &caster, (0, 0), (2, 2), 0).is_ok());
+        assert!(spear.check("spear", &caster, (0, 0), (3, 1), 0).is_err());
+        caster.cooldowns.insert("spear".to_string(), 5);
+        assert!(spear.check("spear", &caster, (0, 0), (1, 0), 4).is_err());
+        assert!(spear.check("spear", &caster, (0, 0), (1, 0), 5).is_ok());
+        caster.mana = 9;
+        assert!(spear.check("spear", &caster, (0, 0), (1, 0), 5).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+use specs::prelude::*;
+
+use super::{Living, StatusEffect, StatusEffects};
+use super::clock::GameClock;
+
+/// Damage done by burning every `BURN_INTERVAL` ticks.
+const BURN_DAMAGE: i32 = 1;
+const BURN_INTERVAL: u64 = 10;
+/// Health the empowered regain every `EMPOWERED_INTERVAL` ticks.
+const EMPOWERED_HEALING: i32 = 1;
+const EMPOWERED_INTERVAL: u64 = 15;
+
+/// Counts down status effects and applies the ones that act over time.
+pub struct StatusSystem {}
+
+impl<'a> System<'a> for StatusSystem {
+    type SystemData = (
+        WriteStorage<'a, StatusEffects>,
+        WriteStorage<'a, Living>,
+        Read<'a, GameClock>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut statuses, mut livings, clock) = data;
+
+        for (effects, living) in (&mut statuses, (&mut livings).maybe()).join() {
+            if let Some(living) = living {
+                if effects.has(StatusEffect::Burning) && clock.tick % BURN_INTERVAL == 0 {
+                    living.health -= BURN_DAMAGE;
+                }
+                if effects.has(StatusEffect::Empowered) && clock.tick % EMPOWERED_INTERVAL == 0 {
+                    living.health = (living.health + EMPOWERED_HEALING).min(living.max_health);
+                }
+            }
+            for effect in effects.effects.iter_mut() {
+                effect.1 = effect.1.saturating_sub(1);
+            }
+            effects.effects.retain(|(_, ticks)| *ticks > 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burning_hurts_and_empowering_heals_until_they_wear_off() {
+        let mut world = World::new();
+        world.register::<StatusEffects>();
+        world.register::<Living>();
+        world.insert(GameClock::default());
+        let wraith = world.create_entity()
+                          .with(Living::new(10))
+                          .with(StatusEffects{ effects: vec![(StatusEffect::Burning, 12), (StatusEffect::Slowed, 3)] })
+                          .build();
+        let zombie = world.create_entity()
+                          .with(Living{ max_health: 10, health: 5 })
+                          .with(StatusEffects{ effects: vec![(StatusEffect::Empowered, 20)] })
+                          .build();
+
+        for _ in 0..30 {
+            StatusSystem {}.run_now(&world);
+            world.write_resource::<GameClock>().tick += 1;
+        }
+
+        let livings = world.read_storage::<Living>();
+        assert_eq!(livings.get(wraith).unwrap().health, 8);
+        assert_eq!(livings.get(zombie).unwrap().health, 7);
+        assert!(world.read_storage::<StatusEffects>().get(wraith).unwrap().effects.is_empty());
+    }
+}
@@ -0,0 +1,115 @@
+use specs::prelude::*;
+use std::collections::HashSet;
+
+use super::{Building, Faction, FactionType, Name, Position, StatusEffect, StatusEffects, Unit};
+use super::clock::GameClock;
+use super::gamelog::{Gamelog, LogCategory, LogEntry, Severity};
+use super::lighting_system::Lighting;
+use super::locale::{tr, tr_args};
+
+/// Daylight from which the sun is strong enough to hurt.
+const SUNLIGHT_LEVEL: f32 = 0.5;
+/// Ticks a sunlight weakness lasts once out of the light.
+const EXPOSURE_TICKS: u32 = 5;
+
+/// Weakens undead caught in sunlight or holy light, and makes them rot faster. Undead buildings like the crypt
+/// shelter them from the sun, but not from holy light.
+pub struct SunlightSystem {}
+
+impl<'a> System<'a> for SunlightSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Unit>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Building>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, StatusEffects>,
+        Read<'a, GameClock>,
+        Read<'a, Lighting>,
+        Write<'a, Gamelog>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, units, positions, buildings, factions, names, mut statuses, clock, lighting, mut log) = data;
+
+        let sunny = clock.daylight() >= SUNLIGHT_LEVEL;
+        let shelters: HashSet<(u32, u32)> = (&buildings, &factions, &positions).join()
+            .filter(|(_, faction, _)| faction.faction == FactionType::Undead)
+            .map(|(_, _, pos)| (pos.x, pos.y))
+            .collect();
+
+        for (entity, unit, pos, effects) in (&entities, &units, &positions, &mut statuses).join() {
+            let exposed = lighting.is_holy(pos.x, pos.y) || (sunny && !shelters.contains(&(pos.x, pos.y)));
+            if !exposed {
+                continue;
+            }
+            effects.add(StatusEffect::Decaying, EXPOSURE_TICKS);
+            let weakness = match unit.kind.sunlight_weakness() {
+                Some(weakness) => weakness,
+                None => continue
+            };
+
+            if !effects.has(weakness) {
+                let name = names.get(entity).map_or_else(|| tr("unit.unnamed"), |name| name.name.clone());
+                let key = if weakness == StatusEffect::Burning { "log.burning" } else { "log.slowed" };
+                log.add(LogEntry::new(clock.tick, LogCategory::Combat, tr_args(key, &[("name", name)]))
+                    .severity(Severity::Warning).about(entity).at(pos.x, pos.y));
+            }
+            effects.add(weakness, EXPOSURE_TICKS);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LightSource, Mission, UndeadType, dispatcher, lighting_system::LightingSystem, map::Map};
+
+    fn spawn(world: &mut World, kind: UndeadType, x: u32, y: u32) -> Entity {
+        world.create_entity()
+             .with(Position{ x, y })
+             .with(Unit{ mission: Mission::Stay, kind })
+             .with(StatusEffects::default())
+             .build()
+    }
+
+    #[test]
+    fn sunlight_weakens_undead_outside_the_crypt() {
+        crate::locale::set_language("en").unwrap();
+        let mut world = World::new();
+        dispatcher::register_components(&mut world);
+        world.insert(Map::new(10, 10));
+        world.insert(GameClock { tick: 4 * 60 });
+        world.insert(Lighting::default());
+        world.insert(Gamelog::default());
+        world.create_entity().with(Position{ x: 5, y: 5 }).with(Building{}).with(Faction{ faction: FactionType::Undead }).build();
+        world.create_entity().with(Position{ x: 9, y: 9 }).with(LightSource{ radius: 1, color: (255, 255, 200), holy: true }).build();
+
+        let wraith = spawn(&mut world, UndeadType::Wraith, 0, 0);
+        let sheltered = spawn(&mut world, UndeadType::Wraith, 5, 5);
+        let zombie = spawn(&mut world, UndeadType::Zombie, 1, 0);
+        let skeleton = spawn(&mut world, UndeadType::Skeleton, 2, 0);
+        LightingSystem {}.run_now(&world);
+        SunlightSystem {}.run_now(&world);
+        {
+            let statuses = world.read_storage::<StatusEffects>();
+            assert!(statuses.get(wraith).unwrap().has(StatusEffect::Burning));
+            assert!(statuses.get(zombie).unwrap().has(StatusEffect::Slowed));
+            assert!(statuses.get(sheltered).unwrap().effects.is_empty());
+            assert!(statuses.get(skeleton).unwrap().has(StatusEffect::Decaying));
+            assert!(!statuses.get(skeleton).unwrap().has(StatusEffect::Slowed));
+        }
+
+        // By night only holy light hurts
+        world.write_resource::<GameClock>().tick = 16 * 60;
+        let night = spawn(&mut world, UndeadType::Zombie, 3, 0);
+        let holy = spawn(&mut world, UndeadType::Zombie, 8, 9);
+        LightingSystem {}.run_now(&world);
+        SunlightSystem {}.run_now(&world);
+        let statuses = world.read_storage::<StatusEffects>();
+        assert!(statuses.get(night).unwrap().effects.is_empty());
+        assert!(statuses.get(holy).unwrap().has(StatusEffect::Slowed));
+        assert_eq!(world.fetch::<Gamelog>().visible().count(), 3);
+    }
+}
@@ -0,0 +1,370 @@
+//! Plays the game in a text console: frames are sent as truecolor ANSI text and
+//! terminal keys and mouse reports are turned into SDL events.
+
+use crossterm::event as term;
+use crossterm::{cursor, execute, queue, style, terminal};
+use sdl2::event::{Event, Event::*};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::{MouseButton, MouseState, MouseWheelDirection};
+use sdl2::rect::Rect;
+use std::io::{self, Stdout, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use super::config::Config;
+use super::frontend::Frontend;
+use super::locale::tr_args;
+use super::renderer::{MemoryRenderer, Renderer};
+use super::widgets::{Area, Color, Symbol};
+
+/// Draws into a grid of characters and sends the cells that changed to the terminal on `present`.
+pub struct TerminalRenderer<W: Write> {
+    out: W,
+    frame: MemoryRenderer,
+    /// What the terminal shows now. None when it has to be redrawn from scratch.
+    shown: Option<MemoryRenderer>,
+    /// Why the last frame could not be sent. Nothing more is sent after that.
+    error: Option<io::Error>
+}
+
+impl<W: Write> TerminalRenderer<W> {
+    pub fn new(out: W, width: u32, height: u32) -> TerminalRenderer<W> {
+        TerminalRenderer { out, frame: MemoryRenderer::new(width, height), shown: None, error: None }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.frame = MemoryRenderer::new(width, height);
+        self.shown = None;
+    }
+
+    /// The error that stopped output, if any.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    fn send_changes(&mut self) -> io::Result<()> {
+        if self.shown.is_none() {
+            queue!(self.out, terminal::Clear(terminal::ClearType::All))?;
+        }
+
+        let (width, height) = self.frame.size();
+        let mut cursor = None;
+        let mut colors = None;
+        for y in 0..height {
+            for x in 0..width {
+                let cell = self.frame.cell(x, y).unwrap();
+                if self.shown.as_ref().and_then(|shown| shown.cell(x, y)) == Some(cell) {
+                    continue;
+                }
+                if cursor != Some((x, y)) {
+                    queue!(self.out, cursor::MoveTo(x as u16, y as u16))?;
+                }
+                if colors != Some((cell.color, cell.background)) {
+                    queue!(self.out, style::SetForegroundColor(rgb(cell.color)), style::SetBackgroundColor(rgb(cell.background)))?;
+                    colors = Some((cell.color, cell.background));
+                }
+                queue!(self.out, style::Print(cell.c))?;
+                cursor = Some((x + 1, y));
+            }
+        }
+
+        self.out.flush()?;
+        self.shown = Some(self.frame.clone());
+        Ok(())
+    }
+}
+
+fn rgb((r, g, b): Color) -> style::Color {
+    style::Color::Rgb { r, g, b }
+}
+
+impl<W: Write> Renderer for TerminalRenderer<W> {
+    fn size(&self) -> (u32, u32) {
+        self.frame.size()
+    }
+
+    fn cell_size(&self) -> (u32, u32) {
+        self.frame.cell_size()
+    }
+
+    fn clear(&mut self, color: Color) {
+        self.frame.clear(color);
+    }
+
+    fn draw_glyph(&mut self, x: i32, y: i32, symbol: Symbol, color: Color, background: Option<Color>) {
+        self.frame.draw_glyph(x, y, symbol, color, background);
+    }
+
+    fn fill_rects(&mut self, rects: &[Rect], color: Color) {
+        self.frame.fill_rects(rects, color);
+    }
+
+    fn outline(&mut self, rect: Rect, color: Color) {
+        self.frame.outline(rect, color);
+    }
+
+    fn shade(&mut self, alpha: u8) {
+        self.frame.shade(alpha);
+    }
+
+    fn image(&mut self, id: &'static str, key: u64, size: (u32, u32), pixels: &dyn Fn() -> Vec<Color>, rect: Rect) {
+        self.frame.image(id, key, size, pixels, rect);
+    }
+
+    fn layer(&mut self, id: &'static str, key: u64, area: Area, draw: &mut dyn FnMut(&mut dyn Renderer)) {
+        self.frame.layer(id, key, area, draw);
+    }
+
+    fn present(&mut self) {
+        if self.error.is_none() {
+            self.error = self.send_changes().err();
+        }
+    }
+
+    fn save_screen(&mut self, path: &Path) -> Result<(), String> {
+        self.frame.save_screen(path)
+    }
+
+    fn export(&mut self, area: Area, path: &Path, draw: &mut dyn FnMut(&mut dyn Renderer)) -> Result<(), String> {
+        self.frame.export(area, path, draw)
+    }
+
+    fn take_draw_calls(&mut self) -> u32 {
+        self.frame.take_draw_calls()
+    }
+}
+
+/// Size of the terminal in cells, or a classic 80x24 when it cannot be told.
+pub fn terminal_size() -> (u32, u32) {
+    terminal::size().map_or((80, 24), |(width, height)| (width as u32, height as u32))
+}
+
+/// Keeps the terminal in raw mode on the alternate screen with mouse reports, until dropped.
+pub struct TerminalFrontend {
+    text_input: bool
+}
+
+impl TerminalFrontend {
+    pub fn new() -> io::Result<TerminalFrontend> {
+        terminal::enable_raw_mode()?;
+        // Dropping it restores whatever part of the setup went through
+        let frontend = TerminalFrontend { text_input: false };
+        execute!(io::stdout(), terminal::EnterAlternateScreen, term::EnableMouseCapture, cursor::Hide)?;
+        Ok(frontend)
+    }
+}
+
+impl Drop for TerminalFrontend {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), style::ResetColor, cursor::Show, term::DisableMouseCapture, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl Frontend for TerminalFrontend {
+    type Renderer = TerminalRenderer<Stdout>;
+
+    fn poll_events(&mut self, renderer: &mut TerminalRenderer<Stdout>) -> Vec<Event> {
+        let mut events = vec![];
+        while term::poll(Duration::ZERO).unwrap_or(false) {
+            match term::read() {
+                Ok(term::Event::Resize(width, height)) => renderer.resize(width as u32, height as u32),
+                Ok(event) => events.extend(translate(event, self.text_input)),
+                Err(_) => break
+            }
+        }
+        events
+    }
+
+    fn set_text_input(&mut self, enabled: bool) {
+        self.text_input = enabled;
+    }
+
+    /// The mouse pointer belongs to the terminal.
+    fn show_cursor(&mut self, _shown: bool) {}
+
+    /// Window mode, tileset and tile size are up to the terminal.
+    fn configure(&mut self, _config: &Config, _renderer: &mut TerminalRenderer<Stdout>) -> Vec<String> {
+        vec![]
+    }
+
+    fn tileset_name(&self, _renderer: &TerminalRenderer<Stdout>) -> Option<String> {
+        None
+    }
+
+    /// Ends the game once the terminal stops taking output, for example when it was closed.
+    fn finished(&mut self, renderer: &mut TerminalRenderer<Stdout>) -> Option<Result<(), String>> {
+        renderer.take_error().map(|e| Err(tr_args("log.terminal_failed", &[("error", e.to_string())])))
+    }
+}
+
+fn keycode(code: term::KeyCode) -> Option<Keycode> {
+    match code {
+        term::KeyCode::Char(c) if c.is_ascii() => Keycode::from_i32(c.to_ascii_lowercase() as i32),
+        term::KeyCode::Enter => Some(Keycode::Return),
+        term::KeyCode::Esc => Some(Keycode::Escape),
+        term::KeyCode::Backspace => Some(Keycode::Backspace),
+        term::KeyCode::Tab | term::KeyCode::BackTab => Some(Keycode::Tab),
+        term::KeyCode::Delete => Some(Keycode::Delete),
+        term::KeyCode::Insert => Some(Keycode::Insert),
+        term::KeyCode::Up => Some(Keycode::Up),
+        term::KeyCode::Down => Some(Keycode::Down),
+        term::KeyCode::Left => Some(Keycode::Left),
+        term::KeyCode::Right => Some(Keycode::Right),
+        term::KeyCode::Home => Some(Keycode::Home),
+        term::KeyCode::End => Some(Keycode::End),
+        term::KeyCode::PageUp => Some(Keycode::PageUp),
+        term::KeyCode::PageDown => Some(Keycode::PageDown),
+        term::KeyCode::F(n) => Keycode::from_name(&format!("F{}", n)),
+        _ => None
+    }
+}
+
+fn mouse_button(button: term::MouseButton) -> MouseButton {
+    match button {
+        term::MouseButton::Left => MouseButton::Left,
+        term::MouseButton::Right => MouseButton::Right,
+        term::MouseButton::Middle => MouseButton::Middle
+    }
+}
+
+/// SDL mouse state with only `button` held.
+fn mouse_state(button: Option<term::MouseButton>) -> MouseState {
+    MouseState::from_sdl_state(match button {
+        Some(term::MouseButton::Left) => 1,
+        Some(term::MouseButton::Middle) => 2,
+        Some(term::MouseButton::Right) => 4,
+        None => 0
+    })
+}
+
+/// The SDL events a terminal event stands for. Mouse positions are in cells, which is
+/// what the terminal renderer uses as pixels.
+fn translate(event: term::Event, text_input: bool) -> Vec<Event> {
+    match event {
+        term::Event::Key(key) if key.kind != term::KeyEventKind::Release => {
+            let ctrl = key.modifiers.contains(term::KeyModifiers::CONTROL);
+            let alt = key.modifiers.contains(term::KeyModifiers::ALT);
+            if ctrl && key.code == term::KeyCode::Char('c') {
+                return vec![Quit { timestamp: 0 }];
+            }
+
+            // Terminals send shifted symbols as they are, so only letters and special keys carry Shift
+            let shift = match key.code {
+                term::KeyCode::Char(c) => c.is_ascii_uppercase(),
+                term::KeyCode::BackTab => true,
+                _ => key.modifiers.contains(term::KeyModifiers::SHIFT)
+            };
+            let mut keymod = Mod::NOMOD;
+            if shift { keymod |= Mod::LSHIFTMOD; }
+            if ctrl { keymod |= Mod::LCTRLMOD; }
+            if alt { keymod |= Mod::LALTMOD; }
+
+            let mut events = vec![KeyDown {
+                timestamp: 0, window_id: 0, keycode: keycode(key.code), scancode: None, keymod,
+                repeat: key.kind == term::KeyEventKind::Repeat
+            }];
+            if let term::KeyCode::Char(c) = key.code {
+                if text_input && !ctrl && !alt {
+                    events.push(TextInput { timestamp: 0, window_id: 0, text: c.to_string() });
+                }
+            }
+            events
+        },
+        term::Event::Mouse(mouse) => {
+            let (x, y) = (mouse.column as i32, mouse.row as i32);
+            match mouse.kind {
+                term::MouseEventKind::Down(button) => vec![MouseButtonDown {
+                    timestamp: 0, window_id: 0, which: 0, mouse_btn: mouse_button(button), clicks: 1, x, y
+                }],
+                term::MouseEventKind::Up(button) => vec![MouseButtonUp {
+                    timestamp: 0, window_id: 0, which: 0, mouse_btn: mouse_button(button), clicks: 1, x, y
+                }],
+                term::MouseEventKind::Drag(button) => vec![MouseMotion {
+                    timestamp: 0, window_id: 0, which: 0, mousestate: mouse_state(Some(button)), x, y, xrel: 0, yrel: 0
+                }],
+                term::MouseEventKind::Moved => vec![MouseMotion {
+                    timestamp: 0, window_id: 0, which: 0, mousestate: mouse_state(None), x, y, xrel: 0, yrel: 0
+                }],
+                term::MouseEventKind::ScrollUp | term::MouseEventKind::ScrollDown => vec![MouseWheel {
+                    timestamp: 0, window_id: 0, which: 0, x: 0,
+                    y: if mouse.kind == term::MouseEventKind::ScrollUp { 1 } else { -1 },
+                    direction: MouseWheelDirection::Normal
+                }],
+                _ => vec![]
+            }
+        },
+        _ => vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: term::KeyCode, modifiers: term::KeyModifiers) -> term::Event {
+        term::Event::Key(term::KeyEvent::new(code, modifiers))
+    }
+
+    #[test]
+    fn terminal_input_becomes_sdl_events() {
+        match translate(key(term::KeyCode::Char('M'), term::KeyModifiers::SHIFT), true).as_slice() {
+            [KeyDown { keycode: Some(Keycode::M), keymod, .. }, TextInput { text, .. }] => {
+                assert!(keymod.contains(Mod::LSHIFTMOD));
+                assert_eq!(text, "M");
+            },
+            other => panic!("{:?}", other)
+        }
+        match translate(key(term::KeyCode::Char('+'), term::KeyModifiers::SHIFT), false).as_slice() {
+            [KeyDown { keycode: Some(Keycode::Plus), keymod: Mod::NOMOD, .. }] => {},
+            other => panic!("{:?}", other)
+        }
+        assert!(matches!(translate(key(term::KeyCode::Char('c'), term::KeyModifiers::CONTROL), false).as_slice(), [Quit { .. }]));
+
+        let drag = term::Event::Mouse(term::MouseEvent { kind: term::MouseEventKind::Drag(term::MouseButton::Left), column: 20, row: 4, modifiers: term::KeyModifiers::NONE });
+        match translate(drag, false).as_slice() {
+            [MouseMotion { mousestate, x: 20, y: 4, .. }] => assert!(mousestate.left()),
+            other => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn only_changed_cells_are_sent() {
+        let mut renderer = TerminalRenderer::new(vec![], 3, 1);
+        renderer.clear((0, 0, 0));
+        renderer.draw_glyph(1, 0, Symbol::Char('A'), (255, 0, 0), None);
+        renderer.present();
+        let first = String::from_utf8(std::mem::take(&mut renderer.out)).unwrap();
+        assert!(first.contains("\x1b[38;2;255;0;0m"), "{:?}", first);
+        assert!(first.contains('A'));
+
+        renderer.clear((0, 0, 0));
+        renderer.draw_glyph(1, 0, Symbol::Char('A'), (255, 0, 0), None);
+        renderer.draw_glyph(2, 0, Symbol::Char('B'), (255, 0, 0), None);
+        renderer.present();
+        let second = String::from_utf8(std::mem::take(&mut renderer.out)).unwrap();
+        assert!(!second.contains('A') && second.contains('B'), "{:?}", second);
+    }
+
+    struct Closed;
+
+    impl Write for Closed {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+        }
+    }
+
+    #[test]
+    fn output_errors_are_kept_for_the_game_loop() {
+        let mut renderer = TerminalRenderer::new(Closed, 3, 1);
+        renderer.draw_glyph(0, 0, Symbol::Char('A'), (255, 0, 0), None);
+        renderer.present();
+        renderer.present();
+        assert_eq!(renderer.take_error().map(|e| e.kind()), Some(io::ErrorKind::BrokenPipe));
+        assert!(renderer.take_error().is_none());
+    }
+}
@@ -0,0 +1,116 @@
+use sdl2::rect::Rect;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::cp437;
+
+const RESOURCES_DIR: &str = "./resources";
+
+/// Describes a glyph atlas image. Loaded from a TOML manifest next to the image:
+///
+/// ```toml
+/// name = "RogueYun"
+/// image = "16x16-RogueYun-AgmEdit.png"
+/// glyph_width = 16
+/// glyph_height = 16
+/// columns = 16
+///
+/// [mapping]
+/// "☺" = 1
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Tileset {
+    pub name: String,
+    /// Image path, relative to the manifest.
+    pub image: String,
+    pub glyph_width: u32,
+    pub glyph_height: u32,
+    pub columns: u32,
+    /// Glyphs for characters that are not at their code point in the atlas.
+    #[serde(default)]
+    pub mapping: BTreeMap<char, u32>,
+    #[serde(skip)]
+    dir: PathBuf
+}
+
+impl Tileset {
+    pub fn load<P: AsRef<Path>>(manifest: P) -> io::Result<Tileset> {
+        let manifest = manifest.as_ref();
+        let text = fs::read_to_string(manifest)?;
+        let mut tileset: Tileset = toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        if tileset.glyph_width == 0 || tileset.glyph_height == 0 || tileset.columns == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "glyph size and column count must be positive"));
+        }
+
+        tileset.dir = manifest.parent().map_or(PathBuf::new(), |dir| dir.to_path_buf());
+        if !tileset.image_path().is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("image {} not found", tileset.image)));
+        }
+        Ok(tileset)
+    }
+
+    pub fn image_path(&self) -> PathBuf {
+        self.dir.join(&self.image)
+    }
+
+    /// Source rectangle of a glyph in the atlas.
+    pub fn tile_rect(&self, idx: u32) -> Rect {
+        let x = idx % self.columns;
+        let y = idx / self.columns;
+        Rect::new((self.glyph_width * x) as i32, (self.glyph_height * y) as i32, self.glyph_width, self.glyph_height)
+    }
+
+    /// Glyph index of a character: the manifest mapping first, then the CP437 layout, then a fallback glyph.
+    pub fn glyph(&self, c: char) -> u32 {
+        match self.mapping.get(&c) {
+            Some(glyph) => *glyph,
+            None => cp437::to_glyph(c).unwrap_or(cp437::FALLBACK_GLYPH)
+        }
+    }
+}
+
+/// Lists tileset manifests shipped in the resources directory. Other data files there, and manifests
+/// that do not load, are left out.
+pub fn available_tilesets() -> Vec<String> {
+    let mut tilesets: Vec<String> = match fs::read_dir(RESOURCES_DIR) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok())
+                              .map(|entry| entry.path())
+                              .filter(|path| path.extension().is_some_and(|ext| ext == "toml") && Tileset::load(path).is_ok())
+                              .map(|path| path.to_string_lossy().to_string())
+                              .collect(),
+        Err(_) => vec![]
+    };
+    tilesets.sort();
+    tilesets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_rect_handles_tall_glyphs_and_narrow_atlases() {
+        let tileset: Tileset = toml::from_str("name = \"Tall\"\nimage = \"tall.png\"\nglyph_width = 8\nglyph_height = 12\ncolumns = 10\n\n[mapping]\n\"é\" = 130").unwrap();
+
+        assert_eq!(tileset.tile_rect(0), Rect::new(0, 0, 8, 12));
+        assert_eq!(tileset.tile_rect(23), Rect::new(24, 24, 8, 12));
+        assert_eq!(tileset.glyph('é'), 130);
+        assert_eq!(tileset.glyph('A'), 65);
+        assert_eq!(tileset.glyph('│'), 179);
+        assert_eq!(tileset.glyph('€'), cp437::FALLBACK_GLYPH);
+    }
+
+    #[test]
+    fn only_tilesets_are_available() {
+        let tilesets = available_tilesets();
+        assert!(tilesets.contains(&crate::config::DEFAULT_TILESET.to_string()), "{:?}", tilesets);
+        for tileset in tilesets.iter() {
+            assert!(Tileset::load(tileset).is_ok(), "{} does not load", tileset);
+        }
+        assert!(!tilesets.iter().any(|tileset| tileset.ends_with("rules.toml")));
+    }
+}
@@ -0,0 +1,26 @@
+use specs::prelude::*;
+use super::{Faction, FactionType, Position, map::Map};
+
+/// How far the undead see, in tiles.
+pub const SIGHT_RADIUS: u32 = 4;
+
+/// Lifts the fog of war around the undead.
+pub struct VisibilitySystem {}
+
+impl<'a> System<'a> for VisibilitySystem {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, Position>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut map, factions, positions) = data;
+
+        for (faction, pos) in (&factions, &positions).join() {
+            if faction.faction == FactionType::Undead {
+                map.reveal_around(pos.x, pos.y, SIGHT_RADIUS);
+            }
+        }
+    }
+}
@@ -0,0 +1,473 @@
+use std::collections::BTreeMap;
+
+pub type Color = (u8, u8, u8);
+
+/// Mixes two colors, from all `from` at 0 to all `to` at 1.
+pub fn blend(from: Color, to: Color, t: f32) -> Color {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    (mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+}
+
+/// Enums the player steps through, like carousel buttons, pages and tabs.
+pub trait Choice: Copy + PartialEq + 'static {
+    fn all() -> &'static [Self];
+
+    fn index(&self) -> usize {
+        Self::all().iter().position(|choice| choice == self).unwrap()
+    }
+
+    fn next(&self) -> Self {
+        let all = Self::all();
+        all[(self.index() + 1) % all.len()]
+    }
+
+    fn prev(&self) -> Self {
+        let all = Self::all();
+        all[(self.index() + all.len() - 1) % all.len()]
+    }
+}
+
+/// A rectangle of screen cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Area {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32
+}
+
+impl Area {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Area {
+        Area { x, y, width, height }
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width as i32 && y < self.y + self.height as i32
+    }
+
+    pub fn inset(&self, cells: u32) -> Area {
+        Area::new(self.x + cells as i32, self.y + cells as i32,
+                  self.width.saturating_sub(cells * 2), self.height.saturating_sub(cells * 2))
+    }
+
+    pub fn centered(&self, width: u32, height: u32) -> Area {
+        let width = width.min(self.width);
+        let height = height.min(self.height);
+        Area::new(self.x + ((self.width - width) / 2) as i32, self.y + ((self.height - height) / 2) as i32, width, height)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align { Left, Center }
+
+/// Text characters go through the tileset's code page, glyphs are atlas indices.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symbol { Char(char), Glyph(u32) }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub x: i32,
+    pub y: i32,
+    pub symbol: Symbol,
+    pub color: Color,
+    pub background: Option<Color>
+}
+
+/// Clickable widget under the mouse. `index` is the item of a list or tab bar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub id: &'static str,
+    pub index: usize
+}
+
+pub enum Widget {
+    Panel { title: Option<String>, border: bool, background: Option<Color>, child: Box<Widget> },
+    Label { text: String, color: Color },
+    Button { id: &'static str, text: String, color: Color },
+    Icon { id: Option<&'static str>, glyph: u32, color: Color },
+    /// Lines with a marker on the selected one, scrolled to keep it visible.
+    List { id: &'static str, items: Vec<(String, Color)>, selected: usize, color: Color },
+    /// Three cells per tab with an icon in the middle.
+    TabBar { id: &'static str, tabs: Vec<(u32, Color)>, selected: usize, background: Color, selected_background: Color },
+    /// Lines the player scrolls through with the mouse wheel. The offset is kept by `Ui`,
+    /// clicks report the line.
+    ScrollView { id: &'static str, lines: Vec<(String, Color)> },
+    ProgressBar { value: f32, width: u32, color: Color },
+    Column(Vec<Widget>, Align),
+    Row(Vec<Widget>),
+    Spacer(u32),
+    /// Takes all space left in a row or column.
+    Fill
+}
+
+impl Widget {
+    pub fn label<T: ToString>(text: T, color: Color) -> Widget {
+        Widget::Label { text: text.to_string(), color }
+    }
+
+    pub fn panel(title: Option<String>, background: Color, child: Widget) -> Widget {
+        Widget::Panel { title, border: true, background: Some(background), child: Box::new(child) }
+    }
+
+    /// Preferred width and height in cells, `None` when the widget takes whatever it is given.
+    pub fn size(&self) -> (Option<u32>, Option<u32>) {
+        match self {
+            Widget::Panel { border, child, .. } => {
+                let (width, height) = child.size();
+                let edge = if *border { 2 } else { 0 };
+                (width.map(|w| w + edge), height.map(|h| h + edge))
+            },
+            Widget::Label { text, .. } | Widget::Button { text, .. } => (Some(text.chars().count() as u32), Some(1)),
+            Widget::Icon { .. } => (Some(1), Some(1)),
+            Widget::List { items, .. } => (items.iter().map(|(text, _)| text.chars().count() as u32 + 2).max(), Some(items.len() as u32)),
+            Widget::TabBar { .. } => (None, Some(1)),
+            Widget::ScrollView { .. } | Widget::Fill => (None, None),
+            Widget::ProgressBar { width, .. } => (Some(*width), Some(1)),
+            // Spacers only take room along the row or column they are in
+            Widget::Column(children, _) => {
+                let sizes: Vec<_> = children.iter().map(|child| match child {
+                    Widget::Spacer(cells) => (Some(0), Some(*cells)),
+                    _ => child.size()
+                }).collect();
+                (sizes.iter().map(|s| s.0).try_fold(0, |max, w| w.map(|w| w.max(max))),
+                 sizes.iter().map(|s| s.1).sum())
+            },
+            Widget::Row(children) => {
+                let sizes: Vec<_> = children.iter().map(|child| match child {
+                    Widget::Spacer(cells) => (Some(*cells), Some(0)),
+                    _ => child.size()
+                }).collect();
+                (sizes.iter().map(|s| s.0).sum(),
+                 sizes.iter().map(|s| s.1).try_fold(0, |max, h| h.map(|h| h.max(max))))
+            },
+            Widget::Spacer(cells) => (Some(*cells), Some(*cells))
+        }
+    }
+}
+
+/// Splits `total` cells between children, fills sharing what fixed sizes leave.
+fn split(sizes: &[Option<u32>], total: u32) -> Vec<u32> {
+    let fixed: u32 = sizes.iter().map(|size| size.unwrap_or(0)).sum();
+    let fills = sizes.iter().filter(|size| size.is_none()).count() as u32;
+    let free = total.saturating_sub(fixed);
+
+    let mut fill_index = 0;
+    sizes.iter().map(|size| match size {
+        Some(size) => *size,
+        None => {
+            fill_index += 1;
+            free / fills + if fill_index == fills { free % fills } else { 0 }
+        }
+    }).collect()
+}
+
+/// Lays widgets out into cells and remembers what is clickable and how far views are scrolled.
+#[derive(Default)]
+pub struct Ui {
+    cells: Vec<Cell>,
+    hits: Vec<(Area, Hit)>,
+    scroll: BTreeMap<&'static str, usize>
+}
+
+impl Ui {
+    /// Forgets the clickable areas of the previous frame.
+    pub fn begin_frame(&mut self) {
+        self.hits.clear();
+    }
+
+    /// Cells laid out since the last call.
+    pub fn take_cells(&mut self) -> Vec<Cell> {
+        std::mem::take(&mut self.cells)
+    }
+
+    /// Makes an area drawn outside the widget tree clickable.
+    pub fn add_hit(&mut self, area: Area, hit: Hit) {
+        self.hits.push((area, hit));
+    }
+
+    /// The topmost clickable widget at a cell.
+    pub fn hit(&self, x: i32, y: i32) -> Option<Hit> {
+        self.hits.iter().rev().find(|(area, _)| area.contains(x, y)).map(|(_, hit)| *hit)
+    }
+
+    pub fn scroll(&mut self, id: &'static str, lines: i32) {
+        let offset = self.scroll.entry(id).or_insert(0);
+        *offset = (*offset as i32 + lines).max(0) as usize;
+    }
+
+    /// Lays a widget out at its preferred size in the middle of `screen`.
+    pub fn layout_centered(&mut self, widget: &Widget, screen: Area) {
+        let (width, height) = widget.size();
+        let area = screen.centered(width.unwrap_or(screen.width), height.unwrap_or(screen.height));
+        self.layout(widget, area);
+    }
+
+    pub fn layout(&mut self, widget: &Widget, area: Area) {
+        match widget {
+            Widget::Panel { title, border, background, child } => {
+                if let Some(background) = background {
+                    self.fill(area, *background);
+                }
+                if *border {
+                    self.border(area, title.as_deref(), background.unwrap_or((0, 0, 0)));
+                    self.layout(child, area.inset(1));
+                } else {
+                    self.layout(child, area);
+                }
+            },
+            Widget::Label { text, color } => {
+                self.text(area, text, *color);
+            },
+            Widget::Button { id, text, color } => {
+                self.text(area, text, *color);
+                self.hits.push((Area::new(area.x, area.y, (text.chars().count() as u32).min(area.width), 1), Hit { id, index: 0 }));
+            },
+            Widget::Icon { id, glyph, color } => {
+                if area.width > 0 && area.height > 0 {
+                    self.put(area.x, area.y, Symbol::Glyph(*glyph), *color, None);
+                    if let Some(id) = id {
+                        self.hits.push((Area::new(area.x, area.y, 1, 1), Hit { id, index: 0 }));
+                    }
+                }
+            },
+            Widget::List { id, items, selected, color } => {
+                let offset = (*selected + 1).saturating_sub(area.height as usize);
+                for (row, (text, item_color)) in items.iter().enumerate().skip(offset).take(area.height as usize) {
+                    let y = area.y + (row - offset) as i32;
+                    let line = Area::new(area.x, y, area.width, 1);
+                    if row == *selected {
+                        self.put(area.x, y, Symbol::Glyph(16), *color, None);
+                        self.text(Area::new(area.x + 2, y, area.width.saturating_sub(2), 1), text, *color);
+                    } else {
+                        self.text(Area::new(area.x + 2, y, area.width.saturating_sub(2), 1), text, *item_color);
+                    }
+                    self.hits.push((line, Hit { id, index: row }));
+                }
+            },
+            Widget::TabBar { id, tabs, selected, background, selected_background } => {
+                self.fill(Area::new(area.x, area.y, area.width, 1), *background);
+                for (i, (glyph, color)) in tabs.iter().enumerate() {
+                    let tab = Area::new(area.x + i as i32 * 3, area.y, 3, 1);
+                    if i == *selected {
+                        self.fill(tab, *selected_background);
+                    }
+                    let tab_background = if i == *selected { *selected_background } else { *background };
+                    self.put(tab.x + 1, tab.y, Symbol::Glyph(*glyph), *color, Some(tab_background));
+                    self.hits.push((tab, Hit { id, index: i }));
+                }
+            },
+            Widget::ScrollView { id, lines } => {
+                let visible = area.height as usize;
+                let max_offset = lines.len().saturating_sub(visible);
+                let offset = self.scroll.get(id).copied().unwrap_or(0).min(max_offset);
+                self.scroll.insert(id, offset);
+
+                let overflows = max_offset > 0;
+                let text_width = if overflows { area.width.saturating_sub(1) } else { area.width };
+                for (row, (text, color)) in lines.iter().skip(offset).take(visible).enumerate() {
+                    let line = Area::new(area.x, area.y + row as i32, text_width, 1);
+                    self.text(line, text, *color);
+                    self.hits.push((line, Hit { id, index: offset + row }));
+                }
+
+                if overflows && area.width > 0 {
+                    let x = area.x + area.width as i32 - 1;
+                    let thumb = offset * visible.saturating_sub(1) / max_offset;
+                    for row in 0..visible {
+                        let symbol = if row == thumb { Symbol::Char('█') } else { Symbol::Char('░') };
+                        self.put(x, area.y + row as i32, symbol, (100, 100, 100), None);
+                    }
+                }
+            },
+            Widget::ProgressBar { value, width, color } => {
+                let width = (*width).min(area.width);
+                let filled = (value.clamp(0.0, 1.0) * width as f32).round() as u32;
+                for i in 0..width {
+                    let (symbol, cell_color) = if i < filled { (Symbol::Char('█'), *color) } else { (Symbol::Char('░'), (100, 100, 100)) };
+                    self.put(area.x + i as i32, area.y, symbol, cell_color, None);
+                }
+            },
+            Widget::Column(children, align) => {
+                let heights = split(&children.iter().map(|child| child.size().1).collect::<Vec<_>>(), area.height);
+                let mut y = area.y;
+                for (child, height) in children.iter().zip(heights) {
+                    let width = child.size().0.unwrap_or(area.width).min(area.width);
+                    let x = match align {
+                        Align::Left => area.x,
+                        Align::Center => area.x + ((area.width - width) / 2) as i32
+                    };
+                    let height = height.min((area.y + area.height as i32 - y).max(0) as u32);
+                    self.layout(child, Area::new(x, y, width, height));
+                    y += height as i32;
+                }
+            },
+            Widget::Row(children) => {
+                let widths = split(&children.iter().map(|child| child.size().0).collect::<Vec<_>>(), area.width);
+                let mut x = area.x;
+                for (child, width) in children.iter().zip(widths) {
+                    let width = width.min((area.x + area.width as i32 - x).max(0) as u32);
+                    self.layout(child, Area::new(x, area.y, width, area.height));
+                    x += width as i32;
+                }
+            },
+            Widget::Spacer(_) | Widget::Fill => {}
+        }
+    }
+
+    fn put(&mut self, x: i32, y: i32, symbol: Symbol, color: Color, background: Option<Color>) {
+        self.cells.push(Cell { x, y, symbol, color, background });
+    }
+
+    fn text(&mut self, area: Area, text: &str, color: Color) {
+        if area.height == 0 {
+            return;
+        }
+        for (i, c) in text.chars().take(area.width as usize).enumerate() {
+            self.put(area.x + i as i32, area.y, Symbol::Char(c), color, None);
+        }
+    }
+
+    fn fill(&mut self, area: Area, background: Color) {
+        for y in area.y..area.y + area.height as i32 {
+            for x in area.x..area.x + area.width as i32 {
+                self.put(x, y, Symbol::Char(' '), background, Some(background));
+            }
+        }
+    }
+
+    fn border(&mut self, area: Area, title: Option<&str>, background: Color) {
+        if area.width < 2 || area.height < 2 {
+            return;
+        }
+        let color = (100, 100, 100);
+        let (left, top) = (area.x, area.y);
+        let right = area.x + area.width as i32 - 1;
+        let bottom = area.y + area.height as i32 - 1;
+
+        for x in left + 1..right {
+            self.put(x, top, Symbol::Char('─'), color, Some(background));
+            self.put(x, bottom, Symbol::Char('─'), color, Some(background));
+        }
+        for y in top + 1..bottom {
+            self.put(left, y, Symbol::Char('│'), color, Some(background));
+            self.put(right, y, Symbol::Char('│'), color, Some(background));
+        }
+        self.put(left, top, Symbol::Char('┌'), color, Some(background));
+        self.put(right, top, Symbol::Char('┐'), color, Some(background));
+        self.put(left, bottom, Symbol::Char('└'), color, Some(background));
+        self.put(right, bottom, Symbol::Char('┘'), color, Some(background));
+
+        if let Some(title) = title {
+            let title = format!(" {} ", title);
+            let width = (title.chars().count() as u32).min(area.width - 2);
+            let x = area.x + ((area.width - width) / 2) as i32;
+            self.text(Area::new(x, top, width, 1), &title, (200, 200, 200));
+        }
+    }
+}
+
+type Stack = (Option<Color>, Vec<(Symbol, Color)>);
+
+/// Laid out cells regrouped for drawing with few calls: backgrounds merged into runs along rows
+/// and symbols grouped by color, so a backend changes colors once per color.
+#[derive(Default)]
+pub struct Batch {
+    pub backgrounds: BTreeMap<Color, Vec<Area>>,
+    pub symbols: BTreeMap<Color, Vec<(i32, i32, Symbol)>>
+}
+
+impl Batch {
+    /// Later cells are drawn over earlier ones, so a background hides what was below it.
+    pub fn new(cells: &[Cell]) -> Batch {
+        // Per cell, by row: the topmost background and the symbols drawn over it
+        let mut stacks: BTreeMap<(i32, i32), Stack> = BTreeMap::new();
+        for cell in cells.iter().filter(|cell| cell.x >= 0 && cell.y >= 0) {
+            let stack = stacks.entry((cell.y, cell.x)).or_default();
+            if cell.background.is_some() {
+                *stack = (cell.background, vec![]);
+            }
+            if cell.symbol != Symbol::Char(' ') {
+                stack.1.push((cell.symbol, cell.color));
+            }
+        }
+
+        let mut batch = Batch::default();
+        for ((y, x), (background, symbols)) in stacks {
+            if let Some(background) = background {
+                let runs = batch.backgrounds.entry(background).or_default();
+                match runs.last_mut() {
+                    Some(run) if run.y == y && run.x + run.width as i32 == x => run.width += 1,
+                    _ => runs.push(Area::new(x, y, 1, 1))
+                }
+            }
+            for (symbol, color) in symbols {
+                batch.symbols.entry(color).or_default().push((x, y, symbol));
+            }
+        }
+        batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol_at(cells: &[Cell], x: i32, y: i32) -> Option<Symbol> {
+        cells.iter().rev().find(|cell| cell.x == x && cell.y == y).map(|cell| cell.symbol)
+    }
+
+    #[test]
+    fn centered_panel_follows_screen_size() {
+        let panel = Widget::panel(None, (0, 0, 0), Widget::Column(vec![
+            Widget::label("Hello", (255, 255, 255)),
+            Widget::Button { id: "ok", text: "OK".to_string(), color: (0, 255, 0) }
+        ], Align::Center));
+        assert_eq!(panel.size(), (Some(7), Some(4)));
+        assert_eq!(Widget::Row(vec![Widget::Spacer(18)]).size(), (Some(18), Some(0)));
+
+        for (width, height) in [(20, 10), (41, 23)] {
+            let mut ui = Ui::default();
+            ui.layout_centered(&panel, Area::new(0, 0, width, height));
+            let cells = ui.take_cells();
+            let (left, top) = (((width - 7) / 2) as i32, ((height - 4) / 2) as i32);
+
+            assert_eq!(symbol_at(&cells, left, top), Some(Symbol::Char('┌')));
+            assert_eq!(symbol_at(&cells, left + 6, top + 3), Some(Symbol::Char('┘')));
+            assert_eq!(symbol_at(&cells, left + 1, top + 1), Some(Symbol::Char('H')));
+            assert_eq!(ui.hit(left + 3, top + 2), Some(Hit { id: "ok", index: 0 }));
+            assert_eq!(ui.hit(left + 1, top + 2), None);
+        }
+    }
+
+    #[test]
+    fn scroll_view_clamps_offset() {
+        let lines = (0..10).map(|i| (i.to_string(), (255, 255, 255))).collect();
+        let view = Widget::ScrollView { id: "log", lines };
+        let mut ui = Ui::default();
+
+        ui.scroll("log", 100);
+        ui.layout(&view, Area::new(0, 0, 5, 4));
+        let cells = ui.take_cells();
+
+        assert_eq!(symbol_at(&cells, 0, 0), Some(Symbol::Char('6')));
+        assert_eq!(symbol_at(&cells, 4, 3), Some(Symbol::Char('█')));
+        assert_eq!(ui.hit(1, 2), Some(Hit { id: "log", index: 8 }));
+
+        ui.scroll("log", -10);
+        ui.layout(&view, Area::new(0, 0, 5, 4));
+        assert_eq!(symbol_at(&ui.take_cells(), 0, 0), Some(Symbol::Char('0')));
+    }
+
+    #[test]
+    fn batch_merges_backgrounds_and_keeps_what_is_on_top() {
+        let mut ui = Ui::default();
+        ui.layout(&Widget::panel(None, (0, 0, 0), Widget::label("ab", (255, 255, 255))), Area::new(0, 0, 4, 3));
+        ui.layout(&Widget::Panel { title: None, border: false, background: Some((9, 9, 9)), child: Box::new(Widget::Fill) }, Area::new(2, 1, 1, 1));
+        let batch = Batch::new(&ui.take_cells());
+
+        assert_eq!(batch.backgrounds[&(0, 0, 0)], [Area::new(0, 0, 4, 1), Area::new(0, 1, 2, 1), Area::new(3, 1, 1, 1), Area::new(0, 2, 4, 1)]);
+        assert_eq!(batch.backgrounds[&(9, 9, 9)], [Area::new(2, 1, 1, 1)]);
+        assert_eq!(batch.symbols[&(255, 255, 255)], [(1, 1, Symbol::Char('a'))]);
+        assert_eq!(batch.symbols[&(100, 100, 100)].len(), 10);
+    }
+}